@@ -4,6 +4,7 @@ use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const BLOCK_SZ: usize = 512;
 
@@ -29,6 +30,14 @@ impl BlockDevice for BlockFile {
     }
 }
 
+/// Timestamps of the packed files come from the host.
+fn host_clock() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
+
 fn main() {
     easy_fs_pack().expect("Error when packing easy-fs!");
 }
@@ -64,6 +73,7 @@ fn easy_fs_pack() -> std::io::Result<()> {
     })));
     // 16MiB, at most 4095 files
     let efs = EasyFileSystem::create(block_file, 16 * 2048, 1);
+    efs.lock().set_clock(host_clock);
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
@@ -104,7 +114,9 @@ fn efs_test() -> std::io::Result<()> {
     })));
     EasyFileSystem::create(block_file.clone(), 4096, 1);
     let efs = EasyFileSystem::open(block_file.clone());
+    efs.lock().set_clock(host_clock);
     let root_inode = EasyFileSystem::root_inode(&efs);
+    let before = host_clock();
     root_inode.create("filea");
     root_inode.create("fileb");
     for name in root_inode.ls() {
@@ -117,6 +129,11 @@ fn efs_test() -> std::io::Result<()> {
     let mut buffer = [0u8; 233];
     let len = filea.read_at(0, &mut buffer);
     assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap(),);
+    let times = filea.times();
+    assert!(times.mtime >= before && times.mtime <= host_clock());
+    assert_eq!(times.ctime, times.mtime);
+    assert!(times.atime >= times.mtime);
+    assert!(root_inode.times().mtime >= before);
 
     let mut random_str_test = |len: usize| {
         filea.clear();
@@ -12,6 +12,13 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// wall-clock time in seconds since the Unix epoch, for inode timestamps
+    clock: fn() -> u32,
+}
+
+/// Before `set_clock` all timestamps are the epoch.
+fn no_clock() -> u32 {
+    0
 }
 
 type DataBlock = [u8; BLOCK_SZ];
@@ -41,6 +48,7 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            clock: no_clock,
         };
         // clear all blocks
         for i in 0..total_blocks {
@@ -72,7 +80,7 @@ impl EasyFileSystem {
         get_block_cache(root_inode_block_id as usize, Arc::clone(&block_device))
             .lock()
             .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
-                disk_inode.initialize(DiskInodeType::Directory);
+                disk_inode.initialize(DiskInodeType::Directory, efs.now());
             });
         block_cache_sync_all();
         Arc::new(Mutex::new(efs))
@@ -83,7 +91,10 @@ impl EasyFileSystem {
         get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
+                assert!(
+                    super_block.is_valid(),
+                    "Error loading EFS, the image may be of an older layout!"
+                );
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
                 let efs = Self {
@@ -95,11 +106,21 @@ impl EasyFileSystem {
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    clock: no_clock,
                 };
                 Arc::new(Mutex::new(efs))
             })
     }
 
+    /// Set the source of timestamps, the root inode created before keeps the epoch.
+    pub fn set_clock(&mut self, clock: fn() -> u32) {
+        self.clock = clock;
+    }
+
+    pub fn now(&self) -> u32 {
+        (self.clock)()
+    }
+
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
         let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
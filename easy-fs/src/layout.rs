@@ -3,8 +3,9 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
 
-const EFS_MAGIC: u32 = 0x3b800001;
-const INODE_DIRECT_COUNT: usize = 28;
+/// changed with the layout of `DiskInode`, so that older images are rejected
+const EFS_MAGIC: u32 = 0x3b800002;
+const INODE_DIRECT_COUNT: usize = 25;
 const NAME_LENGTH_LIMIT: usize = 27;
 const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
@@ -73,18 +74,32 @@ pub struct DiskInode {
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// last access, in seconds since the Unix epoch
+    pub atime: u32,
+    /// last modification of the data
+    pub mtime: u32,
+    /// last change of the data or the inode
+    pub ctime: u32,
     type_: DiskInodeType,
 }
 
 impl DiskInode {
     /// indirect1 and indirect2 block are allocated only when they are needed.
-    pub fn initialize(&mut self, type_: DiskInodeType) {
+    pub fn initialize(&mut self, type_: DiskInodeType, now: u32) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.atime = now;
+        self.mtime = now;
+        self.ctime = now;
         self.type_ = type_;
     }
+    /// Called when the data is modified.
+    pub fn touch(&mut self, now: u32) {
+        self.mtime = now;
+        self.ctime = now;
+    }
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
     }
@@ -407,3 +422,14 @@ impl DirEntry {
         self.inode_number
     }
 }
+
+/// Timestamps of an inode, in seconds since the Unix epoch.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InodeTimes {
+    pub atime: u32,
+    pub mtime: u32,
+    pub ctime: u32,
+}
+
+// the inode area is an array of 4 inodes per block
+const _: () = assert!(core::mem::size_of::<DiskInode>() == 128);
@@ -1,6 +1,6 @@
 use super::{
     block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    EasyFileSystem, InodeTimes, DIRENT_SZ,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
@@ -110,7 +110,7 @@ impl Inode {
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(DiskInodeType::File, fs.now());
             });
         self.modify_disk_inode(|root_inode| {
             // append file in the dirent
@@ -125,6 +125,7 @@ impl Inode {
                 dirent.as_bytes(),
                 &self.block_device,
             );
+            root_inode.touch(fs.now());
         });
 
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
@@ -156,8 +157,22 @@ impl Inode {
         })
     }
 
-    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+    pub fn times(&self) -> InodeTimes {
         let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| InodeTimes {
+            atime: disk_inode.atime,
+            mtime: disk_inode.mtime,
+            ctime: disk_inode.ctime,
+        })
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let fs = self.fs.lock();
+        let now = fs.now();
+        // dirty the inode block at most once a second
+        if self.read_disk_inode(|disk_inode| disk_inode.atime != now) {
+            self.modify_disk_inode(|disk_inode| disk_inode.atime = now);
+        }
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
@@ -165,6 +180,7 @@ impl Inode {
         let mut fs = self.fs.lock();
         let size = self.modify_disk_inode(|disk_inode| {
             self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
+            disk_inode.touch(fs.now());
             disk_inode.write_at(offset, buf, &self.block_device)
         });
         block_cache_sync_all();
@@ -180,6 +196,7 @@ impl Inode {
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
+            disk_inode.touch(fs.now());
         });
         block_cache_sync_all();
     }
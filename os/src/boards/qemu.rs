@@ -9,9 +9,11 @@ pub const MMIO: &[(usize, usize)] = &[
 
 pub type BlockDeviceImpl = crate::drivers::block::VirtIOBlock;
 pub type CharDeviceImpl = crate::drivers::chardev::NS16550a<VIRT_UART>;
+pub type RtcDeviceImpl = crate::drivers::rtc::GoldfishRtc<VIRT_RTC>;
 
 pub const VIRT_PLIC: usize = 0xC00_0000;
 pub const VIRT_UART: usize = 0x1000_0000;
+pub const VIRT_RTC: usize = 0x0010_1000;
 #[allow(unused)]
 pub const VIRTGPU_XRES: u32 = 1280;
 #[allow(unused)]
@@ -20,20 +22,34 @@ pub const VIRTGPU_YRES: u32 = 800;
 use crate::drivers::block::BLOCK_DEVICE;
 use crate::drivers::chardev::{CharDevice, UART};
 use crate::drivers::plic::{IntrTargetPriority, PLIC};
-use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE};
+use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE, NET_DEVICE};
+use crate::net::net_poll;
+use crate::smp::hart_id;
+use crate::task::schedule_work;
+
+//irq nums: 4 net, 5 keyboard, 6 mouse, 8 block, 10 uart
+const IRQ_SOURCES: [usize; 5] = [4, 5, 6, 8, 10];
 
 pub fn device_init() {
+    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
+    for intr_src_id in IRQ_SOURCES {
+        plic.set_priority(intr_src_id, 1);
+    }
+    device_init_hart();
+}
+
+/// Route the device interrupts to the supervisor context of current hart,
+/// the first hart claiming an interrupt handles it.
+pub fn device_init_hart() {
     use riscv::register::sie;
     let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let hart_id: usize = 0;
+    let hart_id = hart_id();
     let supervisor = IntrTargetPriority::Supervisor;
     let machine = IntrTargetPriority::Machine;
     plic.set_threshold(hart_id, supervisor, 0);
     plic.set_threshold(hart_id, machine, 1);
-    //irq nums: 5 keyboard, 6 mouse, 8 block, 10 uart
-    for intr_src_id in [5usize, 6, 8, 10] {
+    for intr_src_id in IRQ_SOURCES {
         plic.enable(hart_id, supervisor, intr_src_id);
-        plic.set_priority(intr_src_id, 1);
     }
     unsafe {
         sie::set_sext();
@@ -42,13 +58,26 @@ pub fn device_init() {
 
 pub fn irq_handler() {
     let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let intr_src_id = plic.claim(0, IntrTargetPriority::Supervisor);
+    let hart_id = hart_id();
+    let intr_src_id = plic.claim(hart_id, IntrTargetPriority::Supervisor);
     match intr_src_id {
-        5 => KEYBOARD_DEVICE.handle_irq(),
-        6 => MOUSE_DEVICE.handle_irq(),
+        // already claimed by another hart
+        0 => return,
+        4 => {
+            NET_DEVICE.handle_irq();
+            schedule_work(net_poll);
+        }
+        5 => {
+            KEYBOARD_DEVICE.handle_irq();
+            schedule_work(|| KEYBOARD_DEVICE.handle_events());
+        }
+        6 => {
+            MOUSE_DEVICE.handle_irq();
+            schedule_work(|| MOUSE_DEVICE.handle_events());
+        }
         8 => BLOCK_DEVICE.handle_irq(),
         10 => UART.handle_irq(),
         _ => panic!("unsupported IRQ {}", intr_src_id),
     }
-    plic.complete(0, IntrTargetPriority::Supervisor, intr_src_id);
+    plic.complete(hart_id, IntrTargetPriority::Supervisor, intr_src_id);
 }
@@ -1,13 +1,24 @@
 #[allow(unused)]
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+pub const USER_STACK_MAX_SIZE: usize = 4096 * 16;
+/// thread-local block at the top of every user stack, `tp` points to it
+pub const USER_TLS_SIZE: usize = 256;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-pub const KERNEL_HEAP_SIZE: usize = 0x100_0000;
+/// a kernel stack and the guard below it span a power of two, as `__alltraps_k` relies on
+pub const KERNEL_STACK_GUARD_SIZE: usize = 4096 * 2;
+pub const STACK_GUARD_SIZE: usize = 4096;
+pub const KERNEL_HEAP_INIT_SIZE: usize = 0x4_0000;
+pub const KERNEL_HEAP_GROW_SIZE: usize = 0x4_0000;
 pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// harts with larger ids are not started
+pub const MAX_HARTS: usize = 4;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+/// user mappings stay in the lower half of Sv39, the trampoline and trap contexts are above
+pub const USER_SPACE_END: usize = 1 << 38;
 
 pub use crate::board::{CLOCK_FREQ, MMIO};
@@ -1,6 +1,9 @@
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
+use crate::smp::hart_id;
 use core::fmt::{self, Write};
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 struct Stdout;
 
@@ -13,8 +16,28 @@ impl Write for Stdout {
     }
 }
 
+const NO_OWNER: usize = usize::MAX;
+
+/// Hart printing at the moment, so that the lines of different harts do
+/// not interleave. A hart printing again while holding it, e.g. when
+/// panicking in the middle of a print, goes on without waiting.
+static PRINT_OWNER: AtomicUsize = AtomicUsize::new(NO_OWNER);
+
 pub fn print(args: fmt::Arguments) {
+    let hart = hart_id();
+    let reentered = PRINT_OWNER.load(Ordering::Relaxed) == hart;
+    if !reentered {
+        while PRINT_OWNER
+            .compare_exchange_weak(NO_OWNER, hart, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+    }
     Stdout.write_fmt(args).unwrap();
+    if !reentered {
+        PRINT_OWNER.store(NO_OWNER, Ordering::Release);
+    }
 }
 
 #[macro_export]
@@ -1,6 +1,6 @@
 use super::BlockDevice;
 use crate::drivers::bus::virtio::VirtioHal;
-use crate::sync::{Condvar, UPIntrFreeCell};
+use crate::sync::{Condvar, SpinNoIrqLock};
 use crate::task::schedule;
 use crate::DEV_NON_BLOCKING_ACCESS;
 use alloc::collections::BTreeMap;
@@ -10,7 +10,7 @@ use virtio_drivers::{BlkResp, RespStatus, VirtIOBlk, VirtIOHeader};
 const VIRTIO0: usize = 0x10008000;
 
 pub struct VirtIOBlock {
-    virtio_blk: UPIntrFreeCell<VirtIOBlk<'static, VirtioHal>>,
+    virtio_blk: SpinNoIrqLock<VirtIOBlk<'static, VirtioHal>>,
     condvars: BTreeMap<u16, Condvar>,
 }
 
@@ -68,11 +68,9 @@ impl BlockDevice for VirtIOBlock {
 
 impl VirtIOBlock {
     pub fn new() -> Self {
-        let virtio_blk = unsafe {
-            UPIntrFreeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
-            )
-        };
+        let virtio_blk = SpinNoIrqLock::new(
+            VirtIOBlk::<VirtioHal>::new(unsafe { &mut *(VIRTIO0 as *mut VirtIOHeader) }).unwrap(),
+        );
         let mut condvars = BTreeMap::new();
         let channels = virtio_blk.exclusive_access().virt_queue_size();
         for i in 0..channels {
@@ -1,37 +1,22 @@
 use crate::mm::{
-    frame_alloc_more, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
-    StepByOne, VirtAddr,
+    frame_alloc_contiguous, frame_dealloc_contiguous, kernel_token, PageTable, PhysAddr,
+    PhysPageNum, VirtAddr,
 };
-use crate::sync::UPIntrFreeCell;
-use alloc::vec::Vec;
-use lazy_static::*;
 use virtio_drivers::Hal;
 
-lazy_static! {
-    static ref QUEUE_FRAMES: UPIntrFreeCell<Vec<FrameTracker>> =
-        unsafe { UPIntrFreeCell::new(Vec::new()) };
-}
-
 pub struct VirtioHal;
 
 impl Hal for VirtioHal {
     fn dma_alloc(pages: usize) -> usize {
-        let trakcers = frame_alloc_more(pages);
-        let ppn_base = trakcers.as_ref().unwrap().last().unwrap().ppn;
-        QUEUE_FRAMES
-            .exclusive_access()
-            .append(&mut trakcers.unwrap());
+        let ppn_base = frame_alloc_contiguous(pages).unwrap();
         let pa: PhysAddr = ppn_base.into();
         pa.0
     }
 
     fn dma_dealloc(pa: usize, pages: usize) -> i32 {
         let pa = PhysAddr::from(pa);
-        let mut ppn_base: PhysPageNum = pa.into();
-        for _ in 0..pages {
-            frame_dealloc(ppn_base);
-            ppn_base.step();
-        }
+        let ppn_base: PhysPageNum = pa.into();
+        frame_dealloc_contiguous(ppn_base, pages);
         0
     }
 
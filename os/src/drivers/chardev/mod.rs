@@ -1,9 +1,11 @@
 mod ns16550a;
+mod tty;
 
 use crate::board::CharDeviceImpl;
 use alloc::sync::Arc;
 use lazy_static::*;
 pub use ns16550a::NS16550a;
+pub use tty::TTY;
 
 pub trait CharDevice {
     fn init(&self);
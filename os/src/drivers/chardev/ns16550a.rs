@@ -1,10 +1,12 @@
 ///! Ref: https://www.lammertbies.nl/comm/info/serial-uart
 ///! Ref: ns16550a datasheet: https://datasheetspdf.com/pdf-file/605590/NationalSemiconductor/NS16550A/1
 ///! Ref: ns16450 datasheet: https://datasheetspdf.com/pdf-file/1311818/NationalSemiconductor/NS16450/1
-use super::CharDevice;
-use crate::sync::{Condvar, UPIntrFreeCell};
+use super::{CharDevice, TTY};
+use crate::fs::{PollQueue, PollWaiter};
+use crate::sync::{Condvar, SpinNoIrqLock};
 use crate::task::schedule;
 use alloc::collections::VecDeque;
+use alloc::sync::Arc;
 use bitflags::*;
 use volatile::{ReadOnly, Volatile, WriteOnly};
 
@@ -125,8 +127,9 @@ struct NS16550aInner {
 }
 
 pub struct NS16550a<const BASE_ADDR: usize> {
-    inner: UPIntrFreeCell<NS16550aInner>,
+    inner: SpinNoIrqLock<NS16550aInner>,
     condvar: Condvar,
+    pollers: PollQueue,
 }
 
 impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
@@ -137,8 +140,9 @@ impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
         };
         //inner.ns16550a.init();
         Self {
-            inner: unsafe { UPIntrFreeCell::new(inner) },
+            inner: SpinNoIrqLock::new(inner),
             condvar: Condvar::new(),
+            pollers: PollQueue::new(),
         }
     }
 
@@ -146,6 +150,17 @@ impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
         self.inner
             .exclusive_session(|inner| inner.read_buffer.is_empty())
     }
+
+    /// Take a byte received if there is one, without blocking.
+    pub fn try_read(&self) -> Option<u8> {
+        self.inner
+            .exclusive_session(|inner| inner.read_buffer.pop_front())
+    }
+
+    /// Wake `waiter` up when some bytes come.
+    pub fn register_poll(&self, waiter: &Arc<PollWaiter>) {
+        self.pollers.register(waiter);
+    }
 }
 
 impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
@@ -173,14 +188,17 @@ impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
     }
     fn handle_irq(&self) {
         let mut count = 0;
-        self.inner.exclusive_session(|inner| {
-            while let Some(ch) = inner.ns16550a.read() {
+        while let Some(ch) = self.inner.exclusive_session(|inner| inner.ns16550a.read()) {
+            // the line discipline may consume the byte and echo, so the device is not borrowed
+            if TTY.receive(ch) {
                 count += 1;
-                inner.read_buffer.push_back(ch);
+                self.inner
+                    .exclusive_session(|inner| inner.read_buffer.push_back(ch));
             }
-        });
+        }
         if count > 0 {
             self.condvar.signal();
+            self.pollers.wake_all();
         }
     }
 }
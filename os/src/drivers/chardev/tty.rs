@@ -0,0 +1,402 @@
+//! Terminal line discipline of the console.
+//!
+//! Every byte received by the UART passes `Tty::receive` first, which
+//! turns the interrupt characters into signals for the foreground
+//! process group instead of handing them to the reader. The remaining
+//! bytes are read from the device by `Tty::read`, which edits lines in
+//! canonical mode and hands them over unchanged in raw mode.
+
+use super::{CharDevice, UART};
+use crate::errno::{Errno, SysResult};
+use crate::mm::{copy_from_user, copy_to_user, translated_ref, translated_refmut, UserBuffer};
+use crate::sync::SpinNoIrqLock;
+use crate::task::{current_process, current_user_token, pgid2processes, SignalFlags};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use bitflags::*;
+use lazy_static::*;
+
+pub const TCGETS: usize = 0x5401;
+pub const TCSETS: usize = 0x5402;
+/// set after output is drained, the same as `TCSETS` since output is synchronous
+pub const TCSETSW: usize = 0x5403;
+/// set after discarding pending input
+pub const TCSETSF: usize = 0x5404;
+pub const TIOCGPGRP: usize = 0x540f;
+pub const TIOCSPGRP: usize = 0x5410;
+pub const TIOCGWINSZ: usize = 0x5413;
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// Indices of the control characters in `Termios::c_cc`.
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+pub const NCCS: usize = 19;
+
+bitflags! {
+    pub struct InputFlags: u32 {
+        /// translate CR to NL
+        const ICRNL = 0o400;
+    }
+    pub struct LocalFlags: u32 {
+        /// generate signals for the interrupt characters
+        const ISIG = 0o1;
+        /// canonical mode
+        const ICANON = 0o2;
+        const ECHO = 0o10;
+        /// erase the character on screen for VERASE
+        const ECHOE = 0o20;
+        /// erase the line on screen for VKILL
+        const ECHOK = 0o40;
+    }
+}
+
+/// Layout shared with user space, the same as `struct termios` of Linux.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Termios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; NCCS],
+}
+
+impl Default for Termios {
+    fn default() -> Self {
+        let mut c_cc = [0; NCCS];
+        c_cc[VINTR] = 0x03; // ^C
+        c_cc[VQUIT] = 0x1c; // ^\
+        c_cc[VERASE] = 0x7f; // DEL
+        c_cc[VKILL] = 0x15; // ^U
+        c_cc[VEOF] = 0x04; // ^D
+        c_cc[VMIN] = 1;
+        c_cc[VSUSP] = 0x1a; // ^Z
+        Self {
+            c_iflag: InputFlags::ICRNL.bits(),
+            // output is written unchanged
+            c_oflag: 0,
+            c_cflag: 0,
+            c_lflag: (LocalFlags::ISIG
+                | LocalFlags::ICANON
+                | LocalFlags::ECHO
+                | LocalFlags::ECHOE
+                | LocalFlags::ECHOK)
+                .bits(),
+            c_line: 0,
+            c_cc,
+        }
+    }
+}
+
+impl Termios {
+    fn lflag(&self) -> LocalFlags {
+        LocalFlags::from_bits_truncate(self.c_lflag)
+    }
+    fn iflag(&self) -> InputFlags {
+        InputFlags::from_bits_truncate(self.c_iflag)
+    }
+    /// Whether `ch` is the enabled control character at `index`, 0 disables it.
+    fn is_control(&self, index: usize, ch: u8) -> bool {
+        self.c_cc[index] != 0 && self.c_cc[index] == ch
+    }
+}
+
+/// Layout shared with user space, the same as `struct winsize` of Linux.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WinSize {
+    pub ws_row: u16,
+    pub ws_col: u16,
+    pub ws_xpixel: u16,
+    pub ws_ypixel: u16,
+}
+
+impl Default for WinSize {
+    fn default() -> Self {
+        Self {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+}
+
+pub struct Tty {
+    inner: SpinNoIrqLock<TtyInner>,
+}
+
+struct TtyInner {
+    /// process group receiving the signals of interrupt characters
+    foreground: Option<usize>,
+    termios: Termios,
+    winsize: WinSize,
+    /// the line being edited in canonical mode
+    line: Vec<u8>,
+    /// finished lines not read yet
+    cooked: VecDeque<u8>,
+    /// end of file typed before the reader comes
+    eof: bool,
+}
+
+impl TtyInner {
+    fn echo(&self, bytes: &[u8]) {
+        if self.termios.lflag().contains(LocalFlags::ECHO) {
+            for &ch in bytes {
+                UART.write(ch);
+            }
+        }
+    }
+    fn flush_input(&mut self) {
+        self.line.clear();
+        self.cooked.clear();
+        self.eof = false;
+    }
+    fn translate(&self, ch: u8) -> u8 {
+        if ch == b'\r' && self.termios.iflag().contains(InputFlags::ICRNL) {
+            b'\n'
+        } else {
+            ch
+        }
+    }
+    /// Apply canonical editing to `ch`, return false on end of file at the start of a line.
+    fn edit(&mut self, ch: u8) -> bool {
+        let termios = self.termios;
+        let lflag = termios.lflag();
+        // backspace is accepted as well since terminals disagree on the erase key
+        if termios.is_control(VERASE, ch) || ch == 0x08 {
+            if self.line.pop().is_some() && lflag.contains(LocalFlags::ECHOE) {
+                self.echo(b"\x08 \x08");
+            }
+        } else if termios.is_control(VKILL, ch) {
+            if lflag.contains(LocalFlags::ECHOK) {
+                for _ in 0..self.line.len() {
+                    self.echo(b"\x08 \x08");
+                }
+            }
+            self.line.clear();
+        } else if termios.is_control(VEOF, ch) {
+            if self.line.is_empty() {
+                return false;
+            }
+            // the line is passed to the reader without a terminator
+            self.cooked.extend(self.line.drain(..));
+        } else if ch == b'\n' {
+            self.echo(b"\n");
+            self.line.push(ch);
+            self.cooked.extend(self.line.drain(..));
+        } else {
+            self.echo(&[ch]);
+            self.line.push(ch);
+        }
+        true
+    }
+}
+
+impl Tty {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(TtyInner {
+                foreground: None,
+                termios: Termios::default(),
+                winsize: WinSize::default(),
+                line: Vec::new(),
+                cooked: VecDeque::new(),
+                eof: false,
+            }),
+        }
+    }
+    pub fn foreground(&self) -> Option<usize> {
+        self.inner.exclusive_access().foreground
+    }
+    /// Make `pgid` the foreground process group, it must be in the session of current process.
+    pub fn set_foreground(&self, pgid: usize) -> bool {
+        let sid = current_process().inner_exclusive_access().sid;
+        let processes = pgid2processes(pgid);
+        if processes.is_empty()
+            || processes
+                .iter()
+                .any(|process| process.inner_exclusive_access().sid != sid)
+        {
+            return false;
+        }
+        self.inner.exclusive_access().foreground = Some(pgid);
+        true
+    }
+    /// Handle a byte from the device, return whether it should be passed to readers.
+    pub fn receive(&self, ch: u8) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let termios = inner.termios;
+        if !termios.lflag().contains(LocalFlags::ISIG) {
+            return true;
+        }
+        let (signal, echo) = if termios.is_control(VINTR, ch) {
+            (SignalFlags::SIGINT, "^C\n")
+        } else if termios.is_control(VQUIT, ch) {
+            (SignalFlags::SIGQUIT, "^\\\n")
+        } else if termios.is_control(VSUSP, ch) {
+            (SignalFlags::SIGTSTP, "^Z\n")
+        } else {
+            return true;
+        };
+        let pgid = match inner.foreground {
+            Some(pgid) => pgid,
+            None => return true,
+        };
+        inner.echo(echo.as_bytes());
+        inner.flush_input();
+        drop(inner);
+        for process in pgid2processes(pgid) {
+            process.inner_exclusive_access().add_signal(signal);
+        }
+        false
+    }
+    /// Whether a read returns without blocking, the bytes received are
+    /// edited first in canonical mode to tell if a line is finished.
+    pub fn has_input(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.termios.lflag().contains(LocalFlags::ICANON) {
+            drop(inner);
+            return !UART.read_buffer_is_empty();
+        }
+        while inner.cooked.is_empty() && !inner.eof {
+            match UART.try_read() {
+                Some(ch) => {
+                    let ch = inner.translate(ch);
+                    inner.eof = !inner.edit(ch);
+                }
+                None => break,
+            }
+        }
+        !inner.cooked.is_empty() || inner.eof
+    }
+    /// Read into `user_buf`, a line at most in canonical mode and at least `VMIN` bytes in raw mode.
+    /// A `nonblock` read returns what there is instead of waiting for more.
+    pub fn read(&self, user_buf: UserBuffer, nonblock: bool) -> usize {
+        let len = user_buf.len();
+        if len == 0 {
+            return 0;
+        }
+        let mut buf_iter = user_buf.into_iter();
+        let mut read_size = 0usize;
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let canonical = inner.termios.lflag().contains(LocalFlags::ICANON);
+            if canonical {
+                while read_size < len {
+                    let ch = match inner.cooked.pop_front() {
+                        Some(ch) => ch,
+                        None => break,
+                    };
+                    unsafe {
+                        *buf_iter.next().unwrap() = ch;
+                    }
+                    read_size += 1;
+                    if ch == b'\n' {
+                        break;
+                    }
+                }
+                if read_size > 0 {
+                    return read_size;
+                }
+                if inner.eof {
+                    inner.eof = false;
+                    return 0;
+                }
+            } else {
+                let min = (inner.termios.c_cc[VMIN] as usize).clamp(1, len);
+                if read_size >= min {
+                    return read_size;
+                }
+            }
+            drop(inner);
+            // block on the device without holding the tty
+            let ch = if nonblock {
+                match UART.try_read() {
+                    Some(ch) => ch,
+                    None => return read_size,
+                }
+            } else {
+                UART.read()
+            };
+            let mut inner = self.inner.exclusive_access();
+            let ch = inner.translate(ch);
+            // the mode may have changed while blocked
+            if inner.termios.lflag().contains(LocalFlags::ICANON) {
+                if !inner.edit(ch) {
+                    return 0;
+                }
+            } else {
+                inner.echo(&[ch]);
+                unsafe {
+                    *buf_iter.next().unwrap() = ch;
+                }
+                read_size += 1;
+            }
+        }
+    }
+    /// Handle ioctls of stdin and stdout, `arg` points to user memory.
+    pub fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        let token = current_user_token();
+        match cmd {
+            TCGETS => {
+                let termios = self.inner.exclusive_access().termios;
+                copy_to_user(token, arg as *mut Termios, &termios)?;
+            }
+            TCSETS | TCSETSW | TCSETSF => {
+                let termios = copy_from_user(token, arg as *const Termios)?;
+                let mut inner = self.inner.exclusive_access();
+                if cmd == TCSETSF {
+                    inner.flush_input();
+                }
+                // a partial line becomes readable when leaving canonical mode
+                if !termios.lflag().contains(LocalFlags::ICANON) {
+                    let line: Vec<u8> = inner.line.drain(..).collect();
+                    inner.cooked.extend(line);
+                }
+                inner.termios = termios;
+            }
+            TIOCGPGRP => {
+                let pgid = self.foreground().ok_or(Errno::ENOTTY)?;
+                *translated_refmut(token, arg as *mut usize)? = pgid;
+            }
+            TIOCSPGRP => {
+                let pgid = *translated_ref(token, arg as *const usize)?;
+                if !self.set_foreground(pgid) {
+                    return Err(Errno::EPERM);
+                }
+            }
+            TIOCGWINSZ => {
+                let winsize = self.inner.exclusive_access().winsize;
+                copy_to_user(token, arg as *mut WinSize, &winsize)?;
+            }
+            TIOCSWINSZ => {
+                let winsize = copy_from_user(token, arg as *const WinSize)?;
+                let mut inner = self.inner.exclusive_access();
+                let changed = inner.winsize != winsize;
+                inner.winsize = winsize;
+                let foreground = inner.foreground;
+                drop(inner);
+                if let (true, Some(pgid)) = (changed, foreground) {
+                    for process in pgid2processes(pgid) {
+                        process
+                            .inner_exclusive_access()
+                            .add_signal(SignalFlags::SIGWINCH);
+                    }
+                }
+            }
+            _ => return Err(Errno::ENOTTY),
+        }
+        Ok(0)
+    }
+}
+
+lazy_static! {
+    pub static ref TTY: Tty = Tty::new();
+}
@@ -1,5 +1,6 @@
 use crate::drivers::bus::virtio::VirtioHal;
-use crate::sync::{Condvar, UPIntrFreeCell};
+use crate::fs::{PollQueue, PollWaiter};
+use crate::sync::{Condvar, SpinNoIrqLock};
 use crate::task::schedule;
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
@@ -15,14 +16,19 @@ struct VirtIOInputInner {
 }
 
 struct VirtIOInputWrapper {
-    inner: UPIntrFreeCell<VirtIOInputInner>,
+    inner: SpinNoIrqLock<VirtIOInputInner>,
     condvar: Condvar,
+    pollers: PollQueue,
 }
 
 pub trait InputDevice: Send + Sync + Any {
     fn read_event(&self) -> u64;
+    /// Acknowledge the interrupt, the events are decoded by `handle_events` later.
     fn handle_irq(&self);
+    fn handle_events(&self);
     fn is_empty(&self) -> bool;
+    /// Wake `waiter` up when events come.
+    fn register_poll(&self, waiter: &Arc<PollWaiter>);
 }
 
 lazy_static::lazy_static!(
@@ -39,8 +45,9 @@ impl VirtIOInputWrapper {
             events: VecDeque::new(),
         };
         Self {
-            inner: unsafe { UPIntrFreeCell::new(inner) },
+            inner: SpinNoIrqLock::new(inner),
             condvar: Condvar::new(),
+            pollers: PollQueue::new(),
         }
     }
 }
@@ -50,6 +57,10 @@ impl InputDevice for VirtIOInputWrapper {
         self.inner.exclusive_access().events.is_empty()
     }
 
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) {
+        self.pollers.register(waiter);
+    }
+
     fn read_event(&self) -> u64 {
         loop {
             let mut inner = self.inner.exclusive_access();
@@ -64,10 +75,14 @@ impl InputDevice for VirtIOInputWrapper {
     }
 
     fn handle_irq(&self) {
+        self.inner
+            .exclusive_session(|inner| inner.virtio_input.ack_interrupt());
+    }
+
+    fn handle_events(&self) {
         let mut count = 0;
         let mut result = 0;
         self.inner.exclusive_session(|inner| {
-            inner.virtio_input.ack_interrupt();
             while let Some(event) = inner.virtio_input.pop_pending_event() {
                 count += 1;
                 result = (event.event_type as u64) << 48
@@ -78,6 +93,7 @@ impl InputDevice for VirtIOInputWrapper {
         });
         if count > 0 {
             self.condvar.signal();
+            self.pollers.wake_all();
         };
     }
 }
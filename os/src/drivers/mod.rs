@@ -5,6 +5,7 @@ pub mod gpu;
 pub mod input;
 pub mod net;
 pub mod plic;
+pub mod rtc;
 
 pub use block::BLOCK_DEVICE;
 pub use bus::*;
@@ -12,3 +13,4 @@ pub use chardev::UART;
 pub use gpu::*;
 pub use input::*;
 pub use net::*;
+pub use rtc::RTC;
@@ -1,7 +1,7 @@
 use core::any::Any;
 
 use crate::drivers::virtio::VirtioHal;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
 use alloc::sync::Arc;
 use lazy_static::*;
 use virtio_drivers::{VirtIOHeader, VirtIONet};
@@ -14,10 +14,15 @@ lazy_static! {
 
 pub trait NetDevice: Send + Sync + Any {
     fn transmit(&self, data: &[u8]);
+    /// Wait for a packet.
     fn receive(&self, data: &mut [u8]) -> usize;
+    /// Whether a packet has been received and `receive` returns at once.
+    fn can_receive(&self) -> bool;
+    /// Acknowledge the interrupt, packets are handled later by `net_poll`.
+    fn handle_irq(&self);
 }
 
-pub struct VirtIONetWrapper(UPIntrFreeCell<VirtIONet<'static, VirtioHal>>);
+pub struct VirtIONetWrapper(SpinNoIrqLock<VirtIONet<'static, VirtioHal>>);
 
 impl NetDevice for VirtIONetWrapper {
     fn transmit(&self, data: &[u8]) {
@@ -33,6 +38,14 @@ impl NetDevice for VirtIONetWrapper {
             .recv(data)
             .expect("can't receive data")
     }
+
+    fn can_receive(&self) -> bool {
+        self.0.exclusive_access().can_recv()
+    }
+
+    fn handle_irq(&self) {
+        self.0.exclusive_access().ack_interrupt();
+    }
 }
 
 impl VirtIONetWrapper {
@@ -40,7 +53,7 @@ impl VirtIONetWrapper {
         unsafe {
             let virtio = VirtIONet::<VirtioHal>::new(&mut *(VIRTIO8 as *mut VirtIOHeader))
                 .expect("can't create net device by virtio");
-            VirtIONetWrapper(UPIntrFreeCell::new(virtio))
+            VirtIONetWrapper(SpinNoIrqLock::new(virtio))
         }
     }
 }
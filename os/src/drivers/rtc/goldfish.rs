@@ -0,0 +1,52 @@
+///! Ref: https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT
+use super::RtcDevice;
+use crate::sync::SpinNoIrqLock;
+use volatile::Volatile;
+
+#[repr(C)]
+#[allow(dead_code)]
+struct GoldfishRtcRegs {
+    /// reading latches the high half, writing sets the low half of the time
+    time_low: Volatile<u32>,
+    time_high: Volatile<u32>,
+    alarm_low: Volatile<u32>,
+    alarm_high: Volatile<u32>,
+    irq_enabled: Volatile<u32>,
+    clear_alarm: Volatile<u32>,
+    alarm_status: Volatile<u32>,
+    clear_interrupt: Volatile<u32>,
+}
+
+pub struct GoldfishRtc<const BASE_ADDR: usize> {
+    /// the halves of the time must be accessed together
+    lock: SpinNoIrqLock<()>,
+}
+
+impl<const BASE_ADDR: usize> GoldfishRtc<BASE_ADDR> {
+    pub fn new() -> Self {
+        Self {
+            lock: SpinNoIrqLock::new(()),
+        }
+    }
+
+    fn regs(&self) -> &mut GoldfishRtcRegs {
+        unsafe { &mut *(BASE_ADDR as *mut GoldfishRtcRegs) }
+    }
+}
+
+impl<const BASE_ADDR: usize> RtcDevice for GoldfishRtc<BASE_ADDR> {
+    fn read_ns(&self) -> u64 {
+        let _guard = self.lock.exclusive_access();
+        let regs = self.regs();
+        let low = regs.time_low.read() as u64;
+        let high = regs.time_high.read() as u64;
+        high << 32 | low
+    }
+
+    fn set_ns(&self, ns: u64) {
+        let _guard = self.lock.exclusive_access();
+        let regs = self.regs();
+        regs.time_high.write((ns >> 32) as u32);
+        regs.time_low.write(ns as u32);
+    }
+}
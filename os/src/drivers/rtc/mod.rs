@@ -0,0 +1,17 @@
+mod goldfish;
+
+use crate::board::RtcDeviceImpl;
+use alloc::sync::Arc;
+pub use goldfish::GoldfishRtc;
+use lazy_static::*;
+
+/// A battery-backed wall clock.
+pub trait RtcDevice {
+    /// Nanoseconds since the Unix epoch.
+    fn read_ns(&self) -> u64;
+    fn set_ns(&self, ns: u64);
+}
+
+lazy_static! {
+    pub static ref RTC: Arc<RtcDeviceImpl> = Arc::new(RtcDeviceImpl::new());
+}
@@ -1,12 +1,32 @@
     .section .text.entry
     .globl _start
 _start:
+    # a0: hart id, kept in tp while running in kernel
+    mv tp, a0
+    # each hart runs on its own boot stack
     la sp, boot_stack_top
+    li t0, 4096 * 16
+    mul t0, t0, a0
+    sub sp, sp, t0
     call rust_main
 
+    .globl _start_secondary
+_start_secondary:
+    mv tp, a0
+    la sp, boot_stack_top
+    li t0, 4096 * 16
+    mul t0, t0, a0
+    sub sp, sp, t0
+    call rust_main_secondary
+
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    # BOOT_STACK_SIZE * MAX_HARTS
+    .space 4096 * 16 * 4
     .globl boot_stack_top
 boot_stack_top:
+    .globl emergency_stack_lower_bound
+emergency_stack_lower_bound:
+    # EMERGENCY_STACK_SIZE * MAX_HARTS, used once a kernel stack overflows
+    .space 4096 * 4 * 4
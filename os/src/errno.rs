@@ -0,0 +1,83 @@
+//! Error numbers of syscalls.
+//!
+//! A syscall returns its result, which is never negative unless it is a
+//! pointer or a saved register, or an error number negated. The numbers
+//! are the same as Linux, so that user programs can tell the errors apart.
+
+/// The same as the error numbers of Linux.
+#[repr(isize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Errno {
+    /// operation not permitted
+    EPERM = 1,
+    /// no such file or directory
+    ENOENT = 2,
+    /// no such process
+    ESRCH = 3,
+    /// interrupted, the process is exiting
+    EINTR = 4,
+    /// argument list too long
+    E2BIG = 7,
+    /// exec format error
+    ENOEXEC = 8,
+    /// bad file descriptor
+    EBADF = 9,
+    /// no child processes
+    ECHILD = 10,
+    /// try again, the operation would block
+    EAGAIN = 11,
+    /// out of memory
+    ENOMEM = 12,
+    /// bad address
+    EFAULT = 14,
+    /// device or resource busy
+    EBUSY = 16,
+    /// file exists
+    EEXIST = 17,
+    /// invalid argument
+    EINVAL = 22,
+    /// too many open files
+    EMFILE = 24,
+    /// not a typewriter
+    ENOTTY = 25,
+    /// no space left on device
+    ENOSPC = 28,
+    /// broken pipe
+    EPIPE = 32,
+    /// resource deadlock would occur
+    EDEADLK = 35,
+    /// file name too long
+    ENAMETOOLONG = 36,
+    /// function not implemented
+    ENOSYS = 38,
+    /// socket operation on non-socket
+    ENOTSOCK = 88,
+    /// message too long
+    EMSGSIZE = 90,
+    /// protocol wrong type for socket
+    EPROTOTYPE = 91,
+    /// operation not supported
+    EOPNOTSUPP = 95,
+    /// address family not supported
+    EAFNOSUPPORT = 97,
+    /// address already in use
+    EADDRINUSE = 98,
+    /// transport endpoint is already connected
+    EISCONN = 106,
+    /// transport endpoint is not connected
+    ENOTCONN = 107,
+    /// connection timed out
+    ETIMEDOUT = 110,
+    /// connection refused
+    ECONNREFUSED = 111,
+}
+
+/// The value of a successful syscall, or why it failed.
+pub type SysResult = Result<isize, Errno>;
+
+impl Errno {
+    /// The negated number returned to user space.
+    pub fn as_ret(self) -> isize {
+        -(self as isize)
+    }
+}
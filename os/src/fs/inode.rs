@@ -1,17 +1,20 @@
 use super::File;
 use crate::drivers::BLOCK_DEVICE;
+use crate::errno::Errno;
 use crate::mm::UserBuffer;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
+use crate::task::schedule_work;
+use crate::timer::{add_oneshot_timer, get_time, realtime, us_to_cycles};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
+use easy_fs::{block_cache_sync_all, EasyFileSystem, Inode};
 use lazy_static::*;
 
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPIntrFreeCell<OSInodeInner>,
+    inner: SpinNoIrqLock<OSInodeInner>,
 }
 
 pub struct OSInodeInner {
@@ -24,7 +27,7 @@ impl OSInode {
         Self {
             readable,
             writable,
-            inner: unsafe { UPIntrFreeCell::new(OSInodeInner { offset: 0, inode }) },
+            inner: SpinNoIrqLock::new(OSInodeInner { offset: 0, inode }),
         }
     }
     pub fn read_all(&self) -> Vec<u8> {
@@ -46,10 +49,30 @@ impl OSInode {
 lazy_static! {
     pub static ref ROOT_INODE: Arc<Inode> = {
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+        efs.lock().set_clock(fs_clock);
         Arc::new(EasyFileSystem::root_inode(&efs))
     };
 }
 
+/// Inode timestamps are wall-clock seconds.
+fn fs_clock() -> u32 {
+    realtime().sec as u32
+}
+
+/// Interval of writing dirty blocks back, in microseconds.
+const FLUSH_INTERVAL_US: usize = 1_000_000;
+
+/// Write the block cache back every `FLUSH_INTERVAL_US` from the system work
+/// queue, since it blocks on the device.
+pub fn start_cache_flush() {
+    add_oneshot_timer(get_time() + us_to_cycles(FLUSH_INTERVAL_US), || {
+        schedule_work(|| {
+            block_cache_sync_all();
+            start_cache_flush();
+        })
+    });
+}
+
 pub fn list_apps() {
     println!("/**** APPS ****");
     for app in ROOT_INODE.ls() {
@@ -65,6 +88,9 @@ bitflags! {
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        /// the fd is closed by `exec`
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -72,12 +98,12 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
@@ -112,7 +138,7 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, Errno> {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
@@ -123,9 +149,9 @@ impl File for OSInode {
             inner.offset += read_size;
             total_read_size += read_size;
         }
-        total_read_size
+        Ok(total_read_size)
     }
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
         let mut inner = self.inner.exclusive_access();
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
@@ -134,6 +160,6 @@ impl File for OSInode {
             inner.offset += write_size;
             total_write_size += write_size;
         }
-        total_write_size
+        Ok(total_write_size)
     }
 }
@@ -0,0 +1,77 @@
+//! The events of the keyboard and the mouse as a file.
+
+use super::{File, PollEvents, PollWaiter};
+use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE};
+use crate::errno::Errno;
+use crate::mm::UserBuffer;
+use crate::task::current_process_exited;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+/// Reads whole events of `u64` in the format of `event_get`, blocking
+/// until there is one.
+pub struct InputEvents;
+
+impl InputEvents {
+    fn pop_event(&self) -> Option<u64> {
+        [&*KEYBOARD_DEVICE, &*MOUSE_DEVICE]
+            .iter()
+            .find(|device| !device.is_empty())
+            .map(|device| device.read_event())
+    }
+}
+
+impl File for InputEvents {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let max_events = buf.len() / size_of::<u64>();
+        if max_events == 0 {
+            return Err(Errno::EINVAL);
+        }
+        let mut events = Vec::new();
+        while events.is_empty() {
+            let waiter = PollWaiter::new();
+            self.register_poll(&waiter);
+            while events.len() < max_events {
+                match self.pop_event() {
+                    Some(event) => events.push(event),
+                    None => break,
+                }
+            }
+            if events.is_empty() {
+                waiter.wait(None);
+                if current_process_exited() {
+                    return Err(Errno::EINTR);
+                }
+            }
+        }
+        let bytes = events.iter().flat_map(|event| event.to_le_bytes());
+        for (byte_ref, byte) in buf.into_iter().zip(bytes) {
+            unsafe {
+                *byte_ref = byte;
+            }
+        }
+        Ok(events.len() * size_of::<u64>())
+    }
+    fn write(&self, _buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
+    }
+    fn poll(&self) -> PollEvents {
+        if !KEYBOARD_DEVICE.is_empty() || !MOUSE_DEVICE.is_empty() {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        KEYBOARD_DEVICE.register_poll(waiter);
+        MOUSE_DEVICE.register_poll(waiter);
+        true
+    }
+}
@@ -1,16 +1,68 @@
 mod inode;
+mod input;
+mod mqueue;
 mod pipe;
+mod poll;
 mod stdio;
+mod unix;
 
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
+use alloc::sync::Arc;
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// Read into `buf`, the number of bytes read or why nothing could be.
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno>;
+    /// Write from `buf`, the number of bytes written or why nothing could be.
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno>;
+    /// Device specific control, `arg` is usually a pointer to user memory.
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> SysResult {
+        Err(Errno::ENOTTY)
+    }
+    /// Readiness of the file, readable and writable files are always ready
+    /// unless they tell otherwise.
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        if self.readable() {
+            events |= PollEvents::POLLIN;
+        }
+        if self.writable() {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    /// Whether reads and writes fail with `EAGAIN` instead of blocking.
+    fn nonblock(&self) -> bool {
+        false
+    }
+    /// Ignored by files which never block.
+    fn set_nonblock(&self, _nonblock: bool) {}
+    /// Wake `waiter` up once when the readiness may change, return false if
+    /// the file cannot tell and has to be polled again later.
+    fn register_poll(&self, _waiter: &Arc<PollWaiter>) -> bool {
+        true
+    }
+    /// The epoll instance behind the file, if it is one.
+    fn epoll(&self) -> Option<&EpollFile> {
+        None
+    }
+    /// The message queue behind the file, if it is one.
+    fn mqueue(&self) -> Option<&MqFile> {
+        None
+    }
+    /// The unix domain socket behind the file, if it is one.
+    fn unix_socket(&self) -> Option<&UnixSocket> {
+        None
+    }
 }
 
-pub use inode::{list_apps, open_file, OSInode, OpenFlags, ROOT_INODE};
+pub use inode::{list_apps, open_file, start_cache_flush, OSInode, OpenFlags, ROOT_INODE};
+pub use input::InputEvents;
+pub use mqueue::{mq_open, mq_unlink, MqAttr, MqFile};
 pub use pipe::{make_pipe, Pipe};
+pub use poll::{poll_files, EpollEvent, EpollFile, PollEvents, PollFd, PollQueue, PollWaiter};
+pub use poll::{EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD};
 pub use stdio::{Stdin, Stdout};
+pub use unix::{MsgHdr, SocketKind, UnixSocket, AF_UNIX, SCM_MAX_FD};
@@ -0,0 +1,261 @@
+//! POSIX style message queues.
+//!
+//! A queue is named by a string starting with '/' and lives in `MQUEUES`
+//! until it is unlinked, its messages are kept while some descriptor of it
+//! is still open.
+
+use super::{File, PollEvents, PollQueue, PollWaiter};
+use crate::errno::Errno;
+use crate::mm::UserBuffer;
+use crate::sync::SpinNoIrqLock;
+use crate::task::{block_current_task, current_process_exited, current_task, schedule};
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// Priorities of messages are below it.
+pub const MQ_PRIO_MAX: usize = 32;
+pub const MQ_MAXMSG_DEFAULT: usize = 10;
+pub const MQ_MSGSIZE_DEFAULT: usize = 64;
+const MQ_MAXMSG_LIMIT: usize = 64;
+const MQ_MSGSIZE_LIMIT: usize = 4096;
+
+/// Same layout as the user `MqAttr`.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MqAttr {
+    pub flags: usize,
+    pub max_msg: usize,
+    pub msg_size: usize,
+    pub cur_msgs: usize,
+}
+
+pub struct MessageQueue {
+    max_msg: usize,
+    msg_size: usize,
+    inner: SpinNoIrqLock<MessageQueueInner>,
+}
+
+struct MessageQueueInner {
+    /// FIFO queues keyed by priority, the highest priority goes first
+    messages: BTreeMap<usize, VecDeque<Vec<u8>>>,
+    len: usize,
+    /// all woken up on a change, the first one finding it useful goes on
+    receivers: VecDeque<Arc<TaskControlBlock>>,
+    senders: VecDeque<Arc<TaskControlBlock>>,
+    pollers: PollQueue,
+}
+
+impl MessageQueue {
+    fn new(max_msg: usize, msg_size: usize) -> Self {
+        Self {
+            max_msg,
+            msg_size,
+            inner: SpinNoIrqLock::new(MessageQueueInner {
+                messages: BTreeMap::new(),
+                len: 0,
+                receivers: VecDeque::new(),
+                senders: VecDeque::new(),
+                pollers: PollQueue::new(),
+            }),
+        }
+    }
+}
+
+lazy_static! {
+    static ref MQUEUES: SpinNoIrqLock<BTreeMap<String, Arc<MessageQueue>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Open the queue `name`, creating it with `attr` (or the default limits)
+/// if `create` is set and it does not exist yet.
+pub fn mq_open(
+    name: String,
+    create: bool,
+    attr: Option<MqAttr>,
+) -> Result<Arc<MessageQueue>, Errno> {
+    if !name.starts_with('/') || name.len() == 1 {
+        return Err(Errno::EINVAL);
+    }
+    let mut mqueues = MQUEUES.exclusive_access();
+    if let Some(queue) = mqueues.get(&name) {
+        return Ok(Arc::clone(queue));
+    }
+    if !create {
+        return Err(Errno::ENOENT);
+    }
+    let (max_msg, msg_size) = attr.map_or((MQ_MAXMSG_DEFAULT, MQ_MSGSIZE_DEFAULT), |attr| {
+        (attr.max_msg, attr.msg_size)
+    });
+    if !(1..=MQ_MAXMSG_LIMIT).contains(&max_msg) || !(1..=MQ_MSGSIZE_LIMIT).contains(&msg_size) {
+        return Err(Errno::EINVAL);
+    }
+    let queue = Arc::new(MessageQueue::new(max_msg, msg_size));
+    mqueues.insert(name, Arc::clone(&queue));
+    Ok(queue)
+}
+
+/// Remove the name of a queue, return false if there is no such queue.
+pub fn mq_unlink(name: &str) -> bool {
+    MQUEUES.exclusive_access().remove(name).is_some()
+}
+
+/// An open descriptor of a message queue.
+pub struct MqFile {
+    readable: bool,
+    writable: bool,
+    nonblock: AtomicBool,
+    queue: Arc<MessageQueue>,
+}
+
+impl MqFile {
+    pub fn new(readable: bool, writable: bool, nonblock: bool, queue: Arc<MessageQueue>) -> Self {
+        Self {
+            readable,
+            writable,
+            nonblock: AtomicBool::new(nonblock),
+            queue,
+        }
+    }
+
+    pub fn attr(&self) -> MqAttr {
+        MqAttr {
+            flags: self.nonblock() as usize,
+            max_msg: self.queue.max_msg,
+            msg_size: self.queue.msg_size,
+            cur_msgs: self.queue.inner.exclusive_access().len,
+        }
+    }
+
+    /// Queue the message in `buf` with priority `prio`, blocking while the
+    /// queue is full. Fail with `EAGAIN` instead of blocking if opened
+    /// non-blocking.
+    pub fn send(&self, buf: UserBuffer, prio: usize) -> Result<(), Errno> {
+        if !self.writable {
+            return Err(Errno::EBADF);
+        }
+        if buf.len() > self.queue.msg_size {
+            return Err(Errno::EMSGSIZE);
+        }
+        if prio >= MQ_PRIO_MAX {
+            return Err(Errno::EINVAL);
+        }
+        let mut inner = self.queue.inner.exclusive_access();
+        while inner.len == self.queue.max_msg {
+            if self.nonblock() {
+                return Err(Errno::EAGAIN);
+            }
+            inner.senders.push_back(current_task().unwrap());
+            let task_cx_ptr = block_current_task();
+            drop(inner);
+            schedule(task_cx_ptr);
+            if current_process_exited() {
+                return Err(Errno::EINTR);
+            }
+            inner = self.queue.inner.exclusive_access();
+        }
+        let message: Vec<u8> = buf.into_iter().map(|byte| unsafe { *byte }).collect();
+        inner
+            .messages
+            .entry(prio)
+            .or_insert_with(VecDeque::new)
+            .push_back(message);
+        inner.len += 1;
+        for task in inner.receivers.drain(..) {
+            wakeup_task(task);
+        }
+        inner.pollers.wake_all();
+        Ok(())
+    }
+
+    /// Take the oldest message of the highest priority into `buf`, blocking
+    /// while the queue is empty. Return its length and priority, or fail with
+    /// `EAGAIN` instead of blocking if opened non-blocking.
+    pub fn receive(&self, buf: UserBuffer) -> Result<(usize, usize), Errno> {
+        if !self.readable {
+            return Err(Errno::EBADF);
+        }
+        if buf.len() < self.queue.msg_size {
+            return Err(Errno::EMSGSIZE);
+        }
+        let mut inner = self.queue.inner.exclusive_access();
+        while inner.len == 0 {
+            if self.nonblock() {
+                return Err(Errno::EAGAIN);
+            }
+            inner.receivers.push_back(current_task().unwrap());
+            let task_cx_ptr = block_current_task();
+            drop(inner);
+            schedule(task_cx_ptr);
+            if current_process_exited() {
+                return Err(Errno::EINTR);
+            }
+            inner = self.queue.inner.exclusive_access();
+        }
+        let (prio, messages) = inner.messages.iter_mut().next_back().unwrap();
+        let prio = *prio;
+        let message = messages.pop_front().unwrap();
+        if messages.is_empty() {
+            inner.messages.remove(&prio);
+        }
+        inner.len -= 1;
+        for task in inner.senders.drain(..) {
+            wakeup_task(task);
+        }
+        inner.pollers.wake_all();
+        drop(inner);
+        for (byte_ref, byte) in buf.into_iter().zip(message.iter()) {
+            unsafe {
+                *byte_ref = *byte;
+            }
+        }
+        Ok((message.len(), prio))
+    }
+}
+
+impl File for MqFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    /// Receive a message, its priority is dropped.
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        self.receive(buf).map(|(len, _)| len)
+    }
+    /// Send a message with the lowest priority.
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let len = buf.len();
+        self.send(buf, 0)?;
+        Ok(len)
+    }
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+    fn poll(&self) -> PollEvents {
+        let len = self.queue.inner.exclusive_access().len;
+        let mut events = PollEvents::empty();
+        if self.readable && len > 0 {
+            events |= PollEvents::POLLIN;
+        }
+        if self.writable && len < self.queue.max_msg {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        self.queue.inner.exclusive_access().pollers.register(waiter);
+        true
+    }
+    fn mqueue(&self) -> Option<&MqFile> {
+        Some(self)
+    }
+}
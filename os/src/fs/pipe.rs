@@ -1,28 +1,33 @@
-use super::File;
+use super::{File, PollEvents, PollQueue, PollWaiter};
+use crate::errno::Errno;
 use crate::mm::UserBuffer;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
 use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use crate::task::suspend_current_and_run_next;
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>,
+    nonblock: AtomicBool,
+    buffer: Arc<SpinNoIrqLock<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<SpinNoIrqLock<PipeRingBuffer>>) -> Self {
         Self {
             readable: true,
             writable: false,
+            nonblock: AtomicBool::new(false),
             buffer,
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<SpinNoIrqLock<PipeRingBuffer>>) -> Self {
         Self {
             readable: false,
             writable: true,
+            nonblock: AtomicBool::new(false),
             buffer,
         }
     }
@@ -43,6 +48,7 @@ pub struct PipeRingBuffer {
     tail: usize,
     status: RingBufferStatus,
     write_end: Option<Weak<Pipe>>,
+    pollers: PollQueue,
 }
 
 impl PipeRingBuffer {
@@ -53,6 +59,7 @@ impl PipeRingBuffer {
             tail: 0,
             status: RingBufferStatus::Empty,
             write_end: None,
+            pollers: PollQueue::new(),
         }
     }
     pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
@@ -98,7 +105,7 @@ impl PipeRingBuffer {
 
 /// Return (read_end, write_end)
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPIntrFreeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(SpinNoIrqLock::new(PipeRingBuffer::new()));
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
     buffer.exclusive_access().set_write_end(&write_end);
@@ -112,7 +119,7 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: UserBuffer) -> usize {
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
         assert!(self.readable());
         let want_to_read = buf.len();
         let mut buf_iter = buf.into_iter();
@@ -121,13 +128,16 @@ impl File for Pipe {
             let mut ring_buffer = self.buffer.exclusive_access();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return already_read;
+                // a non-blocking read takes what there is
+                if ring_buffer.all_write_ends_closed() || self.nonblock() {
+                    return Ok(already_read);
                 }
                 drop(ring_buffer);
                 suspend_current_and_run_next();
                 continue;
             }
+            // there is room for writers
+            ring_buffer.pollers.wake_all();
             for _ in 0..loop_read {
                 if let Some(byte_ref) = buf_iter.next() {
                     unsafe {
@@ -135,15 +145,15 @@ impl File for Pipe {
                     }
                     already_read += 1;
                     if already_read == want_to_read {
-                        return want_to_read;
+                        return Ok(want_to_read);
                     }
                 } else {
-                    return already_read;
+                    return Ok(already_read);
                 }
             }
         }
     }
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
         assert!(self.writable());
         let want_to_write = buf.len();
         let mut buf_iter = buf.into_iter();
@@ -152,22 +162,62 @@ impl File for Pipe {
             let mut ring_buffer = self.buffer.exclusive_access();
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
+                if self.nonblock() {
+                    return Ok(already_write);
+                }
                 drop(ring_buffer);
                 suspend_current_and_run_next();
                 continue;
             }
+            // there is data for readers
+            ring_buffer.pollers.wake_all();
             // write at most loop_write bytes
             for _ in 0..loop_write {
                 if let Some(byte_ref) = buf_iter.next() {
                     ring_buffer.write_byte(unsafe { *byte_ref });
                     already_write += 1;
                     if already_write == want_to_write {
-                        return want_to_write;
+                        return Ok(want_to_write);
                     }
                 } else {
-                    return already_write;
+                    return Ok(already_write);
                 }
             }
         }
     }
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+    fn poll(&self) -> PollEvents {
+        let ring_buffer = self.buffer.exclusive_access();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if ring_buffer.available_read() > 0 {
+                events |= PollEvents::POLLIN;
+            }
+            if ring_buffer.all_write_ends_closed() {
+                events |= PollEvents::POLLHUP;
+            }
+        }
+        if self.writable && ring_buffer.available_write() > 0 {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        self.buffer.exclusive_access().pollers.register(waiter);
+        true
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // readers find the write end closed
+        if self.writable {
+            self.buffer.exclusive_access().pollers.wake_all();
+        }
+    }
 }
@@ -0,0 +1,298 @@
+//! Waiting on the readiness of several files.
+//!
+//! A poller registers one `PollWaiter` on the `PollQueue` of every file it
+//! waits on, checks their readiness again and blocks. A file wakes up all
+//! waiters registered when its readiness may have changed, the waiters are
+//! registered again on the next round. Files which cannot wake waiters up
+//! are checked again every `POLL_INTERVAL_MS`.
+
+use super::File;
+use crate::config::CLOCK_FREQ;
+use crate::errno::Errno;
+use crate::mm::UserBuffer;
+use crate::sync::SpinNoIrqLock;
+use crate::task::{block_current_task, current_process_exited, current_task, schedule};
+use crate::task::{wakeup_task, TaskControlBlock};
+use crate::timer::{add_timer_callback, cancel_timer, get_time};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use bitflags::*;
+
+const POLL_INTERVAL_MS: usize = 10;
+
+bitflags! {
+    /// The same as the `poll` events of Linux.
+    pub struct PollEvents: u16 {
+        const POLLIN = 0x001;
+        const POLLPRI = 0x002;
+        const POLLOUT = 0x004;
+        const POLLERR = 0x008;
+        const POLLHUP = 0x010;
+        const POLLNVAL = 0x020;
+    }
+}
+
+impl PollEvents {
+    /// Reported whether asked for or not.
+    pub fn always() -> Self {
+        Self::POLLERR | Self::POLLHUP | Self::POLLNVAL
+    }
+}
+
+/// Same layout as `struct pollfd` of Linux.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+pub struct PollWaiter {
+    inner: SpinNoIrqLock<PollWaiterInner>,
+}
+
+struct PollWaiterInner {
+    /// set when blocked
+    task: Option<Arc<TaskControlBlock>>,
+    woken: bool,
+}
+
+impl PollWaiter {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinNoIrqLock::new(PollWaiterInner {
+                task: None,
+                woken: false,
+            }),
+        })
+    }
+
+    pub fn wake(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.woken = true;
+        if let Some(task) = inner.task.take() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Block until woken up or `expire` cycles, it does not block at all if
+    /// woken up since created.
+    pub(super) fn wait(self: &Arc<Self>, expire: Option<usize>) {
+        let task = current_task().unwrap();
+        let timer = expire.map(|expire| {
+            let waiter = Arc::clone(self);
+            add_timer_callback(expire, Arc::clone(&task), move || waiter.wake())
+        });
+        let mut inner = self.inner.exclusive_access();
+        if !inner.woken {
+            inner.task = Some(task);
+            let task_cx_ptr = block_current_task();
+            drop(inner);
+            schedule(task_cx_ptr);
+        } else {
+            drop(inner);
+        }
+        if let Some(timer) = timer {
+            cancel_timer(timer);
+        }
+    }
+}
+
+/// Waiters to wake up when the readiness of a file may have changed.
+pub struct PollQueue(SpinNoIrqLock<Vec<Weak<PollWaiter>>>);
+
+impl PollQueue {
+    pub fn new() -> Self {
+        Self(SpinNoIrqLock::new(Vec::new()))
+    }
+
+    pub fn register(&self, waiter: &Arc<PollWaiter>) {
+        let mut waiters = self.0.exclusive_access();
+        // drop those which have given up
+        waiters.retain(|waiter| waiter.strong_count() > 0);
+        waiters.push(Arc::downgrade(waiter));
+    }
+
+    pub fn wake_all(&self) {
+        let waiters: Vec<_> = self.0.exclusive_access().drain(..).collect();
+        for waiter in waiters.iter().filter_map(Weak::upgrade) {
+            waiter.wake();
+        }
+    }
+}
+
+/// Wait until one of `files` is ready for the events asked, or until
+/// `expire` cycles. Return the events of each file, which are all empty on
+/// timeout, or `None` if the process exits meanwhile. A closed fd comes as
+/// `None` and gets `POLLNVAL`.
+pub fn poll_files(
+    files: &[(Option<Arc<dyn File + Send + Sync>>, PollEvents)],
+    expire: Option<usize>,
+) -> Option<Vec<PollEvents>> {
+    loop {
+        let waiter = PollWaiter::new();
+        let mut notified = true;
+        for file in files.iter().filter_map(|(file, _)| file.as_ref()) {
+            notified &= file.register_poll(&waiter);
+        }
+        // checked after registering so that no change is missed
+        let revents: Vec<_> = files
+            .iter()
+            .map(|(file, events)| match file {
+                Some(file) => file.poll() & (*events | PollEvents::always()),
+                None => PollEvents::POLLNVAL,
+            })
+            .collect();
+        let now = get_time();
+        if revents.iter().any(|revents| !revents.is_empty())
+            || expire.map_or(false, |expire| now >= expire)
+        {
+            return Some(revents);
+        }
+        let deadline = if notified {
+            expire
+        } else {
+            let next_check = now + CLOCK_FREQ / 1000 * POLL_INTERVAL_MS;
+            Some(expire.map_or(next_check, |expire| expire.min(next_check)))
+        };
+        waiter.wait(deadline);
+        if current_process_exited() {
+            return None;
+        }
+    }
+}
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// Same layout as `struct epoll_event` of Linux on RISC-V.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// An interest list of files, which do not stay open for being in it.
+pub struct EpollFile {
+    interests: SpinNoIrqLock<BTreeMap<usize, (Weak<dyn File + Send + Sync>, EpollEvent)>>,
+}
+
+impl EpollFile {
+    pub fn new() -> Self {
+        Self {
+            interests: SpinNoIrqLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn ctl(
+        &self,
+        op: usize,
+        fd: usize,
+        file: &Arc<dyn File + Send + Sync>,
+        event: EpollEvent,
+    ) -> Result<(), Errno> {
+        let mut interests = self.interests.exclusive_access();
+        // an fd closed and reused is a new file
+        let present = interests
+            .get(&fd)
+            .and_then(|(interest, _)| interest.upgrade())
+            .map_or(false, |interest| Arc::ptr_eq(&interest, file));
+        match (op, present) {
+            (EPOLL_CTL_ADD, false) | (EPOLL_CTL_MOD, true) => {
+                interests.insert(fd, (Arc::downgrade(file), event));
+            }
+            (EPOLL_CTL_DEL, true) => {
+                interests.remove(&fd);
+            }
+            (EPOLL_CTL_ADD, true) => return Err(Errno::EEXIST),
+            (EPOLL_CTL_MOD | EPOLL_CTL_DEL, false) => return Err(Errno::ENOENT),
+            _ => return Err(Errno::EINVAL),
+        }
+        Ok(())
+    }
+
+    /// The files still open and the events asked for them.
+    fn files(&self) -> Vec<(Option<Arc<dyn File + Send + Sync>>, PollEvents, u64)> {
+        let mut interests = self.interests.exclusive_access();
+        interests.retain(|_, (file, _)| file.strong_count() > 0);
+        interests
+            .values()
+            .map(|(file, event)| {
+                (
+                    file.upgrade(),
+                    PollEvents::from_bits_truncate(event.events as u16),
+                    event.data,
+                )
+            })
+            .collect()
+    }
+
+    /// Wait until some files are ready or until `expire` cycles, return up
+    /// to `max_events` of them.
+    pub fn wait(&self, max_events: usize, expire: Option<usize>) -> Option<Vec<EpollEvent>> {
+        let interests = self.files();
+        let files: Vec<_> = interests
+            .iter()
+            .map(|(file, events, _)| (file.clone(), *events))
+            .collect();
+        let revents = poll_files(&files, expire)?;
+        Some(
+            revents
+                .iter()
+                .zip(interests.iter())
+                // a file closed meanwhile is not reported
+                .filter(|(revents, _)| {
+                    !revents.is_empty() && !revents.contains(PollEvents::POLLNVAL)
+                })
+                .take(max_events)
+                .map(|(revents, (_, _, data))| EpollEvent {
+                    events: revents.bits() as u32,
+                    data: *data,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl File for EpollFile {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, _buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+    fn write(&self, _buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+    /// Readable if some file in the interest list is ready.
+    fn poll(&self) -> PollEvents {
+        let ready = self.files().iter().any(|(file, events, _)| {
+            file.as_ref().map_or(false, |file| {
+                file.poll().intersects(*events | PollEvents::always())
+            })
+        });
+        if ready {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        self.files()
+            .iter()
+            .filter_map(|(file, _, _)| file.as_ref())
+            .fold(true, |notified, file| {
+                file.register_poll(waiter) && notified
+            })
+    }
+    fn epoll(&self) -> Option<&EpollFile> {
+        Some(self)
+    }
+}
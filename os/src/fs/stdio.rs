@@ -1,11 +1,23 @@
-use super::File;
-use crate::drivers::chardev::CharDevice;
-use crate::drivers::chardev::UART;
+use super::{File, PollEvents, PollWaiter};
+use crate::drivers::chardev::{TTY, UART};
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-pub struct Stdin;
+pub struct Stdin {
+    nonblock: AtomicBool,
+}
 pub struct Stdout;
 
+impl Stdin {
+    pub fn new() -> Self {
+        Self {
+            nonblock: AtomicBool::new(false),
+        }
+    }
+}
+
 impl File for Stdin {
     fn readable(&self) -> bool {
         true
@@ -13,18 +25,32 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
-        //println!("before UART.read() in Stdin::read()");
-        let ch = UART.read();
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
-        }
-        1
+    fn read(&self, user_buf: UserBuffer) -> Result<usize, Errno> {
+        Ok(TTY.read(user_buf, self.nonblock()))
     }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot write to stdin!");
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        TTY.ioctl(cmd, arg)
+    }
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+    fn poll(&self) -> PollEvents {
+        if TTY.has_input() {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        UART.register_poll(waiter);
+        true
+    }
 }
 
 impl File for Stdout {
@@ -34,13 +60,16 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot read from stdout!");
     }
-    fn write(&self, user_buf: UserBuffer) -> usize {
+    fn write(&self, user_buf: UserBuffer) -> Result<usize, Errno> {
         for buffer in user_buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*buffer).unwrap());
         }
-        user_buf.len()
+        Ok(user_buf.len())
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        TTY.ioctl(cmd, arg)
     }
 }
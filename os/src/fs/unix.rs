@@ -0,0 +1,577 @@
+//! Unix domain sockets.
+//!
+//! A bound socket is named by a file of easy-fs, `connect` and `sendto`
+//! look the name up in `UNIX_NAMES`. Data sent goes straight into the
+//! receive queue of the peer, which holds at most `UNIX_BUF_SIZE` bytes.
+//! Files may be attached to the data and are installed as new fds of the
+//! receiving process.
+
+use super::{File, PollEvents, PollQueue, PollWaiter, ROOT_INODE};
+use crate::errno::Errno;
+use crate::mm::UserBuffer;
+use crate::sync::{SpinNoIrqGuard, SpinNoIrqLock};
+use crate::task::{block_current_task, current_process_exited, current_task, schedule};
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::mem;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+pub const AF_UNIX: usize = 1;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+const UNIX_BUF_SIZE: usize = 4096;
+/// The name length limit of easy-fs.
+const UNIX_PATH_MAX: usize = 27;
+const BACKLOG_MAX: usize = 16;
+/// Files passed by one message at most.
+pub const SCM_MAX_FD: usize = 16;
+
+/// Same layout as the user `MsgHdr`, `fds_len` is the number of fds
+/// received on return of `recvmsg`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MsgHdr {
+    pub buf: *mut u8,
+    pub len: usize,
+    pub fds: *mut usize,
+    pub fds_len: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SocketKind {
+    Stream,
+    Datagram,
+}
+
+impl SocketKind {
+    pub fn from_type(type_: usize) -> Option<Self> {
+        match type_ {
+            SOCK_STREAM => Some(Self::Stream),
+            SOCK_DGRAM => Some(Self::Datagram),
+            _ => None,
+        }
+    }
+}
+
+struct Packet {
+    data: Vec<u8>,
+    /// bytes of `data` already read from a stream
+    offset: usize,
+    /// name of the sender of a datagram
+    from: Option<String>,
+    files: Vec<Arc<dyn File + Send + Sync>>,
+}
+
+enum SocketState {
+    Unconnected,
+    Listening {
+        backlog: VecDeque<Arc<UnixSocket>>,
+        max: usize,
+    },
+    Connected(Weak<UnixSocket>),
+}
+
+/// What a receive returns besides the data.
+pub struct Received {
+    pub len: usize,
+    pub from: Option<String>,
+    pub files: Vec<Arc<dyn File + Send + Sync>>,
+}
+
+pub struct UnixSocket {
+    kind: SocketKind,
+    me: Weak<UnixSocket>,
+    nonblock: AtomicBool,
+    /// shared with senders blocked on the queue, who do not keep the socket
+    inner: Arc<SpinNoIrqLock<UnixSocketInner>>,
+}
+
+struct UnixSocketInner {
+    state: SocketState,
+    name: Option<String>,
+    queue: VecDeque<Packet>,
+    /// bytes in `queue` not read yet
+    queued: usize,
+    /// the socket is dropped, there is no one to receive
+    closed: bool,
+    /// waiting for data or connections, all woken up on a change
+    readers: VecDeque<Arc<TaskControlBlock>>,
+    /// waiting for room in `queue`
+    writers: VecDeque<Arc<TaskControlBlock>>,
+    /// woken up along with `readers` and `writers`
+    pollers: PollQueue,
+}
+
+lazy_static! {
+    static ref UNIX_NAMES: SpinNoIrqLock<BTreeMap<String, Weak<UnixSocket>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+fn lookup(name: &str) -> Option<Arc<UnixSocket>> {
+    UNIX_NAMES
+        .exclusive_access()
+        .get(name)
+        .and_then(Weak::upgrade)
+}
+
+fn wake_all(waiters: &mut VecDeque<Arc<TaskControlBlock>>) {
+    for task in waiters.drain(..) {
+        wakeup_task(task);
+    }
+}
+
+/// Wait in the `readers` or `writers` of a socket, `guard` is released
+/// while blocked. Return false if the process exited meanwhile.
+fn wait(mut guard: SpinNoIrqGuard<UnixSocketInner>, writer: bool) -> bool {
+    let task = current_task().unwrap();
+    if writer {
+        guard.writers.push_back(task);
+    } else {
+        guard.readers.push_back(task);
+    }
+    let task_cx_ptr = block_current_task();
+    drop(guard);
+    schedule(task_cx_ptr);
+    !current_process_exited()
+}
+
+impl UnixSocket {
+    pub fn new(kind: SocketKind) -> Arc<Self> {
+        Arc::new_cyclic(|me| Self {
+            kind,
+            me: me.clone(),
+            nonblock: AtomicBool::new(false),
+            inner: Arc::new(SpinNoIrqLock::new(UnixSocketInner {
+                state: SocketState::Unconnected,
+                name: None,
+                queue: VecDeque::new(),
+                queued: 0,
+                closed: false,
+                readers: VecDeque::new(),
+                writers: VecDeque::new(),
+                pollers: PollQueue::new(),
+            })),
+        })
+    }
+
+    /// Return a pair of sockets connected to each other.
+    pub fn pair(kind: SocketKind) -> (Arc<Self>, Arc<Self>) {
+        let (a, b) = (Self::new(kind), Self::new(kind));
+        a.inner.exclusive_access().state = SocketState::Connected(Arc::downgrade(&b));
+        b.inner.exclusive_access().state = SocketState::Connected(Arc::downgrade(&a));
+        (a, b)
+    }
+
+    /// Name the socket by the file `name`, which is created if necessary.
+    pub fn bind(&self, name: String) -> Result<(), Errno> {
+        if name.is_empty() {
+            return Err(Errno::EINVAL);
+        }
+        if name.len() > UNIX_PATH_MAX {
+            return Err(Errno::ENAMETOOLONG);
+        }
+        let mut inner = self.inner.exclusive_access();
+        if inner.name.is_some() {
+            return Err(Errno::EINVAL);
+        }
+        let mut names = UNIX_NAMES.exclusive_access();
+        // the file is left behind by a socket closed before
+        if names
+            .get(&name)
+            .map_or(false, |socket| socket.strong_count() > 0)
+        {
+            return Err(Errno::EADDRINUSE);
+        }
+        if ROOT_INODE.find(&name).is_none() && ROOT_INODE.create(&name).is_none() {
+            return Err(Errno::ENOSPC);
+        }
+        names.insert(name.clone(), self.me.clone());
+        inner.name = Some(name);
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: usize) -> Result<(), Errno> {
+        let mut inner = self.inner.exclusive_access();
+        if self.kind != SocketKind::Stream {
+            return Err(Errno::EOPNOTSUPP);
+        }
+        match inner.state {
+            SocketState::Unconnected if inner.name.is_some() => {
+                inner.state = SocketState::Listening {
+                    backlog: VecDeque::new(),
+                    max: backlog.clamp(1, BACKLOG_MAX),
+                };
+                Ok(())
+            }
+            _ => Err(Errno::EINVAL),
+        }
+    }
+
+    /// Block until a connection comes, return the socket of this end, or
+    /// fail with `EAGAIN` instead of blocking if non-blocking.
+    pub fn accept(&self) -> Result<Arc<Self>, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        loop {
+            match &mut inner.state {
+                SocketState::Listening { backlog, .. } => {
+                    if let Some(socket) = backlog.pop_front() {
+                        return Ok(socket);
+                    }
+                }
+                _ => return Err(Errno::EINVAL),
+            }
+            if self.nonblock() {
+                return Err(Errno::EAGAIN);
+            }
+            if !wait(inner, false) {
+                return Err(Errno::EINTR);
+            }
+            inner = self.inner.exclusive_access();
+        }
+    }
+
+    /// Connect a stream to the listening socket `name`, or set the default
+    /// destination of a datagram socket.
+    pub fn connect(&self, name: &str) -> Result<(), Errno> {
+        let target = lookup(name).ok_or(Errno::ECONNREFUSED)?;
+        match self.inner.exclusive_access().state {
+            SocketState::Unconnected => {}
+            SocketState::Connected(_) => return Err(Errno::EISCONN),
+            SocketState::Listening { .. } => return Err(Errno::EINVAL),
+        }
+        if target.kind != self.kind {
+            return Err(Errno::EPROTOTYPE);
+        }
+        if self.kind == SocketKind::Datagram {
+            self.inner.exclusive_access().state = SocketState::Connected(Arc::downgrade(&target));
+            return Ok(());
+        }
+        let mut target_inner = target.inner.exclusive_access();
+        match &mut target_inner.state {
+            SocketState::Listening { backlog, max } if backlog.len() < *max => {
+                let server = Self::new(SocketKind::Stream);
+                server.inner.exclusive_access().state = SocketState::Connected(self.me.clone());
+                self.inner.exclusive_access().state =
+                    SocketState::Connected(Arc::downgrade(&server));
+                backlog.push_back(server);
+            }
+            _ => return Err(Errno::ECONNREFUSED),
+        }
+        wake_all(&mut target_inner.readers);
+        target_inner.pollers.wake_all();
+        Ok(())
+    }
+
+    fn peer(&self) -> Option<Arc<Self>> {
+        match &self.inner.exclusive_access().state {
+            SocketState::Connected(peer) => peer.upgrade(),
+            _ => None,
+        }
+    }
+
+    /// Why there is no peer, `EPIPE` if it is closed.
+    fn no_peer(&self) -> Errno {
+        match self.inner.exclusive_access().state {
+            SocketState::Connected(_) => Errno::EPIPE,
+            _ => Errno::ENOTCONN,
+        }
+    }
+
+    /// Senders keep only the queue while blocked, so that the socket can
+    /// be dropped meanwhile.
+    fn into_queue(self: Arc<Self>) -> Arc<SpinNoIrqLock<UnixSocketInner>> {
+        Arc::clone(&self.inner)
+    }
+
+    fn name(&self) -> Option<String> {
+        self.inner.exclusive_access().name.clone()
+    }
+
+    /// Send `buf` with `files` attached to the peer, or to the socket `to`
+    /// for a datagram. Return the bytes sent, a stream blocks until all of
+    /// them go into the queue of the peer, `EPIPE` if the peer is closed
+    /// before. A non-blocking socket returns the bytes which fit, or fails
+    /// with `EAGAIN` if none. Fail with `EINVAL` if a unix socket is among
+    /// `files`, sockets queued in each other would keep themselves alive.
+    pub fn send(
+        &self,
+        buf: UserBuffer,
+        to: Option<&str>,
+        files: Vec<Arc<dyn File + Send + Sync>>,
+    ) -> Result<usize, Errno> {
+        if files.iter().any(|file| file.unix_socket().is_some()) {
+            return Err(Errno::EINVAL);
+        }
+        let data: Vec<u8> = buf.into_iter().map(|byte| unsafe { *byte }).collect();
+        match self.kind {
+            SocketKind::Stream if to.is_none() => self.send_stream(data, files),
+            SocketKind::Datagram => self.send_datagram(data, to, files),
+            _ => Err(Errno::EISCONN),
+        }
+    }
+
+    fn send_stream(
+        &self,
+        data: Vec<u8>,
+        mut files: Vec<Arc<dyn File + Send + Sync>>,
+    ) -> Result<usize, Errno> {
+        // files go with bytes only
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let mut sent = 0;
+        // the bytes sent so far are reported instead of a later error
+        let partial = |sent: usize, err: Errno| if sent > 0 { Ok(sent) } else { Err(err) };
+        loop {
+            let peer_buf = match self.peer() {
+                Some(peer) => peer.into_queue(),
+                None => return partial(sent, self.no_peer()),
+            };
+            let mut peer_inner = peer_buf.exclusive_access();
+            if peer_inner.closed {
+                return partial(sent, Errno::EPIPE);
+            }
+            let room = UNIX_BUF_SIZE - peer_inner.queued;
+            if room == 0 {
+                if self.nonblock() {
+                    return partial(sent, Errno::EAGAIN);
+                }
+                if !wait(peer_inner, true) {
+                    return Err(Errno::EINTR);
+                }
+                continue;
+            }
+            let len = room.min(data.len() - sent);
+            peer_inner.queue.push_back(Packet {
+                data: data[sent..sent + len].to_vec(),
+                offset: 0,
+                from: None,
+                // go with the first byte
+                files: mem::take(&mut files),
+            });
+            peer_inner.queued += len;
+            sent += len;
+            wake_all(&mut peer_inner.readers);
+            peer_inner.pollers.wake_all();
+            if sent == data.len() {
+                return Ok(sent);
+            }
+        }
+    }
+
+    fn send_datagram(
+        &self,
+        data: Vec<u8>,
+        to: Option<&str>,
+        files: Vec<Arc<dyn File + Send + Sync>>,
+    ) -> Result<usize, Errno> {
+        if data.len() > UNIX_BUF_SIZE {
+            return Err(Errno::EMSGSIZE);
+        }
+        let from = self.name();
+        loop {
+            let peer_buf = match to {
+                Some(name) => lookup(name)
+                    .filter(|peer| peer.kind == SocketKind::Datagram)
+                    .ok_or(Errno::ECONNREFUSED)?,
+                None => self.peer().ok_or_else(|| self.no_peer())?,
+            }
+            .into_queue();
+            let mut peer_inner = peer_buf.exclusive_access();
+            if peer_inner.closed {
+                return Err(Errno::ECONNREFUSED);
+            }
+            if UNIX_BUF_SIZE - peer_inner.queued < data.len() {
+                if self.nonblock() {
+                    return Err(Errno::EAGAIN);
+                }
+                if !wait(peer_inner, true) {
+                    return Err(Errno::EINTR);
+                }
+                continue;
+            }
+            let len = data.len();
+            peer_inner.queue.push_back(Packet {
+                data,
+                offset: 0,
+                from,
+                files,
+            });
+            peer_inner.queued += len;
+            wake_all(&mut peer_inner.readers);
+            peer_inner.pollers.wake_all();
+            return Ok(len);
+        }
+    }
+
+    /// Block until there is data, a stream returns no data once the peer
+    /// is closed. Files come with the first byte they are attached to, a
+    /// read of a stream stops before the next bytes with files attached.
+    /// Fail with `EAGAIN` instead of blocking if non-blocking.
+    pub fn receive(&self, buf: UserBuffer) -> Result<Received, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        while inner.queue.is_empty() {
+            let connected = match &inner.state {
+                SocketState::Connected(peer) => peer.strong_count() > 0,
+                SocketState::Listening { .. } => return Err(Errno::EINVAL),
+                SocketState::Unconnected => false,
+            };
+            if self.kind == SocketKind::Stream && !connected {
+                return Ok(Received {
+                    len: 0,
+                    from: None,
+                    files: Vec::new(),
+                });
+            }
+            if self.nonblock() {
+                return Err(Errno::EAGAIN);
+            }
+            if !wait(inner, false) {
+                return Err(Errno::EINTR);
+            }
+            inner = self.inner.exclusive_access();
+        }
+        let mut buf_iter = buf.into_iter();
+        let mut received = Received {
+            len: 0,
+            from: None,
+            files: Vec::new(),
+        };
+        if self.kind == SocketKind::Datagram {
+            // the rest of a datagram is discarded
+            let packet = inner.queue.pop_front().unwrap();
+            inner.queued -= packet.data.len();
+            for (byte_ref, byte) in (&mut buf_iter).zip(packet.data.iter()) {
+                unsafe {
+                    *byte_ref = *byte;
+                }
+                received.len += 1;
+            }
+            received.from = packet.from;
+            received.files = packet.files;
+        } else {
+            while let Some(packet) = inner.queue.front_mut() {
+                if received.len > 0 && !packet.files.is_empty() {
+                    break;
+                }
+                received.files.append(&mut packet.files);
+                while packet.offset < packet.data.len() {
+                    match buf_iter.next() {
+                        Some(byte_ref) => unsafe {
+                            *byte_ref = packet.data[packet.offset];
+                        },
+                        None => break,
+                    }
+                    packet.offset += 1;
+                    received.len += 1;
+                }
+                if packet.offset < packet.data.len() {
+                    break;
+                }
+                inner.queue.pop_front();
+            }
+            inner.queued -= received.len;
+        }
+        wake_all(&mut inner.writers);
+        inner.pollers.wake_all();
+        Ok(received)
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.closed = true;
+        if let Some(name) = inner.name.take() {
+            let mut names = UNIX_NAMES.exclusive_access();
+            if names
+                .get(&name)
+                .map_or(false, |socket| socket.strong_count() == 0)
+            {
+                names.remove(&name);
+            }
+        }
+        // writers find the peer closed
+        wake_all(&mut inner.writers);
+        inner.pollers.wake_all();
+        // files and connections not taken yet are released with the lock free
+        let queue = mem::take(&mut inner.queue);
+        let state = mem::replace(&mut inner.state, SocketState::Unconnected);
+        drop(inner);
+        drop(queue);
+        if let SocketState::Connected(peer) = state {
+            if let Some(peer) = peer.upgrade() {
+                let mut peer_inner = peer.inner.exclusive_access();
+                wake_all(&mut peer_inner.readers);
+                peer_inner.pollers.wake_all();
+            }
+        }
+    }
+}
+
+impl File for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    /// Files attached are closed.
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        self.receive(buf).map(|received| received.len)
+    }
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        self.send(buf, None, Vec::new())
+    }
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+    /// A stream whose peer is closed reads the end of file.
+    fn poll(&self) -> PollEvents {
+        let inner = self.inner.exclusive_access();
+        let mut events = PollEvents::empty();
+        let hang_up = match &inner.state {
+            SocketState::Listening { backlog, .. } => {
+                if !backlog.is_empty() {
+                    events |= PollEvents::POLLIN;
+                }
+                return events;
+            }
+            SocketState::Connected(peer) => peer.strong_count() == 0,
+            SocketState::Unconnected => self.kind == SocketKind::Stream,
+        };
+        if !inner.queue.is_empty() {
+            events |= PollEvents::POLLIN;
+        }
+        drop(inner);
+        if hang_up {
+            return events | PollEvents::POLLIN | PollEvents::POLLHUP;
+        }
+        // a datagram socket without a peer sends by name
+        let room = self.peer().map_or(true, |peer| {
+            let peer_inner = peer.inner.exclusive_access();
+            !peer_inner.closed && peer_inner.queued < UNIX_BUF_SIZE
+        });
+        if room {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    fn register_poll(&self, waiter: &Arc<PollWaiter>) -> bool {
+        self.inner.exclusive_access().pollers.register(waiter);
+        if let Some(peer) = self.peer() {
+            peer.inner.exclusive_access().pollers.register(waiter);
+        }
+        true
+    }
+    fn unix_socket(&self) -> Option<&UnixSocket> {
+        Some(self)
+    }
+}
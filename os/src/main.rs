@@ -17,11 +17,13 @@ mod board;
 mod console;
 mod config;
 mod drivers;
+mod errno;
 mod fs;
 mod lang_items;
 mod mm;
 mod net;
 mod sbi;
+mod smp;
 mod sync;
 mod syscall;
 mod task;
@@ -30,6 +32,7 @@ mod trap;
 
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
+use crate::mm::KERNEL_SPACE;
 
 core::arch::global_asm!(include_str!("entry.asm"));
 
@@ -45,11 +48,10 @@ fn clear_bss() {
 }
 
 use lazy_static::*;
-use sync::UPIntrFreeCell;
+use sync::SpinNoIrqLock;
 
 lazy_static! {
-    pub static ref DEV_NON_BLOCKING_ACCESS: UPIntrFreeCell<bool> =
-        unsafe { UPIntrFreeCell::new(false) };
+    pub static ref DEV_NON_BLOCKING_ACCESS: SpinNoIrqLock<bool> = SpinNoIrqLock::new(false);
 }
 
 #[no_mangle]
@@ -66,11 +68,34 @@ pub fn rust_main() -> ! {
     println!("KERN: init trap");
     trap::init();
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
     timer::set_next_trigger();
     board::device_init();
+    timer::init_realtime();
     fs::list_apps();
     task::add_initproc();
+    task::init_workqueue();
+    fs::start_cache_flush();
     *DEV_NON_BLOCKING_ACCESS.exclusive_access() = true;
+    let harts = smp::start_secondary_harts();
+    println!(
+        "KERN: hart {} booted, {} more harts started",
+        smp::hart_id(),
+        harts
+    );
     task::run_tasks();
     panic!("Unreachable in rust_main!");
 }
+
+/// Entry of the harts started by `smp::start_secondary_harts`.
+#[no_mangle]
+pub fn rust_main_secondary() -> ! {
+    KERNEL_SPACE.exclusive_access().activate();
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    timer::set_next_trigger();
+    board::device_init_hart();
+    task::run_tasks();
+    panic!("Unreachable in rust_main_secondary!");
+}
@@ -1,6 +1,6 @@
 use super::{PhysAddr, PhysPageNum};
-use crate::config::MEMORY_END;
-use crate::sync::UPIntrFreeCell;
+use crate::config::{MEMORY_END, PAGE_SIZE};
+use crate::sync::SpinNoIrqLock;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -35,67 +35,223 @@ impl Drop for FrameTracker {
 trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
-    fn alloc_more(&mut self, pages: usize) -> Option<Vec<PhysPageNum>>;
+    fn alloc_contiguous(&mut self, pages: usize) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, pages: usize);
 }
 
-pub struct StackFrameAllocator {
-    current: usize,
+/// Blocks of up to `1 << (MAX_ORDER - 1)` pages (16 MiB) are managed,
+/// which also bounds the largest single kernel heap allocation.
+const MAX_ORDER: usize = 13;
+const NIL: usize = usize::MAX;
+/// State of a frame which is not the head of a free block.
+const NOT_FREE: u8 = u8::MAX;
+
+/// Counters of a zone, used for diagnostics.
+#[derive(Copy, Clone, Debug)]
+pub struct ZoneStats {
+    pub name: &'static str,
+    pub total: usize,
+    pub free: usize,
+    pub used: usize,
+}
+
+/// Links of a free block, stored in its first frame.
+#[repr(C)]
+struct FreeBlock {
+    prev: usize,
+    next: usize,
+}
+
+/// A buddy system over the frames [start, end).
+///
+/// Blocks are naturally aligned to their size in physical memory. One byte
+/// per frame, placed in the first frames of the zone, records the order of
+/// every free block head, so that buddies can be merged.
+pub struct BuddyZone {
+    name: &'static str,
+    start: usize,
     end: usize,
-    recycled: Vec<usize>,
+    state_base: usize,
+    free_heads: [usize; MAX_ORDER],
+    free: usize,
 }
 
-impl StackFrameAllocator {
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
-        self.end = r.0;
-        // println!("last {} Physical Frames.", self.end - self.current);
-    }
+fn order_of(pages: usize) -> usize {
+    pages.next_power_of_two().trailing_zeros() as usize
 }
-impl FrameAllocator for StackFrameAllocator {
-    fn new() -> Self {
-        Self {
-            current: 0,
-            end: 0,
-            recycled: Vec::new(),
+
+fn free_block(ppn: usize) -> &'static mut FreeBlock {
+    PhysPageNum(ppn).get_mut()
+}
+
+impl BuddyZone {
+    pub fn new(name: &'static str, l: PhysPageNum, r: PhysPageNum) -> Self {
+        let frames = r.0 - l.0;
+        let state_frames = (frames + PAGE_SIZE - 1) / PAGE_SIZE;
+        let mut zone = Self {
+            name,
+            start: l.0 + state_frames,
+            end: r.0,
+            state_base: PhysAddr::from(l).0,
+            free_heads: [NIL; MAX_ORDER],
+            free: 0,
+        };
+        for ppn in zone.start..zone.end {
+            *zone.state(ppn) = NOT_FREE;
+        }
+        // split the zone into maximal aligned blocks
+        let mut ppn = zone.start;
+        while ppn < zone.end {
+            let mut order = MAX_ORDER - 1;
+            while ppn % (1 << order) != 0 || ppn + (1 << order) > zone.end {
+                order -= 1;
+            }
+            zone.push(ppn, order);
+            zone.free += 1 << order;
+            ppn += 1 << order;
         }
+        zone
     }
-    fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
+    fn contains(&self, ppn: usize) -> bool {
+        self.start <= ppn && ppn < self.end
+    }
+    fn state(&self, ppn: usize) -> &'static mut u8 {
+        unsafe { &mut *((self.state_base + ppn - self.start) as *mut u8) }
+    }
+    fn push(&mut self, ppn: usize, order: usize) {
+        let head = self.free_heads[order];
+        let block = free_block(ppn);
+        block.prev = NIL;
+        block.next = head;
+        if head != NIL {
+            free_block(head).prev = ppn;
         }
+        self.free_heads[order] = ppn;
+        *self.state(ppn) = order as u8;
     }
-    fn alloc_more(&mut self, pages: usize) -> Option<Vec<PhysPageNum>> {
-        if self.current + pages >= self.end {
-            None
+    fn remove(&mut self, ppn: usize, order: usize) {
+        let block = free_block(ppn);
+        let (prev, next) = (block.prev, block.next);
+        if prev != NIL {
+            free_block(prev).next = next;
         } else {
-            self.current += pages;
-            let arr: Vec<usize> = (1..pages + 1).collect();
-            let v = arr.iter().map(|x| (self.current - x).into()).collect();
-            Some(v)
+            self.free_heads[order] = next;
         }
+        if next != NIL {
+            free_block(next).prev = prev;
+        }
+        *self.state(ppn) = NOT_FREE;
     }
-    fn dealloc(&mut self, ppn: PhysPageNum) {
-        let ppn = ppn.0;
+    fn alloc(&mut self, order: usize) -> Option<usize> {
+        let mut current = (order..MAX_ORDER).find(|&k| self.free_heads[k] != NIL)?;
+        let ppn = self.free_heads[current];
+        self.remove(ppn, current);
+        // give the upper halves back
+        while current > order {
+            current -= 1;
+            self.push(ppn + (1 << current), current);
+        }
+        self.free -= 1 << order;
+        Some(ppn)
+    }
+    fn dealloc(&mut self, ppn: usize, order: usize) {
         // validity check
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
+        if !self.contains(ppn) || *self.state(ppn) != NOT_FREE {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
-        // recycle
-        self.recycled.push(ppn);
+        self.free += 1 << order;
+        let mut ppn = ppn;
+        let mut order = order;
+        while order + 1 < MAX_ORDER {
+            let buddy = ppn ^ (1 << order);
+            if buddy < self.start
+                || buddy + (1 << order) > self.end
+                || *self.state(buddy) != order as u8
+            {
+                break;
+            }
+            self.remove(buddy, order);
+            ppn = ppn.min(buddy);
+            order += 1;
+        }
+        self.push(ppn, order);
+    }
+    /// Allocate exactly `pages` contiguous frames.
+    fn alloc_pages(&mut self, pages: usize) -> Option<usize> {
+        if pages == 0 {
+            return None;
+        }
+        let order = order_of(pages);
+        if order >= MAX_ORDER {
+            return None;
+        }
+        let ppn = self.alloc(order)?;
+        // return the unused tail frames
+        for tail in ppn + pages..ppn + (1 << order) {
+            self.dealloc(tail, 0);
+        }
+        Some(ppn)
+    }
+    pub fn stats(&self) -> ZoneStats {
+        let total = self.end - self.start;
+        ZoneStats {
+            name: self.name,
+            total,
+            free: self.free,
+            used: total - self.free,
+        }
+    }
+}
+
+pub struct BuddyFrameAllocator {
+    zones: Vec<BuddyZone>,
+}
+
+impl BuddyFrameAllocator {
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.zones.push(BuddyZone::new("DRAM", l, r));
+    }
+    pub fn zone_stats(&self, idx: usize) -> Option<ZoneStats> {
+        self.zones.get(idx).map(|zone| zone.stats())
+    }
+}
+
+impl FrameAllocator for BuddyFrameAllocator {
+    fn new() -> Self {
+        Self { zones: Vec::new() }
+    }
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        self.alloc_contiguous(1)
+    }
+    fn alloc_contiguous(&mut self, pages: usize) -> Option<PhysPageNum> {
+        self.zones
+            .iter_mut()
+            .find_map(|zone| zone.alloc_pages(pages))
+            .map(PhysPageNum)
+    }
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        self.dealloc_contiguous(ppn, 1);
+    }
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, pages: usize) {
+        let zone = self
+            .zones
+            .iter_mut()
+            .find(|zone| zone.contains(ppn.0))
+            .unwrap_or_else(|| panic!("Frame ppn={:#x} has not been allocated!", ppn.0));
+        for ppn in ppn.0..ppn.0 + pages {
+            zone.dealloc(ppn, 0);
+        }
     }
 }
 
-type FrameAllocatorImpl = StackFrameAllocator;
+type FrameAllocatorImpl = BuddyFrameAllocator;
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPIntrFreeCell<FrameAllocatorImpl> =
-        unsafe { UPIntrFreeCell::new(FrameAllocatorImpl::new()) };
+    /// The kernel heap grows from this allocator, so never allocate
+    /// from the heap while it is borrowed.
+    pub static ref FRAME_ALLOCATOR: SpinNoIrqLock<FrameAllocatorImpl> =
+        SpinNoIrqLock::new(FrameAllocatorImpl::new());
 }
 
 pub fn init_frame_allocator() {
@@ -116,16 +272,46 @@ pub fn frame_alloc() -> Option<FrameTracker> {
 }
 
 pub fn frame_alloc_more(num: usize) -> Option<Vec<FrameTracker>> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc_more(num)
-        .map(|x| x.iter().map(|&t| FrameTracker::new(t)).collect())
+    let base = FRAME_ALLOCATOR.exclusive_access().alloc_contiguous(num)?;
+    Some(
+        (base.0..base.0 + num)
+            .map(|ppn| FrameTracker::new(PhysPageNum(ppn)))
+            .collect(),
+    )
+}
+
+/// Allocate `pages` physically contiguous frames, e.g. for DMA.
+/// They are not tracked and should be freed by `frame_dealloc_contiguous`.
+pub fn frame_alloc_contiguous(pages: usize) -> Option<PhysPageNum> {
+    let ppn = FRAME_ALLOCATOR.exclusive_access().alloc_contiguous(pages)?;
+    let pa: PhysAddr = ppn.into();
+    unsafe {
+        core::slice::from_raw_parts_mut(pa.0 as *mut u8, pages * PAGE_SIZE).fill(0);
+    }
+    Some(ppn)
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+pub fn frame_dealloc_contiguous(ppn: PhysPageNum, pages: usize) {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .dealloc_contiguous(ppn, pages);
+}
+
+pub fn frame_stats() -> Vec<ZoneStats> {
+    let mut stats = Vec::new();
+    loop {
+        let zone = FRAME_ALLOCATOR.exclusive_access().zone_stats(stats.len());
+        match zone {
+            Some(zone) => stats.push(zone),
+            None => return stats,
+        }
+    }
+}
+
 #[allow(unused)]
 pub fn frame_allocator_test() {
     let mut v: Vec<FrameTracker> = Vec::new();
@@ -160,3 +346,18 @@ pub fn frame_allocator_alloc_more_test() {
     drop(v);
     println!("frame_allocator_test passed!");
 }
+
+#[allow(unused)]
+pub fn frame_allocator_contiguous_test() {
+    let before = frame_stats();
+    let ppn = frame_alloc_contiguous(5).unwrap();
+    println!("contiguous frames: [{:?}, {:#x})", ppn, ppn.0 + 5);
+    let frames: Vec<FrameTracker> = (0..3).map(|_| frame_alloc().unwrap()).collect();
+    frame_dealloc_contiguous(ppn, 5);
+    drop(frames);
+    for (old, new) in before.iter().zip(frame_stats().iter()) {
+        assert_eq!(old.free, new.free);
+        println!("{:?}", new);
+    }
+    println!("frame_allocator_contiguous_test passed!");
+}
@@ -1,24 +1,119 @@
-use crate::config::KERNEL_HEAP_SIZE;
-use buddy_system_allocator::LockedHeap;
+use super::frame_allocator::frame_alloc_contiguous;
+use super::slab::{SizeClassStats, SlabAllocator, SIZE_CLASSES};
+use super::PhysAddr;
+use crate::config::{KERNEL_HEAP_GROW_SIZE, KERNEL_HEAP_INIT_SIZE, PAGE_SIZE};
+use crate::sync::SpinNoIrqLock;
+use buddy_system_allocator::Heap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+use lazy_static::*;
+
+/// Small objects are served from the slabs, others from the buddy heap.
+/// The heap starts with a static area and grows from the frame allocator.
+pub struct KernelHeap {
+    heap: Heap,
+    slabs: SlabAllocator,
+}
+
+/// Counters of the kernel heap, used for diagnostics.
+#[derive(Copy, Clone, Debug)]
+pub struct HeapStats {
+    pub total: usize,
+    pub allocated: usize,
+    pub size_classes: [SizeClassStats; SIZE_CLASSES.len()],
+}
+
+impl KernelHeap {
+    pub fn new() -> Self {
+        Self {
+            heap: Heap::empty(),
+            slabs: SlabAllocator::new(),
+        }
+    }
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        match SlabAllocator::class_of(&layout) {
+            Some(class) => self.slabs.alloc(class, &mut self.heap),
+            None => self
+                .heap
+                .alloc(layout)
+                .map_or(null_mut(), |ptr| ptr.as_ptr()),
+        }
+    }
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        match SlabAllocator::class_of(&layout) {
+            Some(class) => self.slabs.dealloc(class, &mut self.heap, ptr),
+            None => self.heap.dealloc(NonNull::new_unchecked(ptr), layout),
+        }
+    }
+    /// Add a block of frames big enough for `layout` to the heap.
+    /// Allocations larger than the biggest frame block (16 MiB) fail.
+    fn grow(&mut self, layout: &Layout) -> bool {
+        let size = KERNEL_HEAP_GROW_SIZE.max(layout.size()).max(layout.align());
+        // a power of two block from the buddy frame allocator is aligned to its size
+        let pages = ((size + PAGE_SIZE - 1) / PAGE_SIZE).next_power_of_two();
+        if let Some(ppn) = frame_alloc_contiguous(pages) {
+            let start: PhysAddr = ppn.into();
+            unsafe {
+                self.heap.add_to_heap(start.0, start.0 + pages * PAGE_SIZE);
+            }
+            true
+        } else {
+            false
+        }
+    }
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            total: self.heap.stats_total_bytes(),
+            allocated: self.heap.stats_alloc_actual(),
+            size_classes: self.slabs.stats(),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref KERNEL_HEAP: SpinNoIrqLock<KernelHeap> = SpinNoIrqLock::new(KernelHeap::new());
+}
+
+pub struct KernelHeapAllocator;
+
+unsafe impl GlobalAlloc for KernelHeapAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = KERNEL_HEAP.exclusive_access();
+        loop {
+            let ptr = heap.alloc(layout);
+            if !ptr.is_null() || !heap.grow(&layout) {
+                return ptr;
+            }
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        KERNEL_HEAP.exclusive_access().dealloc(ptr, layout);
+    }
+}
 
 #[global_allocator]
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: KernelHeapAllocator = KernelHeapAllocator;
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
-static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
+static mut HEAP_SPACE: [u8; KERNEL_HEAP_INIT_SIZE] = [0; KERNEL_HEAP_INIT_SIZE];
 
 pub fn init_heap() {
     unsafe {
-        HEAP_ALLOCATOR
-            .lock()
-            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+        KERNEL_HEAP
+            .exclusive_access()
+            .heap
+            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_INIT_SIZE);
     }
 }
 
+pub fn heap_stats() -> HeapStats {
+    KERNEL_HEAP.exclusive_access().stats()
+}
+
 #[allow(unused)]
 pub fn heap_test() {
     use alloc::boxed::Box;
@@ -43,3 +138,21 @@ pub fn heap_test() {
     drop(v);
     println!("heap_test passed!");
 }
+
+#[allow(unused)]
+pub fn heap_grow_test() {
+    use alloc::vec::Vec;
+    let before = heap_stats();
+    let v: Vec<u8> = Vec::with_capacity(KERNEL_HEAP_INIT_SIZE * 2);
+    assert!(heap_stats().total > before.total);
+    drop(v);
+    let small: Vec<Vec<u8>> = (0..100).map(|_| Vec::with_capacity(100)).collect();
+    let stats = heap_stats();
+    let class = SlabAllocator::class_of(&Layout::array::<u8>(100).unwrap()).unwrap();
+    assert!(stats.size_classes[class].in_use >= before.size_classes[class].in_use + 100);
+    drop(small);
+    for class in stats.size_classes.iter() {
+        println!("{:?}", class);
+    }
+    println!("heap_grow_test passed!");
+}
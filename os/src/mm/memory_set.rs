@@ -1,9 +1,11 @@
-use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::shm::ShmHandle;
+use super::{frame_alloc, frame_alloc_contiguous, FrameTracker};
+use super::{PTEFlags, PageSize, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
 use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE};
-use crate::sync::UPIntrFreeCell;
+use crate::smp::tlb_shootdown;
+use crate::sync::SpinNoIrqLock;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -25,8 +27,8 @@ extern "C" {
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPIntrFreeCell<MemorySet>> =
-        Arc::new(unsafe { UPIntrFreeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinNoIrqLock<MemorySet>> =
+        Arc::new(SpinNoIrqLock::new(MemorySet::new_kernel()));
 }
 
 pub fn kernel_token() -> usize {
@@ -68,7 +70,108 @@ impl MemorySet {
             .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
         {
             area.unmap(&mut self.page_table);
-            self.areas.remove(idx);
+            let area = self.areas.remove(idx);
+            // frames are freed only after no hart can access them
+            tlb_shootdown(self.token());
+            drop(area);
+        }
+    }
+    pub fn remove_area_with_end_vpn(&mut self, end_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_end() == end_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            let area = self.areas.remove(idx);
+            // frames are freed only after no hart can access them
+            tlb_shootdown(self.token());
+            drop(area);
+        }
+    }
+    /// Return the start of the area ending at `end_vpn`.
+    pub fn area_start_with_end_vpn(&self, end_vpn: VirtPageNum) -> Option<VirtPageNum> {
+        self.areas
+            .iter()
+            .find(|area| area.vpn_range.get_end() == end_vpn)
+            .map(|area| area.vpn_range.get_start())
+    }
+    /// Size of all areas in bytes, the trampoline excluded.
+    pub fn mapped_size(&self) -> usize {
+        self.areas
+            .iter()
+            .map(|area| area.vpn_range.get_end().0 - area.vpn_range.get_start().0)
+            .sum::<usize>()
+            * PAGE_SIZE
+    }
+    /// Extend the framed area ending at `end_vpn` down to `new_start_vpn`,
+    /// return false if there is no such area or it already covers `new_start_vpn`.
+    pub fn extend_area_down(&mut self, end_vpn: VirtPageNum, new_start_vpn: VirtPageNum) -> bool {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.map_type == MapType::Framed && area.vpn_range.get_end() == end_vpn)
+        {
+            let old_start_vpn = area.vpn_range.get_start();
+            if new_start_vpn >= old_start_vpn {
+                return false;
+            }
+            for vpn in VPNRange::new(new_start_vpn, old_start_vpn) {
+                area.map_one(&mut self.page_table, vpn);
+            }
+            area.vpn_range = VPNRange::new(new_start_vpn, end_vpn);
+            true
+        } else {
+            false
+        }
+    }
+    /// Map a shared memory segment at `start_vpn`.
+    pub fn insert_shared_area(
+        &mut self,
+        start_vpn: VirtPageNum,
+        shm: ShmHandle,
+        permission: MapPermission,
+    ) {
+        let end_vpn = VirtPageNum(start_vpn.0 + shm.segment().page_count());
+        let mut map_area = MapArea::new(
+            start_vpn.into(),
+            end_vpn.into(),
+            MapType::Shared,
+            permission,
+        );
+        map_area.shm = Some(shm);
+        self.push(map_area, None);
+    }
+    /// Detach a shared memory segment mapped at `start_vpn`.
+    pub fn remove_shared_area(&mut self, start_vpn: VirtPageNum) -> bool {
+        if let Some((idx, area)) = self.areas.iter_mut().enumerate().find(|(_, area)| {
+            area.map_type == MapType::Shared && area.vpn_range.get_start() == start_vpn
+        }) {
+            area.unmap(&mut self.page_table);
+            let area = self.areas.remove(idx);
+            // frames are freed only after no hart can access them
+            tlb_shootdown(self.token());
+            drop(area);
+            true
+        } else {
+            false
+        }
+    }
+    /// Find `pages` unmapped pages starting from `start_vpn`.
+    pub fn find_free_area(&self, start_vpn: VirtPageNum, pages: usize) -> VirtPageNum {
+        let mut start = start_vpn;
+        loop {
+            let end = VirtPageNum(start.0 + pages);
+            if let Some(area) = self
+                .areas
+                .iter()
+                .find(|area| area.vpn_range.get_start() < end && start < area.vpn_range.get_end())
+            {
+                start = area.vpn_range.get_end();
+            } else {
+                return start;
+            }
         }
     }
     /// Add a new MapArea into this MemorySet.
@@ -149,7 +252,8 @@ impl MemorySet {
                 MEMORY_END.into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
-            ),
+            )
+            .with_huge_pages(),
             None,
         );
         //println!("mapping memory-mapped registers");
@@ -160,7 +264,8 @@ impl MemorySet {
                     ((*pair).0 + (*pair).1).into(),
                     MapType::Identical,
                     MapPermission::R | MapPermission::W,
-                ),
+                )
+                .with_huge_pages(),
                 None,
             );
         }
@@ -220,6 +325,10 @@ impl MemorySet {
         for area in user_space.areas.iter() {
             let new_area = MapArea::from_another(area);
             memory_set.push(new_area, None);
+            // shared memory keeps pointing at the same frames
+            if area.map_type == MapType::Shared {
+                continue;
+            }
             // copy data from another space
             for vpn in area.vpn_range {
                 let src_ppn = user_space.translate(vpn).unwrap().ppn();
@@ -241,8 +350,13 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
+        self.page_table.translate_va(va)
+    }
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
+        // the page table is still intact, make sure no hart uses it before freeing the frames
+        tlb_shootdown(self.token());
         self.areas.clear();
     }
 }
@@ -252,6 +366,8 @@ pub struct MapArea {
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     map_type: MapType,
     map_perm: MapPermission,
+    shm: Option<ShmHandle>,
+    huge: bool,
 }
 
 impl MapArea {
@@ -268,6 +384,8 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            shm: None,
+            huge: false,
         }
     }
     pub fn from_another(another: &MapArea) -> Self {
@@ -276,8 +394,15 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            shm: another.shm.clone(),
+            huge: another.huge,
         }
     }
+    /// Use megapages or gigapages wherever the range allows it.
+    pub fn with_huge_pages(mut self) -> Self {
+        self.huge = true;
+        self
+    }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -294,6 +419,10 @@ impl MapArea {
                 assert!(vpn.0 < (1usize << 27));
                 ppn = PhysPageNum((vpn.0 as isize + pn_offset) as usize);
             }
+            MapType::Shared => {
+                let page_idx = vpn.0 - self.vpn_range.get_start().0;
+                ppn = self.shm.as_ref().unwrap().segment().ppn(page_idx);
+            }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
@@ -304,14 +433,77 @@ impl MapArea {
         }
         page_table.unmap(vpn);
     }
+    /// Map a huge page at `vpn`, return false if it is not possible.
+    fn map_huge_one(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        page_size: PageSize,
+    ) -> bool {
+        let ppn = match self.map_type {
+            MapType::Identical => PhysPageNum(vpn.0),
+            MapType::Linear(pn_offset) => PhysPageNum((vpn.0 as isize + pn_offset) as usize),
+            MapType::Framed => {
+                let ppn = match frame_alloc_contiguous(page_size.pages()) {
+                    Some(ppn) => ppn,
+                    None => return false,
+                };
+                for i in 0..page_size.pages() {
+                    // already cleared by frame_alloc_contiguous
+                    let frame = FrameTracker {
+                        ppn: PhysPageNum(ppn.0 + i),
+                    };
+                    self.data_frames.insert(VirtPageNum(vpn.0 + i), frame);
+                }
+                ppn
+            }
+            MapType::Shared => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map_huge(vpn, ppn, pte_flags, page_size);
+        true
+    }
+    /// Largest page size which can be used at `vpn`.
+    fn page_size_at(&self, vpn: VirtPageNum) -> PageSize {
+        if !self.huge {
+            return PageSize::Size4K;
+        }
+        for page_size in [PageSize::Size1G, PageSize::Size2M] {
+            let pages = page_size.pages();
+            if vpn.0 % pages != 0 || vpn.0 + pages > self.vpn_range.get_end().0 {
+                continue;
+            }
+            let ppn_aligned = match self.map_type {
+                MapType::Identical => true,
+                MapType::Linear(pn_offset) => (vpn.0 as isize + pn_offset) as usize % pages == 0,
+                // a gigapage is larger than any buddy block
+                MapType::Framed => page_size == PageSize::Size2M,
+                MapType::Shared => false,
+            };
+            if ppn_aligned {
+                return page_size;
+            }
+        }
+        PageSize::Size4K
+    }
     pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            let page_size = self.page_size_at(vpn);
+            if page_size != PageSize::Size4K && self.map_huge_one(page_table, vpn, page_size) {
+                vpn = VirtPageNum(vpn.0 + page_size.pages());
+            } else {
+                self.map_one(page_table, vpn);
+                vpn.step();
+            }
         }
     }
+    /// Unmap the whole area, its frames are freed when the area is dropped.
     pub fn unmap(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.unmap_one(page_table, vpn);
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            let page_size = page_table.unmap(vpn);
+            vpn = VirtPageNum(vpn.0 + page_size.pages());
         }
     }
     /// data: start-aligned but maybe with shorter length
@@ -344,6 +536,8 @@ pub enum MapType {
     Framed,
     /// offset of page num
     Linear(isize),
+    /// frames owned by a shared memory segment
+    Shared,
 }
 
 bitflags! {
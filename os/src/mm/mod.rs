@@ -3,17 +3,25 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod shm;
+mod slab;
 
 pub use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_alloc_more, frame_dealloc, FrameTracker};
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_contiguous, frame_dealloc, frame_dealloc_contiguous, frame_stats,
+    FrameTracker, ZoneStats,
+};
+pub use heap_allocator::{heap_stats, HeapStats};
 pub use memory_set::remap_test;
 pub use memory_set::{kernel_token, MapArea, MapPermission, MapType, MemorySet, KERNEL_SPACE};
-use page_table::PTEFlags;
 pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
-    PageTableEntry, UserBuffer, UserBufferIterator,
+    copy_from_user, copy_to_user, translated_byte_buffer, translated_byte_buffer_mut,
+    translated_ref, translated_refmut, translated_str, PageTable, PageTableEntry, UserBuffer,
+    UserBufferIterator,
 };
+use page_table::{PTEFlags, PageSize};
+pub use shm::SHM_MANAGER;
 
 pub fn init() {
     heap_allocator::init_heap();
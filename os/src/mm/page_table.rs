@@ -1,4 +1,7 @@
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::config::PAGE_SIZE;
+use crate::errno::Errno;
+use crate::task::{current_stack_fault, StackFault};
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -50,6 +53,47 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// A valid PTE with any of R/W/X set is a leaf, otherwise it points to the next level.
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid()
+            && (self.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X)) != PTEFlags::empty()
+    }
+}
+
+/// Size of the page mapped by a leaf PTE.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PageSize {
+    Size4K,
+    /// megapage, leaf in the level-1 page table
+    Size2M,
+    /// gigapage, leaf in the root page table
+    Size1G,
+}
+
+impl PageSize {
+    /// Number of 4 KiB pages covered.
+    pub fn pages(&self) -> usize {
+        match self {
+            PageSize::Size4K => 1,
+            PageSize::Size2M => 1 << 9,
+            PageSize::Size1G => 1 << 18,
+        }
+    }
+    /// Index of the page table level holding the leaf, 0 being the root.
+    fn level(&self) -> usize {
+        match self {
+            PageSize::Size1G => 0,
+            PageSize::Size2M => 1,
+            PageSize::Size4K => 2,
+        }
+    }
+    fn from_level(level: usize) -> Self {
+        match level {
+            0 => PageSize::Size1G,
+            1 => PageSize::Size2M,
+            _ => PageSize::Size4K,
+        }
+    }
 }
 
 pub struct PageTable {
@@ -73,16 +117,21 @@ impl PageTable {
             frames: Vec::new(),
         }
     }
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    fn find_pte_create(
+        &mut self,
+        vpn: VirtPageNum,
+        page_size: PageSize,
+    ) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
         let mut result: Option<&mut PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
+            if i == page_size.level() {
                 result = Some(pte);
                 break;
             }
+            assert!(!pte.is_leaf(), "vpn {:?} is inside a huge page", vpn);
             if !pte.is_valid() {
                 let frame = frame_alloc().unwrap();
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
@@ -92,14 +141,15 @@ impl PageTable {
         }
         result
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// Return the leaf PTE covering `vpn` and the size of the page it maps.
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<(&mut PageTableEntry, PageSize)> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
+        let mut result: Option<(&mut PageTableEntry, PageSize)> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
+            if i == 2 || pte.is_leaf() {
+                result = Some((pte, PageSize::from_level(i)));
                 break;
             }
             if !pte.is_valid() {
@@ -111,21 +161,44 @@ impl PageTable {
     }
     #[allow(unused)]
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
+        self.map_huge(vpn, ppn, flags, PageSize::Size4K);
+    }
+    /// Map a page of `page_size`, both `vpn` and `ppn` should be aligned to it.
+    pub fn map_huge(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+        page_size: PageSize,
+    ) {
+        assert!(
+            vpn.0 % page_size.pages() == 0 && ppn.0 % page_size.pages() == 0,
+            "vpn {:?} or ppn {:?} is not aligned to {:?}",
+            vpn,
+            ppn,
+            page_size
+        );
+        let pte = self.find_pte_create(vpn, page_size).unwrap();
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
     }
+    /// Return the size of the unmapped page.
     #[allow(unused)]
-    pub fn unmap(&mut self, vpn: VirtPageNum) {
-        let pte = self.find_pte(vpn).unwrap();
+    pub fn unmap(&mut self, vpn: VirtPageNum) -> PageSize {
+        let (pte, page_size) = self.find_pte(vpn).unwrap();
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
+        page_size
     }
+    /// For a huge page, the returned PTE points to the 4 KiB frame of `vpn`.
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).map(|pte| *pte)
+        self.find_pte(vpn).map(|(pte, page_size)| {
+            let offset = vpn.0 % page_size.pages();
+            PageTableEntry::new(PhysPageNum(pte.ppn().0 + offset), pte.flags())
+        })
     }
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
+        self.translate(va.clone().floor()).map(|pte| {
             let aligned_pa: PhysAddr = pte.ppn().into();
             let offset = va.page_offset();
             let aligned_pa_usize: usize = aligned_pa.into();
@@ -137,15 +210,76 @@ impl PageTable {
     }
 }
 
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// The frame of the user page `vpn`, `EFAULT` if it is not mapped with
+/// all of `flags`.
+fn user_ppn(
+    page_table: &PageTable,
+    vpn: VirtPageNum,
+    flags: PTEFlags,
+) -> Result<PhysPageNum, Errno> {
+    match page_table.translate(vpn) {
+        Some(pte) if pte.is_valid() && pte.flags().contains(flags) => Ok(pte.ppn()),
+        _ => Err(Errno::EFAULT),
+    }
+}
+
+/// Like `user_ppn`, but a page of the user stack the thread has not touched
+/// yet is faulted in first, as if the thread had accessed it.
+fn user_ppn_faulting(
+    page_table: &PageTable,
+    vpn: VirtPageNum,
+    flags: PTEFlags,
+) -> Result<PhysPageNum, Errno> {
+    user_ppn(page_table, vpn, flags).or_else(|err| {
+        let va: VirtAddr = vpn.into();
+        match current_stack_fault(va.into()) {
+            StackFault::Grown => user_ppn(page_table, vpn, flags),
+            StackFault::Overflow | StackFault::Other => Err(err),
+        }
+    })
+}
+
+/// The physical address of the user address `va` to be read.
+fn user_pa(page_table: &PageTable, va: usize) -> Result<PhysAddr, Errno> {
+    let va = VirtAddr::from(va);
+    let pa: usize = PhysAddr::from(user_ppn(page_table, va.floor(), PTEFlags::U)?).into();
+    Ok((pa + va.page_offset()).into())
+}
+
+/// Fail with `EFAULT` unless all of the buffer is mapped for user space,
+/// for the kernel to read from.
+pub fn translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Result<Vec<&'static mut [u8]>, Errno> {
+    user_byte_buffer(token, ptr, len, PTEFlags::U)
+}
+
+/// Fail with `EFAULT` unless all of the buffer is mapped writable for user
+/// space, for the kernel to write to.
+pub fn translated_byte_buffer_mut(
+    token: usize,
+    ptr: *mut u8,
+    len: usize,
+) -> Result<Vec<&'static mut [u8]>, Errno> {
+    user_byte_buffer(token, ptr, len, PTEFlags::U | PTEFlags::W)
+}
+
+fn user_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    flags: PTEFlags,
+) -> Result<Vec<&'static mut [u8]>, Errno> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len).ok_or(Errno::EFAULT)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = user_ppn_faulting(&page_table, vpn, flags)?;
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -156,43 +290,85 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Ok(v)
 }
 
 /// Load a string from other address spaces into kernel space without an end `\0`.
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
+pub fn translated_str(token: usize, ptr: *const u8) -> Result<String, Errno> {
     let page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
+        let ch: u8 = *user_pa(&page_table, va)?.get_mut();
         if ch == 0 {
             break;
         }
         string.push(ch as char);
         va += 1;
     }
-    string
+    Ok(string)
 }
 
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
+/// A reference goes through the frame of a single page, so `T` must be
+/// aligned and must not cross a page boundary.
+fn check_in_page<T>(ptr: usize) -> Result<(), Errno> {
+    if ptr % core::mem::align_of::<T>() != 0
+        || ptr % PAGE_SIZE + core::mem::size_of::<T>() > PAGE_SIZE
+    {
+        return Err(Errno::EFAULT);
+    }
+    Ok(())
+}
+
+/// Fail with `EFAULT` unless `ptr` is mapped for user space, aligned and
+/// within a page. Use `copy_from_user` for structs which may cross pages.
+pub fn translated_ref<T>(token: usize, ptr: *const T) -> Result<&'static T, Errno> {
+    check_in_page::<T>(ptr as usize)?;
     let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+    Ok(user_pa(&page_table, ptr as usize)?.get_ref())
 }
 
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
+/// Fail with `EFAULT` unless `ptr` is mapped writable for user space,
+/// aligned and within a page. Use `copy_to_user` for structs which may
+/// cross pages.
+pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> Result<&'static mut T, Errno> {
+    check_in_page::<T>(ptr as usize)?;
     let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+    let va = VirtAddr::from(ptr as usize);
+    let pa: usize = PhysAddr::from(user_ppn_faulting(
+        &page_table,
+        va.floor(),
+        PTEFlags::U | PTEFlags::W,
+    )?)
+    .into();
+    Ok(PhysAddr::from(pa + va.page_offset()).get_mut())
+}
+
+/// Read a `T` from user space, byte by byte as it may cross pages.
+pub fn copy_from_user<T: Copy>(token: usize, ptr: *const T) -> Result<T, Errno> {
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+    let mut offset = 0;
+    for buffer in translated_byte_buffer(token, ptr as *const u8, bytes.len())? {
+        bytes[offset..offset + buffer.len()].copy_from_slice(buffer);
+        offset += buffer.len();
+    }
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Write `value` to user space, byte by byte as it may cross pages.
+pub fn copy_to_user<T: Copy>(token: usize, ptr: *mut T, value: &T) -> Result<(), Errno> {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    let mut offset = 0;
+    for buffer in translated_byte_buffer_mut(token, ptr as *mut u8, bytes.len())? {
+        buffer.copy_from_slice(&bytes[offset..offset + buffer.len()]);
+        offset += buffer.len();
+    }
+    Ok(())
 }
 
 pub struct UserBuffer {
@@ -0,0 +1,139 @@
+//! System V style shared memory segments.
+//!
+//! A segment owns its frames. Every attachment of a segment in some
+//! `MemorySet` holds a `ShmHandle`, so the frames are released once the
+//! segment is removed by `IPC_RMID` and the last attachment is gone.
+
+use super::{frame_alloc, FrameTracker, PhysPageNum};
+use crate::config::PAGE_SIZE;
+use crate::sync::SpinNoIrqLock;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+pub const IPC_PRIVATE: usize = 0;
+
+pub struct ShmSegment {
+    pub id: usize,
+    pub key: usize,
+    frames: Vec<FrameTracker>,
+}
+
+impl ShmSegment {
+    pub fn page_count(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn ppn(&self, page_idx: usize) -> PhysPageNum {
+        self.frames[page_idx].ppn
+    }
+}
+
+struct ShmEntry {
+    segment: Arc<ShmSegment>,
+    /// number of live `ShmHandle`s, only changed under `SHM_MANAGER`
+    attached: usize,
+    /// marked by `IPC_RMID`, dropped on the last detach
+    removed: bool,
+}
+
+pub struct ShmManager {
+    next_id: usize,
+    segments: BTreeMap<usize, ShmEntry>,
+}
+
+impl ShmManager {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            segments: BTreeMap::new(),
+        }
+    }
+    /// Return the id of the segment with `key`, creating it if necessary.
+    pub fn get(&mut self, key: usize, size: usize) -> Option<usize> {
+        if key != IPC_PRIVATE {
+            if let Some((id, entry)) = self
+                .segments
+                .iter()
+                .find(|(_, entry)| entry.segment.key == key && !entry.removed)
+            {
+                if entry.segment.page_count() * PAGE_SIZE < size {
+                    return None;
+                }
+                return Some(*id);
+            }
+        }
+        let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        if pages == 0 {
+            return None;
+        }
+        let mut frames = Vec::with_capacity(pages);
+        for _ in 0..pages {
+            frames.push(frame_alloc()?);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.segments.insert(
+            id,
+            ShmEntry {
+                segment: Arc::new(ShmSegment { id, key, frames }),
+                attached: 0,
+                removed: false,
+            },
+        );
+        Some(id)
+    }
+    pub fn attach(&mut self, id: usize) -> Option<ShmHandle> {
+        let entry = self.segments.get_mut(&id).filter(|entry| !entry.removed)?;
+        entry.attached += 1;
+        Some(ShmHandle(Arc::clone(&entry.segment)))
+    }
+    /// Mark the segment to be destroyed, frames are kept until the last detach.
+    pub fn remove(&mut self, id: usize) -> bool {
+        if let Some(entry) = self.segments.get_mut(&id) {
+            entry.removed = true;
+            if entry.attached == 0 {
+                self.segments.remove(&id);
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref SHM_MANAGER: SpinNoIrqLock<ShmManager> = SpinNoIrqLock::new(ShmManager::new());
+}
+
+/// An attachment of a segment, owned by a `MapArea`.
+pub struct ShmHandle(Arc<ShmSegment>);
+
+impl ShmHandle {
+    pub fn segment(&self) -> &ShmSegment {
+        &self.0
+    }
+}
+
+impl Clone for ShmHandle {
+    fn clone(&self) -> Self {
+        let mut manager = SHM_MANAGER.exclusive_access();
+        if let Some(entry) = manager.segments.get_mut(&self.0.id) {
+            entry.attached += 1;
+        }
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Drop for ShmHandle {
+    fn drop(&mut self) {
+        let mut manager = SHM_MANAGER.exclusive_access();
+        let id = self.0.id;
+        if let Some(entry) = manager.segments.get_mut(&id) {
+            entry.attached -= 1;
+            if entry.attached == 0 && entry.removed {
+                manager.segments.remove(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Slab caches for small kernel allocations.
+//!
+//! Every allocation of at most 2048 bytes, which includes hot objects such
+//! as `TaskControlBlock`, `TimerCondVar` and network buffers, is served from
+//! slabs of equally sized slots. There is one cache per size class rather
+//! than per type, so that both operations take constant time and small
+//! objects do not fragment the heap.
+
+use crate::config::PAGE_SIZE;
+use buddy_system_allocator::Heap;
+use core::alloc::Layout;
+use core::mem::size_of;
+use core::ptr::{null_mut, NonNull};
+
+pub const SIZE_CLASSES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+/// A slab holds at least this many slots of a big size class.
+const MIN_SLOTS_PER_SLAB: usize = 8;
+
+/// Counters of a size class, used for diagnostics.
+#[derive(Copy, Clone, Debug)]
+pub struct SizeClassStats {
+    pub size: usize,
+    pub slabs: usize,
+    pub in_use: usize,
+    pub allocs: usize,
+    pub frees: usize,
+}
+
+/// Link stored in a free slot.
+struct FreeSlot {
+    next: *mut FreeSlot,
+}
+
+/// Header at the beginning of every slab, slabs are aligned to their size.
+struct SlabHeader {
+    prev: *mut SlabHeader,
+    next: *mut SlabHeader,
+    free: *mut FreeSlot,
+    in_use: usize,
+}
+
+struct SizeClass {
+    size: usize,
+    /// slabs with free slots, full slabs are not linked anywhere
+    partial: *mut SlabHeader,
+    stats: SizeClassStats,
+}
+
+impl SizeClass {
+    const fn new(size: usize) -> Self {
+        Self {
+            size,
+            partial: null_mut(),
+            stats: SizeClassStats {
+                size,
+                slabs: 0,
+                in_use: 0,
+                allocs: 0,
+                frees: 0,
+            },
+        }
+    }
+    fn slab_layout(&self) -> Layout {
+        let slab_size = PAGE_SIZE.max(self.size * MIN_SLOTS_PER_SLAB);
+        Layout::from_size_align(slab_size, slab_size).unwrap()
+    }
+    /// The header takes up the first slots of a slab.
+    fn first_slot_offset(&self) -> usize {
+        (size_of::<SlabHeader>() + self.size - 1) / self.size * self.size
+    }
+    unsafe fn link(&mut self, slab: *mut SlabHeader) {
+        (*slab).prev = null_mut();
+        (*slab).next = self.partial;
+        if !self.partial.is_null() {
+            (*self.partial).prev = slab;
+        }
+        self.partial = slab;
+    }
+    unsafe fn unlink(&mut self, slab: *mut SlabHeader) {
+        let (prev, next) = ((*slab).prev, (*slab).next);
+        if prev.is_null() {
+            self.partial = next;
+        } else {
+            (*prev).next = next;
+        }
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+    }
+    unsafe fn new_slab(&mut self, heap: &mut Heap) -> bool {
+        let layout = self.slab_layout();
+        let base = match heap.alloc(layout) {
+            Ok(base) => base.as_ptr() as usize,
+            Err(_) => return false,
+        };
+        let slab = base as *mut SlabHeader;
+        let mut free = null_mut();
+        let mut slot = base + layout.size() - self.size;
+        while slot >= base + self.first_slot_offset() {
+            let free_slot = slot as *mut FreeSlot;
+            (*free_slot).next = free;
+            free = free_slot;
+            slot -= self.size;
+        }
+        slab.write(SlabHeader {
+            prev: null_mut(),
+            next: null_mut(),
+            free,
+            in_use: 0,
+        });
+        self.link(slab);
+        self.stats.slabs += 1;
+        true
+    }
+    unsafe fn alloc(&mut self, heap: &mut Heap) -> *mut u8 {
+        if self.partial.is_null() && !self.new_slab(heap) {
+            return null_mut();
+        }
+        let slab = self.partial;
+        let slot = (*slab).free;
+        (*slab).free = (*slot).next;
+        (*slab).in_use += 1;
+        if (*slab).free.is_null() {
+            self.unlink(slab);
+        }
+        self.stats.in_use += 1;
+        self.stats.allocs += 1;
+        slot as *mut u8
+    }
+    unsafe fn dealloc(&mut self, heap: &mut Heap, ptr: *mut u8) {
+        let layout = self.slab_layout();
+        let slab = (ptr as usize & !(layout.size() - 1)) as *mut SlabHeader;
+        let was_full = (*slab).free.is_null();
+        let slot = ptr as *mut FreeSlot;
+        (*slot).next = (*slab).free;
+        (*slab).free = slot;
+        (*slab).in_use -= 1;
+        if was_full {
+            self.link(slab);
+        } else if (*slab).in_use == 0 && (self.partial != slab || !(*slab).next.is_null()) {
+            // keep the last partial slab around to avoid thrashing
+            self.unlink(slab);
+            heap.dealloc(NonNull::new_unchecked(slab as *mut u8), layout);
+            self.stats.slabs -= 1;
+        }
+        self.stats.in_use -= 1;
+        self.stats.frees += 1;
+    }
+}
+
+pub struct SlabAllocator {
+    classes: [SizeClass; SIZE_CLASSES.len()],
+}
+
+impl SlabAllocator {
+    pub const fn new() -> Self {
+        Self {
+            classes: [
+                SizeClass::new(SIZE_CLASSES[0]),
+                SizeClass::new(SIZE_CLASSES[1]),
+                SizeClass::new(SIZE_CLASSES[2]),
+                SizeClass::new(SIZE_CLASSES[3]),
+                SizeClass::new(SIZE_CLASSES[4]),
+                SizeClass::new(SIZE_CLASSES[5]),
+                SizeClass::new(SIZE_CLASSES[6]),
+                SizeClass::new(SIZE_CLASSES[7]),
+            ],
+        }
+    }
+    /// Return the smallest size class fitting `layout`, a slot is aligned to its size.
+    pub fn class_of(layout: &Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        SIZE_CLASSES.iter().position(|&class| class >= size)
+    }
+    /// # Safety
+    ///
+    /// `class` must come from `class_of`.
+    pub unsafe fn alloc(&mut self, class: usize, heap: &mut Heap) -> *mut u8 {
+        self.classes[class].alloc(heap)
+    }
+    /// # Safety
+    ///
+    /// `ptr` must be allocated from the same `class` by this allocator.
+    pub unsafe fn dealloc(&mut self, class: usize, heap: &mut Heap, ptr: *mut u8) {
+        self.classes[class].dealloc(heap, ptr)
+    }
+    pub fn stats(&self) -> [SizeClassStats; SIZE_CLASSES.len()] {
+        core::array::from_fn(|idx| self.classes[idx].stats)
+    }
+}
@@ -11,21 +11,19 @@ use lose_net_stack::{results::Packet, LoseStack, MacAddress, TcpFlags};
 use crate::{
     drivers::NET_DEVICE,
     net::socket::{get_socket, push_data},
-    sync::UPIntrFreeCell,
+    sync::SpinNoIrqLock,
 };
 
 use self::{port_table::check_accept, socket::set_s_a_by_index};
 
-pub struct NetStack(UPIntrFreeCell<LoseStack>);
+pub struct NetStack(SpinNoIrqLock<LoseStack>);
 
 impl NetStack {
     pub fn new() -> Self {
-        unsafe {
-            NetStack(UPIntrFreeCell::new(LoseStack::new(
-                IPv4::new(10, 0, 2, 15),
-                MacAddress::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]),
-            )))
-        }
+        NetStack(SpinNoIrqLock::new(LoseStack::new(
+            IPv4::new(10, 0, 2, 15),
+            MacAddress::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]),
+        )))
     }
 }
 
@@ -101,6 +99,16 @@ pub fn net_interrupt_handler() {
     }
 }
 
+/// Handle the packets the device has received already, without waiting for one.
+/// It is queued as work by the interrupt of the net device and called when
+/// polling sockets. Blocking reads still call `net_interrupt_handler`, as the
+/// driver only hands the device a receive buffer while waiting for a packet.
+pub fn net_poll() {
+    while NET_DEVICE.can_receive() {
+        net_interrupt_handler();
+    }
+}
+
 #[allow(unused)]
 pub fn hexdump(data: &[u8]) {
     const PRELAND_WIDTH: usize = 70;
@@ -2,8 +2,9 @@ use alloc::{sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
 use lose_net_stack::packets::tcp::TCPPacket;
 
+use crate::errno::Errno;
 use crate::fs::File;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
 use crate::task::TaskControlBlock;
 
 use super::tcp::TCP;
@@ -15,8 +16,7 @@ pub struct Port {
 }
 
 lazy_static! {
-    static ref LISTEN_TABLE: UPIntrFreeCell<Vec<Option<Port>>> =
-        unsafe { UPIntrFreeCell::new(Vec::new()) };
+    static ref LISTEN_TABLE: SpinNoIrqLock<Vec<Option<Port>>> = SpinNoIrqLock::new(Vec::new());
 }
 
 pub fn listen(port: u16) -> Option<usize> {
@@ -44,15 +44,25 @@ pub fn listen(port: u16) -> Option<usize> {
     }
 }
 
-// can accept request
-pub fn accept(listen_index: usize, task: Arc<TaskControlBlock>) {
+// can accept request, return false if the port is not listened
+pub fn accept(listen_index: usize, task: Arc<TaskControlBlock>) -> bool {
     let mut listen_table = LISTEN_TABLE.exclusive_access();
-    assert!(listen_index < listen_table.len());
-    let listen_port = listen_table[listen_index].as_mut();
-    assert!(listen_port.is_some());
-    let listen_port = listen_port.unwrap();
+    let listen_port = match listen_table.get_mut(listen_index) {
+        Some(Some(listen_port)) => listen_port,
+        _ => return false,
+    };
     listen_port.receivable = true;
     listen_port.schedule = Some(task);
+    true
+}
+
+/// Stop waiting for a connection, which has not come in.
+pub fn cancel_accept(listen_index: usize) {
+    let mut listen_table = LISTEN_TABLE.exclusive_access();
+    if let Some(Some(listen_port)) = listen_table.get_mut(listen_index) {
+        listen_port.receivable = false;
+        listen_port.schedule = None;
+    }
 }
 
 pub fn port_acceptable(listen_index: usize) -> bool {
@@ -91,7 +101,14 @@ pub fn check_accept(port: u16, tcp_packet: &TCPPacket) -> Option<()> {
 pub fn accept_connection(_port: u16, tcp_packet: &TCPPacket, task: Arc<TaskControlBlock>) {
     let process = task.process.upgrade().unwrap();
     let mut inner = process.inner_exclusive_access();
-    let fd = inner.alloc_fd();
+    let cx = task.inner_exclusive_access().get_trap_cx();
+    let fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            cx.x[10] = Errno::EMFILE.as_ret() as usize;
+            return;
+        }
+    };
 
     let tcp_socket = TCP::new(
         tcp_packet.source_ip,
@@ -102,8 +119,6 @@ pub fn accept_connection(_port: u16, tcp_packet: &TCPPacket, task: Arc<TaskContr
     );
 
     inner.fd_table[fd] = Some(Arc::new(tcp_socket));
-
-    let cx = task.inner_exclusive_access().get_trap_cx();
     cx.x[10] = fd;
 }
 
@@ -131,11 +146,11 @@ impl File for PortFd {
         false
     }
 
-    fn read(&self, _buf: crate::mm::UserBuffer) -> usize {
-        0
+    fn read(&self, _buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
     }
 
-    fn write(&self, _buf: crate::mm::UserBuffer) -> usize {
-        0
+    fn write(&self, _buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
     }
 }
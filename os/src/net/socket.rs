@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use lose_net_stack::IPv4;
 
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
 
 // TODO: specify the protocol, TCP or UDP
 pub struct Socket {
@@ -16,8 +16,7 @@ pub struct Socket {
 }
 
 lazy_static! {
-    static ref SOCKET_TABLE: UPIntrFreeCell<Vec<Option<Socket>>> =
-        unsafe { UPIntrFreeCell::new(Vec::new()) };
+    static ref SOCKET_TABLE: SpinNoIrqLock<Vec<Option<Socket>>> = SpinNoIrqLock::new(Vec::new());
 }
 
 /// get the seq and ack by socket index
@@ -121,3 +120,13 @@ pub fn pop_data(index: usize) -> Option<Vec<u8>> {
 
     socket_table[index].as_mut().unwrap().buffers.pop_front()
 }
+
+/// whether there is data received and not popped yet
+pub fn has_data(index: usize) -> bool {
+    let socket_table = SOCKET_TABLE.exclusive_access();
+
+    assert!(socket_table.len() > index);
+    assert!(socket_table[index].is_some());
+
+    !socket_table[index].as_ref().unwrap().buffers.is_empty()
+}
@@ -1,15 +1,21 @@
+use alloc::sync::Arc;
 use alloc::vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lose_net_stack::packets::tcp::TCPPacket;
 use lose_net_stack::IPv4;
 use lose_net_stack::MacAddress;
 use lose_net_stack::TcpFlags;
 
-use crate::{drivers::NET_DEVICE, fs::File};
+use crate::{
+    drivers::NET_DEVICE,
+    errno::Errno,
+    fs::{File, PollEvents, PollWaiter},
+};
 
 use super::socket::get_s_a_by_index;
 use super::{
-    net_interrupt_handler,
-    socket::{add_socket, pop_data, remove_socket},
+    net_interrupt_handler, net_poll,
+    socket::{add_socket, has_data, pop_data, remove_socket},
     LOSE_NET_STACK,
 };
 
@@ -21,6 +27,7 @@ pub struct TCP {
     pub seq: u32,
     pub ack: u32,
     pub socket_index: usize,
+    nonblock: AtomicBool,
 }
 
 impl TCP {
@@ -34,6 +41,7 @@ impl TCP {
             seq,
             ack,
             socket_index: index,
+            nonblock: AtomicBool::new(false),
         }
     }
 }
@@ -47,7 +55,10 @@ impl File for TCP {
         true
     }
 
-    fn read(&self, mut buf: crate::mm::UserBuffer) -> usize {
+    /// A non-blocking read takes the packets the device has received
+    /// already, and reads nothing if none is for this socket.
+    fn read(&self, mut buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
+        let mut polled = false;
         loop {
             if let Some(data) = pop_data(self.socket_index) {
                 let data_len = data.len();
@@ -63,14 +74,19 @@ impl File for TCP {
                         break;
                     }
                 }
-                return left;
-            } else {
+                return Ok(left);
+            } else if !self.nonblock() {
                 net_interrupt_handler();
+            } else if !polled {
+                net_poll();
+                polled = true;
+            } else {
+                return Ok(0);
             }
         }
     }
 
-    fn write(&self, buf: crate::mm::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
         let lose_net_stack = LOSE_NET_STACK.0.exclusive_access();
 
         let mut data = vec![0u8; buf.len()];
@@ -102,7 +118,31 @@ impl File for TCP {
             data: data.as_ref(),
         };
         NET_DEVICE.transmit(&tcp_packet.build_data());
-        len
+        Ok(len)
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+
+    /// Packets the device has received already are taken first.
+    fn poll(&self) -> PollEvents {
+        net_poll();
+        if has_data(self.socket_index) {
+            PollEvents::POLLIN | PollEvents::POLLOUT
+        } else {
+            PollEvents::POLLOUT
+        }
+    }
+
+    /// There is no interrupt of the net device to wake pollers up, they
+    /// poll again later, which takes new packets from the device.
+    fn register_poll(&self, _waiter: &Arc<PollWaiter>) -> bool {
+        false
     }
 }
 
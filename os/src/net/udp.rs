@@ -1,9 +1,12 @@
-use super::net_interrupt_handler;
-use super::socket::{add_socket, pop_data, remove_socket};
+use super::socket::{add_socket, has_data, pop_data, remove_socket};
 use super::LOSE_NET_STACK;
 use super::NET_DEVICE;
-use crate::fs::File;
+use super::{net_interrupt_handler, net_poll};
+use crate::errno::Errno;
+use crate::fs::{File, PollEvents, PollWaiter};
+use alloc::sync::Arc;
 use alloc::vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lose_net_stack::packets::udp::UDPPacket;
 use lose_net_stack::IPv4;
 use lose_net_stack::MacAddress;
@@ -13,6 +16,7 @@ pub struct UDP {
     pub sport: u16,
     pub dport: u16,
     pub socket_index: usize,
+    nonblock: AtomicBool,
 }
 
 impl UDP {
@@ -24,6 +28,7 @@ impl UDP {
             sport,
             dport,
             socket_index: index,
+            nonblock: AtomicBool::new(false),
         }
     }
 }
@@ -37,7 +42,10 @@ impl File for UDP {
         true
     }
 
-    fn read(&self, mut buf: crate::mm::UserBuffer) -> usize {
+    /// A non-blocking read takes the packets the device has received
+    /// already, and reads nothing if none is for this socket.
+    fn read(&self, mut buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
+        let mut polled = false;
         loop {
             if let Some(data) = pop_data(self.socket_index) {
                 let data_len = data.len();
@@ -53,14 +61,19 @@ impl File for UDP {
                         break;
                     }
                 }
-                return left;
-            } else {
+                return Ok(left);
+            } else if !self.nonblock() {
                 net_interrupt_handler();
+            } else if !polled {
+                net_poll();
+                polled = true;
+            } else {
+                return Ok(0);
             }
         }
     }
 
-    fn write(&self, buf: crate::mm::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::UserBuffer) -> Result<usize, Errno> {
         let lose_net_stack = LOSE_NET_STACK.0.exclusive_access();
 
         let mut data = vec![0u8; buf.len()];
@@ -84,7 +97,31 @@ impl File for UDP {
             data.as_ref(),
         );
         NET_DEVICE.transmit(&udp_packet.build_data());
-        len
+        Ok(len)
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+
+    /// Packets the device has received already are taken first.
+    fn poll(&self) -> PollEvents {
+        net_poll();
+        if has_data(self.socket_index) {
+            PollEvents::POLLIN | PollEvents::POLLOUT
+        } else {
+            PollEvents::POLLOUT
+        }
+    }
+
+    /// There is no interrupt of the net device to wake pollers up, they
+    /// poll again later, which takes new packets from the device.
+    fn register_poll(&self, _waiter: &Arc<PollWaiter>) -> bool {
+        false
     }
 }
 
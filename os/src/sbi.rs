@@ -3,6 +3,16 @@ pub fn set_timer(timer: usize) {
     sbi_rt::set_timer(timer as _);
 }
 
+/// use sbi call to start hart `hart_id` at `start_addr` with `a1` = `opaque`
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> bool {
+    sbi_rt::hart_start(hart_id, start_addr, opaque).error == 0
+}
+
+/// use sbi call to send a software interrupt to harts in `hart_mask`
+pub fn send_ipi(hart_mask: usize) {
+    sbi_rt::send_ipi(hart_mask, 0);
+}
+
 /// use sbi call to shutdown the kernel
 pub fn shutdown(failure: bool) -> ! {
     use sbi_rt::{system_reset, NoReason, Shutdown, SystemFailure};
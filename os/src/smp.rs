@@ -0,0 +1,112 @@
+//! Multiprocessor support.
+//!
+//! The boot hart starts the others through SBI HSM once the kernel is
+//! initialized. While running in kernel mode `tp` holds the id of the
+//! hart, the trampoline restores it from `TrapContext` on every trap
+//! from user mode.
+//!
+//! Since satp is switched and the TLB flushed on every trap entry and
+//! exit, only the harts running a user address space can hold stale
+//! translations of it. `tlb_shootdown` kicks them into the kernel with
+//! an IPI and waits until they arrive.
+//!
+//! IPIs also wake up idle harts sleeping in `wfi` when a thread becomes
+//! ready, since their timers may not fire for a long time.
+
+use crate::config::MAX_HARTS;
+use crate::sbi::{hart_start, send_ipi};
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sip;
+
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+/// Start all harts other than current one, return the number of harts started.
+pub fn start_secondary_harts() -> usize {
+    extern "C" {
+        fn _start_secondary();
+    }
+    (0..MAX_HARTS)
+        .filter(|&id| id != hart_id() && hart_start(id, _start_secondary as usize, 0))
+        .count()
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// satp of the user space each hart is running, 0 while in kernel mode
+static USER_TOKEN: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+/// number of traps from user mode taken by each hart
+static TRAP_EPOCH: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+
+/// Called first on every trap from user mode.
+pub fn enter_kernel() {
+    let hart = hart_id();
+    USER_TOKEN[hart].store(0, Ordering::SeqCst);
+    TRAP_EPOCH[hart].fetch_add(1, Ordering::SeqCst);
+}
+
+/// Called last before returning to user space `token`.
+pub fn leave_kernel(token: usize) {
+    USER_TOKEN[hart_id()].store(token, Ordering::SeqCst);
+}
+
+/// Make sure no other hart uses stale translations of user space `token`
+/// after its page table is changed.
+pub fn tlb_shootdown(token: usize) {
+    let current = hart_id();
+    let mut hart_mask = 0usize;
+    let mut epochs = [0usize; MAX_HARTS];
+    for hart in (0..MAX_HARTS).filter(|&hart| hart != current) {
+        epochs[hart] = TRAP_EPOCH[hart].load(Ordering::SeqCst);
+        if USER_TOKEN[hart].load(Ordering::SeqCst) == token {
+            hart_mask |= 1 << hart;
+        }
+    }
+    if hart_mask == 0 {
+        return;
+    }
+    send_ipi(hart_mask);
+    for hart in (0..MAX_HARTS).filter(|hart| hart_mask & (1 << hart) != 0) {
+        // user mode is always interruptible so the hart arrives soon
+        while USER_TOKEN[hart].load(Ordering::SeqCst) == token
+            && TRAP_EPOCH[hart].load(Ordering::SeqCst) == epochs[hart]
+        {
+            spin_loop();
+        }
+    }
+}
+
+/// bit i is set while hart i is idle
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_idle(idle: bool) {
+    let bit = 1 << hart_id();
+    if idle {
+        IDLE_HARTS.fetch_or(bit, Ordering::SeqCst);
+    } else {
+        IDLE_HARTS.fetch_and(!bit, Ordering::SeqCst);
+    }
+}
+
+/// Wake up an idle hart other than current one to run a ready thread.
+pub fn kick_idle_hart() {
+    let others = IDLE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+    if others != 0 {
+        send_ipi(1 << others.trailing_zeros());
+    }
+}
+
+/// Handle a software interrupt, the trap itself has flushed the TLB.
+pub fn handle_ipi() {
+    unsafe {
+        sip::clear_ssoft();
+    }
+}
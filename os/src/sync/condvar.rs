@@ -1,26 +1,28 @@
-use crate::sync::{Mutex, UPIntrFreeCell};
+use super::{remove_waiter, WaitTimeout};
+use crate::sync::{Mutex, SpinNoIrqLock};
 use crate::task::{
-    block_current_and_run_next, block_current_task, current_task, wakeup_task, TaskContext,
-    TaskControlBlock,
+    block_current_and_run_next, block_current_task, current_task, schedule, wakeup_task,
+    TaskContext, TaskControlBlock,
 };
 use alloc::{collections::VecDeque, sync::Arc};
 
 pub struct Condvar {
-    pub inner: UPIntrFreeCell<CondvarInner>,
+    pub inner: SpinNoIrqLock<CondvarInner>,
 }
 
 pub struct CondvarInner {
     pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// waits fail once closed
+    pub closed: bool,
 }
 
 impl Condvar {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPIntrFreeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+                closed: false,
+            }),
         }
     }
 
@@ -47,12 +49,64 @@ impl Condvar {
         block_current_task()
     }
 
-    pub fn wait_with_mutex(&self, mutex: Arc<dyn Mutex>) {
+    /// Return false if the condvar is closed before or while waiting, or the mutex is.
+    /// The mutex is held on return in any case but the last, when it cannot be locked.
+    pub fn wait_with_mutex(&self, mutex: Arc<dyn Mutex>) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return false;
+        }
         mutex.unlock();
-        self.inner.exclusive_session(|inner| {
-            inner.wait_queue.push_back(current_task().unwrap());
-        });
+        inner.wait_queue.push_back(current_task().unwrap());
+        drop(inner);
         block_current_and_run_next();
-        mutex.lock();
+        // locked again even if closed meanwhile
+        mutex.lock() && !self.inner.exclusive_access().closed
+    }
+
+    /// Give up waiting at `expire` cycles, return whether signaled with the mutex locked
+    /// again in either case, or `None` if closed with the mutex held as in `wait_with_mutex`.
+    pub fn wait_with_mutex_timeout(
+        self: &Arc<Self>,
+        mutex: Arc<dyn Mutex>,
+        expire: usize,
+    ) -> Option<bool> {
+        let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return None;
+        }
+        mutex.unlock();
+        let task = current_task().unwrap();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        let condvar = Arc::clone(self);
+        let waiter = Arc::clone(&task);
+        let timeout = WaitTimeout::arm(expire, task, move || {
+            remove_waiter(&mut condvar.inner.exclusive_access().wait_queue, &waiter)
+        });
+        let task_cx_ptr = block_current_task();
+        drop(inner);
+        schedule(task_cx_ptr);
+        let timed_out = timeout.disarm();
+        if !mutex.lock() || self.inner.exclusive_access().closed {
+            return None;
+        }
+        Some(!timed_out)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.exclusive_access().closed
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.inner.exclusive_access().wait_queue.is_empty()
+    }
+
+    /// Wake up all waiters, called when the condvar is destroyed or its process exits.
+    pub fn close(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.closed = true;
+        for task in inner.wait_queue.drain(..) {
+            wakeup_task(task);
+        }
     }
 }
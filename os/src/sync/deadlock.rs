@@ -0,0 +1,166 @@
+//! Deadlock avoidance for the mutexes and semaphores of a process.
+//!
+//! Every mutex is a resource with one unit and every semaphore one with
+//! as many units as its initial count. The detector tracks how many
+//! units each thread holds (allocation) and waits for (need). With
+//! detection enabled, a request is refused unless the state after it is
+//! safe, i.e. the banker's algorithm finds an order in which every
+//! thread can get what it needs.
+//!
+//! A semaphore used to signal events rather than to count resources
+//! looks like a deadlock to the detector, a `down` waiting for an `up`
+//! from another thread is always refused.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+type Units = BTreeMap<Resource, usize>;
+
+fn inc(units: &mut Units, res: Resource) {
+    *units.entry(res).or_insert(0) += 1;
+}
+
+/// Return false if there is no unit to take.
+fn dec(units: &mut Units, res: Resource) -> bool {
+    match units.get_mut(&res) {
+        Some(count) if *count > 0 => {
+            *count -= 1;
+            if *count == 0 {
+                units.remove(&res);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+pub struct DeadlockDetector {
+    enabled: bool,
+    available: Units,
+    /// keyed by tid
+    allocation: BTreeMap<usize, Units>,
+    need: BTreeMap<usize, Units>,
+}
+
+impl DeadlockDetector {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Called when a mutex or semaphore is created in slot `res`.
+    pub fn add_resource(&mut self, res: Resource, units: usize) {
+        self.available.remove(&res);
+        for units_of_thread in self.allocation.values_mut().chain(self.need.values_mut()) {
+            units_of_thread.remove(&res);
+        }
+        if units > 0 {
+            self.available.insert(res, units);
+        }
+    }
+
+    /// Called when the mutex or semaphore in slot `res` is destroyed.
+    pub fn remove_resource(&mut self, res: Resource) {
+        self.add_resource(res, 0);
+    }
+
+    /// Thread `tid` asks for a unit of `res`, return false if it is refused.
+    pub fn request(&mut self, tid: usize, res: Resource) -> bool {
+        inc(self.need.entry(tid).or_default(), res);
+        if self.enabled && !self.is_safe() {
+            self.cancel(tid, res);
+            return false;
+        }
+        true
+    }
+
+    /// The request of thread `tid` is given up, e.g. on timeout.
+    pub fn cancel(&mut self, tid: usize, res: Resource) {
+        if let Some(need) = self.need.get_mut(&tid) {
+            dec(need, res);
+        }
+    }
+
+    /// The request of thread `tid` is granted.
+    pub fn acquire(&mut self, tid: usize, res: Resource) {
+        self.cancel(tid, res);
+        dec(&mut self.available, res);
+        inc(self.allocation.entry(tid).or_default(), res);
+    }
+
+    /// Thread `tid` gives a unit back, it may not hold one for semaphores.
+    pub fn release(&mut self, tid: usize, res: Resource) {
+        if let Some(allocation) = self.allocation.get_mut(&tid) {
+            dec(allocation, res);
+        }
+        inc(&mut self.available, res);
+    }
+
+    /// Units held by an exited thread are never given back.
+    pub fn remove_thread(&mut self, tid: usize) {
+        self.allocation.remove(&tid);
+        self.need.remove(&tid);
+    }
+
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut unfinished: BTreeSet<usize> = self
+            .need
+            .keys()
+            .chain(self.allocation.keys())
+            .copied()
+            .collect();
+        loop {
+            let runnable = unfinished.iter().copied().find(|tid| {
+                self.need
+                    .get(tid)
+                    .into_iter()
+                    .flatten()
+                    .all(|(res, count)| work.get(res).copied().unwrap_or(0) >= *count)
+            });
+            match runnable {
+                Some(tid) => {
+                    // it runs to completion and gives back all it holds
+                    for (res, count) in self.allocation.get(&tid).into_iter().flatten() {
+                        *work.entry(*res).or_insert(0) += count;
+                    }
+                    unfinished.remove(&tid);
+                }
+                None => return unfinished.is_empty(),
+            }
+        }
+    }
+
+    /// Edges from waiting threads to the threads holding what they wait for.
+    pub fn wait_for_graph(&self) -> Vec<(usize, Resource, usize)> {
+        let mut edges = Vec::new();
+        for (waiter, need) in self.need.iter() {
+            for res in need.keys() {
+                for (holder, allocation) in self.allocation.iter() {
+                    if holder != waiter && allocation.contains_key(res) {
+                        edges.push((*waiter, *res, *holder));
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    pub fn dump(&self, pid: usize) {
+        println!("[kernel] wait-for graph of process {}:", pid);
+        for (tid, allocation) in self.allocation.iter() {
+            if !allocation.is_empty() {
+                println!("  thread {} holds {:?}", tid, allocation);
+            }
+        }
+        for (waiter, res, holder) in self.wait_for_graph() {
+            println!("  thread {} -> thread {} on {:?}", waiter, holder, res);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Fast user-space mutexes.
+//!
+//! A futex is a 32-bit word in user memory. Threads wait on it until
+//! another thread changes the word and wakes them up, the kernel only
+//! keeps the wait queues. Queues are keyed by the physical address of
+//! the word so that threads of different processes sharing memory wait
+//! on the same futex.
+
+use super::{remove_waiter, SpinNoIrqLock, WaitTimeout};
+use crate::errno::Errno;
+use crate::mm::PhysAddr;
+use crate::task::{block_current_task, current_task, schedule, wakeup_task, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::*;
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+/// Accepted and ignored, all futexes are keyed by physical address.
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+type WaitQueue = VecDeque<Arc<TaskControlBlock>>;
+
+lazy_static! {
+    /// Only futexes with waiters have a queue.
+    static ref FUTEX_QUEUES: SpinNoIrqLock<BTreeMap<usize, WaitQueue>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+fn futex_word(key: PhysAddr) -> &'static AtomicU32 {
+    key.get_ref()
+}
+
+/// Remove `task` from whatever queue it waits in, a requeue may have moved it.
+fn remove_futex_waiter(
+    queues: &mut BTreeMap<usize, WaitQueue>,
+    task: &Arc<TaskControlBlock>,
+) -> bool {
+    let key = queues
+        .iter_mut()
+        .find_map(|(key, queue)| remove_waiter(queue, task).then(|| *key));
+    match key {
+        Some(key) => {
+            if queues[&key].is_empty() {
+                queues.remove(&key);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Called when a thread is killed while waiting on a futex.
+pub fn futex_cancel(task: &Arc<TaskControlBlock>) {
+    remove_futex_waiter(&mut FUTEX_QUEUES.exclusive_access(), task);
+}
+
+/// Block until woken up if the word at `key` is still `val`, giving up
+/// at `expire` cycles. Fail with `EAGAIN` if the word has changed and
+/// with `ETIMEDOUT` on timeout.
+pub fn futex_wait(key: PhysAddr, val: u32, expire: Option<usize>) -> Result<(), Errno> {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    // the waker changes the word before taking the lock, so no wakeup is lost
+    if futex_word(key).load(Ordering::SeqCst) != val {
+        return Err(Errno::EAGAIN);
+    }
+    let task = current_task().unwrap();
+    queues
+        .entry(key.into())
+        .or_insert_with(VecDeque::new)
+        .push_back(Arc::clone(&task));
+    let timeout = expire.map(|expire| {
+        let waiter = Arc::clone(&task);
+        WaitTimeout::arm(expire, task, move || {
+            remove_futex_waiter(&mut FUTEX_QUEUES.exclusive_access(), &waiter)
+        })
+    });
+    let task_cx_ptr = block_current_task();
+    drop(queues);
+    schedule(task_cx_ptr);
+    match timeout {
+        Some(timeout) if timeout.disarm() => Err(Errno::ETIMEDOUT),
+        _ => Ok(()),
+    }
+}
+
+/// Wake up at most `nr_wake` waiters of `key`, return how many are woken.
+pub fn futex_wake(key: PhysAddr, nr_wake: usize) -> usize {
+    futex_requeue(key, nr_wake, key, 0)
+}
+
+/// Wake up at most `nr_wake` waiters of `key` and move at most
+/// `nr_requeue` of the rest to `key2`, return how many are woken or moved.
+pub fn futex_requeue(key: PhysAddr, nr_wake: usize, key2: PhysAddr, nr_requeue: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let mut queue = match queues.remove(&key.into()) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+    let woken = nr_wake.min(queue.len());
+    for task in queue.drain(..woken) {
+        wakeup_task(task);
+    }
+    let moved = nr_requeue.min(queue.len());
+    if moved > 0 {
+        let target = queues.entry(key2.into()).or_insert_with(VecDeque::new);
+        target.extend(queue.drain(..moved));
+    }
+    if !queue.is_empty() {
+        queues.insert(key.into(), queue);
+    }
+    woken + moved
+}
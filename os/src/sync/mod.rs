@@ -1,9 +1,18 @@
 mod condvar;
+mod deadlock;
+mod futex;
 mod mutex;
+mod rwlock;
 mod semaphore;
-mod up;
+mod spin;
+mod timeout;
 
 pub use condvar::Condvar;
+pub use deadlock::{DeadlockDetector, Resource};
+pub use futex::{futex_cancel, futex_requeue, futex_wait, futex_wake};
+pub use futex::{FUTEX_PRIVATE_FLAG, FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE};
 pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use rwlock::RwLock;
 pub use semaphore::Semaphore;
-pub use up::{UPIntrFreeCell, UPIntrRefMut};
+pub use spin::{SpinNoIrqGuard, SpinNoIrqLock};
+use timeout::{remove_waiter, WaitTimeout};
@@ -1,29 +1,45 @@
-use super::UPIntrFreeCell;
-use crate::task::TaskControlBlock;
+use super::{remove_waiter, SpinNoIrqLock, WaitTimeout};
 use crate::task::{block_current_and_run_next, suspend_current_and_run_next};
-use crate::task::{current_task, wakeup_task};
+use crate::task::{block_current_task, current_task, schedule, wakeup_task};
+use crate::task::{requeue_task, TaskControlBlock, TaskStatus};
+use crate::timer::get_time;
 use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
 
+/// Waiting for a closed mutex fails at once, so do the waits in progress
+/// when it is closed.
 pub trait Mutex: Sync + Send {
-    fn lock(&self);
+    /// Return false if the mutex is closed.
+    fn lock(&self) -> bool;
+    /// Give up at `expire` cycles, return whether the lock is taken.
+    fn lock_timeout(self: Arc<Self>, expire: usize) -> bool;
     fn unlock(&self);
+    /// Locked or waited for.
+    fn busy(&self) -> bool;
+    /// Wake up all waiters, called when the mutex is destroyed or its process exits.
+    fn close(&self);
 }
 
 pub struct MutexSpin {
-    locked: UPIntrFreeCell<bool>,
+    locked: SpinNoIrqLock<bool>,
+    closed: AtomicBool,
 }
 
 impl MutexSpin {
     pub fn new() -> Self {
         Self {
-            locked: unsafe { UPIntrFreeCell::new(false) },
+            locked: SpinNoIrqLock::new(false),
+            closed: AtomicBool::new(false),
         }
     }
 }
 
 impl Mutex for MutexSpin {
-    fn lock(&self) {
+    fn lock(&self) -> bool {
         loop {
+            if self.closed.load(Ordering::Acquire) {
+                return false;
+            }
             let mut locked = self.locked.exclusive_access();
             if *locked {
                 drop(locked);
@@ -31,8 +47,26 @@ impl Mutex for MutexSpin {
                 continue;
             } else {
                 *locked = true;
-                return;
+                return true;
+            }
+        }
+    }
+
+    fn lock_timeout(self: Arc<Self>, expire: usize) -> bool {
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return false;
+            }
+            let mut locked = self.locked.exclusive_access();
+            if !*locked {
+                *locked = true;
+                return true;
             }
+            drop(locked);
+            if get_time() >= expire {
+                return false;
+            }
+            suspend_current_and_run_next();
         }
     }
 
@@ -40,49 +74,182 @@ impl Mutex for MutexSpin {
         let mut locked = self.locked.exclusive_access();
         *locked = false;
     }
+
+    /// Spinning waiters are not tracked, only whether it is locked.
+    fn busy(&self) -> bool {
+        *self.locked.exclusive_access()
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
 }
 
+/// A mutex putting its waiters to sleep. With priority inheritance, the owner
+/// runs at the highest realtime priority among the waiters until it unlocks,
+/// and the lock is handed to the waiter of the highest priority. The boost is
+/// dropped as a whole on unlock, even if other mutexes held have waiters.
 pub struct MutexBlocking {
-    inner: UPIntrFreeCell<MutexBlockingInner>,
+    inner: SpinNoIrqLock<MutexBlockingInner>,
+    priority_inheritance: bool,
 }
 
 pub struct MutexBlockingInner {
     locked: bool,
+    closed: bool,
+    owner: Option<Arc<TaskControlBlock>>,
     wait_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
+fn priority_of(task: &Arc<TaskControlBlock>) -> usize {
+    task.inner_exclusive_access().sched.effective_priority()
+}
+
+impl MutexBlockingInner {
+    /// Take the first waiter, or the first of the highest priority.
+    fn pop_waiter(&mut self, by_priority: bool) -> Option<Arc<TaskControlBlock>> {
+        if !by_priority {
+            return self.wait_queue.pop_front();
+        }
+        let idx = (0..self.wait_queue.len())
+            .rev()
+            .max_by_key(|&idx| priority_of(&self.wait_queue[idx]))?;
+        self.wait_queue.remove(idx)
+    }
+
+    /// Let the owner inherit the highest priority among the waiters,
+    /// a higher priority inherited before is kept unless `reset`.
+    fn propagate_priority(&self, reset: bool) {
+        let owner = match self.owner.as_ref() {
+            Some(owner) => owner,
+            None => return,
+        };
+        let top = self.wait_queue.iter().map(priority_of).max().unwrap_or(0);
+        let mut owner_inner = owner.inner_exclusive_access();
+        let inherited = if reset {
+            top
+        } else {
+            owner_inner.sched.inherited_priority.max(top)
+        };
+        if inherited == owner_inner.sched.inherited_priority {
+            return;
+        }
+        owner_inner.sched.inherited_priority = inherited;
+        let ready = owner_inner.task_status == TaskStatus::Ready;
+        drop(owner_inner);
+        // move it in the ready queue, or it is queued by its new priority later
+        if ready {
+            requeue_task(owner);
+        }
+    }
+}
+
 impl MutexBlocking {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPIntrFreeCell::new(MutexBlockingInner {
-                    locked: false,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(MutexBlockingInner {
+                locked: false,
+                closed: false,
+                owner: None,
+                wait_queue: VecDeque::new(),
+            }),
+            priority_inheritance: false,
+        }
+    }
+
+    pub fn with_priority_inheritance() -> Self {
+        Self {
+            priority_inheritance: true,
+            ..Self::new()
         }
     }
 }
 
 impl Mutex for MutexBlocking {
-    fn lock(&self) {
+    fn lock(&self) -> bool {
         let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.closed {
+            return false;
+        }
         if mutex_inner.locked {
             mutex_inner.wait_queue.push_back(current_task().unwrap());
+            if self.priority_inheritance {
+                mutex_inner.propagate_priority(false);
+            }
             drop(mutex_inner);
             block_current_and_run_next();
+            // the lock is handed over by `unlock` unless closed
+            !self.inner.exclusive_access().closed
         } else {
             mutex_inner.locked = true;
+            mutex_inner.owner = current_task();
+            true
+        }
+    }
+
+    fn lock_timeout(self: Arc<Self>, expire: usize) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.closed {
+            return false;
+        }
+        if !mutex_inner.locked {
+            mutex_inner.locked = true;
+            mutex_inner.owner = current_task();
+            return true;
+        }
+        let task = current_task().unwrap();
+        mutex_inner.wait_queue.push_back(Arc::clone(&task));
+        if self.priority_inheritance {
+            mutex_inner.propagate_priority(false);
         }
+        let mutex = Arc::clone(&self);
+        let waiter = Arc::clone(&task);
+        let timeout = WaitTimeout::arm(expire, task, move || {
+            let mut mutex_inner = mutex.inner.exclusive_access();
+            let removed = remove_waiter(&mut mutex_inner.wait_queue, &waiter);
+            if removed && mutex.priority_inheritance {
+                mutex_inner.propagate_priority(true);
+            }
+            removed
+        });
+        let task_cx_ptr = block_current_task();
+        drop(mutex_inner);
+        schedule(task_cx_ptr);
+        // the lock is handed over by `unlock` unless timed out or closed
+        !timeout.disarm() && !self.inner.exclusive_access().closed
     }
 
     fn unlock(&self) {
         let mut mutex_inner = self.inner.exclusive_access();
         assert!(mutex_inner.locked);
-        if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
+        if let Some(owner) = mutex_inner.owner.take() {
+            if self.priority_inheritance {
+                owner.inner_exclusive_access().sched.inherited_priority = 0;
+            }
+        }
+        if let Some(waking_task) = mutex_inner.pop_waiter(self.priority_inheritance) {
+            mutex_inner.owner = Some(Arc::clone(&waking_task));
+            // the new owner is queued by the priority inherited from the rest
+            if self.priority_inheritance {
+                mutex_inner.propagate_priority(false);
+            }
             wakeup_task(waking_task);
         } else {
             mutex_inner.locked = false;
         }
     }
+
+    fn busy(&self) -> bool {
+        let mutex_inner = self.inner.exclusive_access();
+        mutex_inner.locked || !mutex_inner.wait_queue.is_empty()
+    }
+
+    fn close(&self) {
+        let mut mutex_inner = self.inner.exclusive_access();
+        mutex_inner.closed = true;
+        mutex_inner.owner = None;
+        for waiter in mutex_inner.wait_queue.drain(..) {
+            wakeup_task(waiter);
+        }
+    }
 }
@@ -0,0 +1,113 @@
+use crate::sync::SpinNoIrqLock;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::{collections::VecDeque, sync::Arc};
+
+/// A reader-writer lock preferring writers, new readers wait as long as
+/// a writer is waiting, and the lock goes to the next writer on unlock.
+pub struct RwLock {
+    inner: SpinNoIrqLock<RwLockInner>,
+}
+
+pub struct RwLockInner {
+    readers: usize,
+    writer: bool,
+    /// waits fail once closed
+    closed: bool,
+    read_queue: VecDeque<Arc<TaskControlBlock>>,
+    write_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RwLock {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(RwLockInner {
+                readers: 0,
+                writer: false,
+                closed: false,
+                read_queue: VecDeque::new(),
+                write_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Return false if the lock is closed.
+    pub fn read(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return false;
+        }
+        if !inner.writer && inner.write_queue.is_empty() {
+            inner.readers += 1;
+            true
+        } else {
+            // counted as a reader by the unlock waking it up
+            inner.read_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            !self.inner.exclusive_access().closed
+        }
+    }
+
+    /// Return false if the lock is closed.
+    pub fn write(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return false;
+        }
+        if !inner.writer && inner.readers == 0 {
+            inner.writer = true;
+            true
+        } else {
+            // the lock is handed over by the unlock waking it up
+            inner.write_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            !self.inner.exclusive_access().closed
+        }
+    }
+
+    /// Release a read or write lock, return false if it is not locked.
+    pub fn unlock(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.writer {
+            inner.writer = false;
+        } else if inner.readers > 0 {
+            inner.readers -= 1;
+            if inner.readers > 0 {
+                return true;
+            }
+        } else {
+            return false;
+        }
+        if let Some(writer) = inner.write_queue.pop_front() {
+            inner.writer = true;
+            wakeup_task(writer);
+        } else {
+            while let Some(reader) = inner.read_queue.pop_front() {
+                inner.readers += 1;
+                wakeup_task(reader);
+            }
+        }
+        true
+    }
+
+    /// Locked or waited for.
+    pub fn busy(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.writer || inner.readers > 0
+    }
+
+    /// Wake up all waiters, called when the lock is destroyed or its process exits.
+    pub fn close(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.closed = true;
+        let RwLockInner {
+            read_queue,
+            write_queue,
+            ..
+        } = &mut *inner;
+        for task in read_queue.drain(..).chain(write_queue.drain(..)) {
+            wakeup_task(task);
+        }
+    }
+}
@@ -1,25 +1,28 @@
-use crate::sync::UPIntrFreeCell;
+use super::{remove_waiter, WaitTimeout};
+use crate::sync::SpinNoIrqLock;
 use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use crate::task::{block_current_task, schedule};
 use alloc::{collections::VecDeque, sync::Arc};
 
 pub struct Semaphore {
-    pub inner: UPIntrFreeCell<SemaphoreInner>,
+    pub inner: SpinNoIrqLock<SemaphoreInner>,
 }
 
 pub struct SemaphoreInner {
     pub count: isize,
+    /// waits fail once closed
+    pub closed: bool,
     pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
 impl Semaphore {
     pub fn new(res_count: usize) -> Self {
         Self {
-            inner: unsafe {
-                UPIntrFreeCell::new(SemaphoreInner {
-                    count: res_count as isize,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(SemaphoreInner {
+                count: res_count as isize,
+                closed: false,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
@@ -33,13 +36,61 @@ impl Semaphore {
         }
     }
 
-    pub fn down(&self) {
+    /// Return false if the semaphore is closed.
+    pub fn down(&self) -> bool {
         let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return false;
+        }
         inner.count -= 1;
         if inner.count < 0 {
             inner.wait_queue.push_back(current_task().unwrap());
             drop(inner);
             block_current_and_run_next();
+            return !self.inner.exclusive_access().closed;
+        }
+        true
+    }
+
+    /// Give up at `expire` cycles, return whether the resource is acquired.
+    pub fn down_timeout(self: &Arc<Self>, expire: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.closed {
+            return false;
+        }
+        inner.count -= 1;
+        if inner.count >= 0 {
+            return true;
+        }
+        let task = current_task().unwrap();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        let sem = Arc::clone(self);
+        let waiter = Arc::clone(&task);
+        let timeout = WaitTimeout::arm(expire, task, move || {
+            let mut inner = sem.inner.exclusive_access();
+            // give back the resource taken by the waiter
+            let removed = remove_waiter(&mut inner.wait_queue, &waiter);
+            if removed {
+                inner.count += 1;
+            }
+            removed
+        });
+        let task_cx_ptr = block_current_task();
+        drop(inner);
+        schedule(task_cx_ptr);
+        !timeout.disarm() && !self.inner.exclusive_access().closed
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.inner.exclusive_access().wait_queue.is_empty()
+    }
+
+    /// Wake up all waiters, called when the semaphore is destroyed or its process exits.
+    pub fn close(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.closed = true;
+        for task in inner.wait_queue.drain(..) {
+            wakeup_task(task);
         }
     }
 }
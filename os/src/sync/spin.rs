@@ -0,0 +1,126 @@
+use crate::config::MAX_HARTS;
+use crate::smp::hart_id;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+/// Interrupt masking state of a hart, only accessed by the hart itself
+/// with interrupts disabled.
+pub struct IntrMaskingInfo {
+    nested_level: usize,
+    sie_before_masking: bool,
+}
+
+impl IntrMaskingInfo {
+    pub const fn new() -> Self {
+        Self {
+            nested_level: 0,
+            sie_before_masking: false,
+        }
+    }
+}
+
+struct PerHartMaskingInfo([UnsafeCell<IntrMaskingInfo>; MAX_HARTS]);
+
+unsafe impl Sync for PerHartMaskingInfo {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const MASKING_INFO_INIT: UnsafeCell<IntrMaskingInfo> = UnsafeCell::new(IntrMaskingInfo::new());
+
+static INTR_MASKING_INFO: PerHartMaskingInfo = PerHartMaskingInfo([MASKING_INFO_INIT; MAX_HARTS]);
+
+/// Disable interrupts of current hart, they are restored by the outermost `pop_off`.
+fn push_off() {
+    let sie = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    // the hart cannot change once interrupts are disabled
+    let info = unsafe { &mut *INTR_MASKING_INFO.0[hart_id()].get() };
+    if info.nested_level == 0 {
+        info.sie_before_masking = sie;
+    }
+    info.nested_level += 1;
+}
+
+fn pop_off() {
+    let info = unsafe { &mut *INTR_MASKING_INFO.0[hart_id()].get() };
+    info.nested_level -= 1;
+    if info.nested_level == 0 && info.sie_before_masking {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+const NO_OWNER: usize = usize::MAX;
+
+/// A spinlock which disables interrupts of the holding hart, so it can be
+/// shared with interrupt handlers. Taking it twice on one hart panics
+/// instead of deadlocking.
+pub struct SpinNoIrqLock<T> {
+    /// id of the hart holding the lock
+    owner: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for SpinNoIrqLock<T> {}
+
+pub struct SpinNoIrqGuard<'a, T> {
+    lock: &'a SpinNoIrqLock<T>,
+}
+
+impl<T> SpinNoIrqLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            owner: AtomicUsize::new(NO_OWNER),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Spin until the lock is free, panic if it is held by current hart.
+    pub fn exclusive_access(&self) -> SpinNoIrqGuard<'_, T> {
+        push_off();
+        let hart = hart_id();
+        while self
+            .owner
+            .compare_exchange_weak(NO_OWNER, hart, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            if self.owner.load(Ordering::Relaxed) == hart {
+                panic!("already borrowed on hart {}", hart);
+            }
+            spin_loop();
+        }
+        SpinNoIrqGuard { lock: self }
+    }
+
+    pub fn exclusive_session<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut T) -> V,
+    {
+        let mut inner = self.exclusive_access();
+        f(inner.deref_mut())
+    }
+}
+
+impl<'a, T> Drop for SpinNoIrqGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.owner.store(NO_OWNER, Ordering::Release);
+        pop_off();
+    }
+}
+
+impl<'a, T> Deref for SpinNoIrqGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+impl<'a, T> DerefMut for SpinNoIrqGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::task::{wakeup_task, TaskControlBlock};
+use crate::timer::{add_timer_callback, cancel_timer, TimerId};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Timer bounding how long a task waits in the wait queue of a
+/// synchronization primitive.
+pub struct WaitTimeout {
+    timer: TimerId,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl WaitTimeout {
+    /// Arm a timer calling `remove_waiter` at `expire` cycles. The waiter
+    /// times out and is woken up if it is still in the wait queue then,
+    /// which `remove_waiter` reports by returning true.
+    pub fn arm<F>(expire: usize, task: Arc<TaskControlBlock>, remove_waiter: F) -> Self
+    where
+        F: FnOnce() -> bool + Send + 'static,
+    {
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&timed_out);
+        let waiter = Arc::clone(&task);
+        let timer = add_timer_callback(expire, task, move || {
+            if remove_waiter() {
+                flag.store(true, Ordering::Release);
+                wakeup_task(waiter);
+            }
+        });
+        Self { timer, timed_out }
+    }
+
+    /// Called when the waiter runs again, return whether it has timed out.
+    pub fn disarm(self) -> bool {
+        cancel_timer(self.timer);
+        self.timed_out.load(Ordering::Acquire)
+    }
+}
+
+/// Remove `task` from `wait_queue`, return false if it is not there.
+pub fn remove_waiter(
+    wait_queue: &mut VecDeque<Arc<TaskControlBlock>>,
+    task: &Arc<TaskControlBlock>,
+) -> bool {
+    match wait_queue
+        .iter()
+        .position(|waiter| Arc::ptr_eq(waiter, task))
+    {
+        Some(idx) => {
+            wait_queue.remove(idx);
+            true
+        }
+        None => false,
+    }
+}
@@ -1,10 +1,11 @@
 use crate::drivers::GPU_DEVICE;
+use crate::errno::SysResult;
 use crate::mm::{MapArea, MapPermission, MapType, PhysAddr, VirtAddr};
 use crate::task::current_process;
 
 const FB_VADDR: usize = 0x10000000;
 
-pub fn sys_framebuffer() -> isize {
+pub fn sys_framebuffer() -> SysResult {
     let fb = GPU_DEVICE.get_framebuffer();
     let len = fb.len();
     // println!("[kernel] FrameBuffer: addr 0x{:X}, len {}", fb.as_ptr() as usize , len);
@@ -22,13 +23,14 @@ pub fn sys_framebuffer() -> isize {
             (FB_VADDR + len as usize).into(),
             MapType::Linear(pn_offset),
             MapPermission::R | MapPermission::W | MapPermission::U,
-        ),
+        )
+        .with_huge_pages(),
         None,
     );
-    FB_VADDR as isize
+    Ok(FB_VADDR as isize)
 }
 
-pub fn sys_framebuffer_flush() -> isize {
+pub fn sys_framebuffer_flush() -> SysResult {
     GPU_DEVICE.flush();
-    0
+    Ok(0)
 }
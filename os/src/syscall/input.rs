@@ -1,28 +1,38 @@
 //use crate::drivers::{KEYBOARD_DEVICE,MOUSE_DEVICE,INPUT_CONDVAR,read_input_event};
+use super::fs::install_file;
 use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE};
+use crate::errno::SysResult;
+use crate::fs::InputEvents;
+use alloc::sync::Arc;
 
-pub fn sys_event_get() -> isize {
+pub fn sys_event_get() -> SysResult {
     let kb = KEYBOARD_DEVICE.clone();
     let mouse = MOUSE_DEVICE.clone();
     //let input=INPUT_CONDVAR.clone();
     //read_input_event() as isize
     if !kb.is_empty() {
-        kb.read_event() as isize
+        Ok(kb.read_event() as isize)
     } else if !mouse.is_empty() {
-        mouse.read_event() as isize
+        Ok(mouse.read_event() as isize)
     } else {
-        0
+        Ok(0)
     }
 }
 
+/// Open the events of the keyboard and the mouse as a file, which can be
+/// polled instead of calling `sys_event_get` again and again.
+pub fn sys_event_open() -> SysResult {
+    install_file(Arc::new(InputEvents))
+}
+
 use crate::drivers::chardev::UART;
 
 /// check UART's read-buffer is empty or not
-pub fn sys_key_pressed() -> isize {
+pub fn sys_key_pressed() -> SysResult {
     let res = !UART.read_buffer_is_empty();
     if res {
-        1
+        Ok(1)
     } else {
-        0
+        Ok(0)
     }
 }
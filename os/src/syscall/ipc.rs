@@ -0,0 +1,168 @@
+use crate::config::{PAGE_SIZE, USER_SPACE_END};
+use crate::errno::{Errno, SysResult};
+use crate::fs::{mq_open, mq_unlink, File, MqAttr, MqFile, OpenFlags};
+use crate::mm::{
+    copy_from_user, copy_to_user, translated_byte_buffer, translated_byte_buffer_mut,
+    translated_refmut, translated_str,
+};
+use crate::mm::{MapPermission, UserBuffer, VirtAddr, SHM_MANAGER};
+use crate::task::{current_process, current_user_token};
+use alloc::sync::Arc;
+
+const SHM_VADDR: usize = 0x20000000;
+const IPC_RMID: usize = 0;
+
+/// Return the id of the segment bound to `key`.
+pub fn sys_shmget(key: usize, size: usize) -> SysResult {
+    let id = SHM_MANAGER
+        .exclusive_access()
+        .get(key, size)
+        .ok_or(Errno::EINVAL)?;
+    Ok(id as isize)
+}
+
+/// Attach segment `id` at `addr`, or at a free address if `addr` is 0. An
+/// explicit `addr` must be page aligned and the segment must fit in free user
+/// space, away from where the user stacks may grow.
+pub fn sys_shmat(id: usize, addr: usize) -> SysResult {
+    let shm = SHM_MANAGER
+        .exclusive_access()
+        .attach(id)
+        .ok_or(Errno::EINVAL)?;
+    let pages = shm.segment().page_count();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.may_grow(pages * PAGE_SIZE) {
+        return Err(Errno::ENOMEM);
+    }
+    let start_vpn = if addr == 0 {
+        inner
+            .memory_set
+            .find_free_area(VirtAddr::from(SHM_VADDR).floor(), pages)
+    } else {
+        let end = addr
+            .checked_add(pages * PAGE_SIZE)
+            .filter(|end| *end <= USER_SPACE_END)
+            .ok_or(Errno::EINVAL)?;
+        let start_va = VirtAddr::from(addr);
+        if !start_va.aligned()
+            || inner.overlaps_ustacks(addr, end)
+            || inner.memory_set.find_free_area(start_va.floor(), pages) != start_va.floor()
+        {
+            return Err(Errno::EINVAL);
+        }
+        start_va.floor()
+    };
+    inner.memory_set.insert_shared_area(
+        start_vpn,
+        shm,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+    );
+    Ok((start_vpn.0 * PAGE_SIZE) as isize)
+}
+
+pub fn sys_shmdt(addr: usize) -> SysResult {
+    let start_va = VirtAddr::from(addr);
+    if !start_va.aligned() {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.memory_set.remove_shared_area(start_va.floor()) {
+        Ok(0)
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+pub fn sys_shmctl(id: usize, cmd: usize) -> SysResult {
+    if cmd == IPC_RMID && SHM_MANAGER.exclusive_access().remove(id) {
+        Ok(0)
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+/// Open the message queue `name`, `attr` (may be null) sets the limits of a
+/// queue created with `OpenFlags::CREATE`. Return its fd.
+pub fn sys_mq_open(name: *const u8, flags: u32, attr: *const MqAttr) -> SysResult {
+    let token = current_user_token();
+    let name = translated_str(token, name)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let attr = if attr.is_null() {
+        None
+    } else {
+        Some(copy_from_user(token, attr)?)
+    };
+    let queue = mq_open(name, flags.contains(OpenFlags::CREATE), attr)?;
+    let (readable, writable) = flags.read_write();
+    let file = MqFile::new(
+        readable,
+        writable,
+        flags.contains(OpenFlags::NONBLOCK),
+        queue,
+    );
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd().ok_or(Errno::EMFILE)?;
+    inner.fd_table[fd] = Some(Arc::new(file));
+    if flags.contains(OpenFlags::CLOEXEC) {
+        inner.cloexec.insert(fd);
+    }
+    Ok(fd as isize)
+}
+
+pub fn sys_mq_unlink(name: *const u8) -> SysResult {
+    let name = translated_str(current_user_token(), name)?;
+    if mq_unlink(name.as_str()) {
+        Ok(0)
+    } else {
+        Err(Errno::ENOENT)
+    }
+}
+
+/// The file of `fd`, which must be a message queue.
+fn mq_file(fd: usize) -> Result<Arc<dyn File + Send + Sync>, Errno> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    inner
+        .fd_table
+        .get(fd)
+        .and_then(Option::as_ref)
+        .filter(|file| file.mqueue().is_some())
+        .cloned()
+        .ok_or(Errno::EBADF)
+}
+
+/// Fail with `EAGAIN` if the queue is full and opened non-blocking.
+pub fn sys_mq_send(fd: usize, buf: *const u8, len: usize, prio: usize) -> SysResult {
+    let file = mq_file(fd)?;
+    let buf = UserBuffer::new(translated_byte_buffer(current_user_token(), buf, len)?);
+    file.mqueue().unwrap().send(buf, prio)?;
+    Ok(0)
+}
+
+/// Return the length of the message received, its priority is written to
+/// `prio` if it is not null. Fail with `EAGAIN` if the queue is empty and
+/// opened non-blocking.
+pub fn sys_mq_receive(fd: usize, buf: *mut u8, len: usize, prio: *mut usize) -> SysResult {
+    let file = mq_file(fd)?;
+    let token = current_user_token();
+    let buf = UserBuffer::new(translated_byte_buffer_mut(token, buf, len)?);
+    let prio = if prio.is_null() {
+        None
+    } else {
+        Some(translated_refmut(token, prio)?)
+    };
+    let (len, msg_prio) = file.mqueue().unwrap().receive(buf)?;
+    if let Some(prio) = prio {
+        *prio = msg_prio;
+    }
+    Ok(len as isize)
+}
+
+pub fn sys_mq_getattr(fd: usize, attr: *mut MqAttr) -> SysResult {
+    let file = mq_file(fd)?;
+    copy_to_user(current_user_token(), attr, &file.mqueue().unwrap().attr())?;
+    Ok(0)
+}
@@ -1,4 +1,9 @@
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
 const SYSCALL_DUP: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_IOCTL: usize = 28;
 const SYSCALL_CONNECT: usize = 29;
 const SYSCALL_LISTEN: usize = 30;
 const SYSCALL_ACCEPT: usize = 31;
@@ -7,35 +12,90 @@ const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_PSELECT6: usize = 72;
+const SYSCALL_PPOLL: usize = 73;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_FUTEX: usize = 98;
 const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_CLOCK_SETTIME: usize = 112;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_SEND: usize = 182;
+const SYSCALL_MQ_RECEIVE: usize = 183;
+const SYSCALL_MQ_GETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_UNIX_LISTEN: usize = 201;
+const SYSCALL_UNIX_ACCEPT: usize = 202;
+const SYSCALL_UNIX_CONNECT: usize = 203;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SENDMSG: usize = 211;
+const SYSCALL_RECVMSG: usize = 212;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_DUMP_WAIT_FOR_GRAPH: usize = 470;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
+const SYSCALL_THREAD_DETACH: usize = 1003;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_LOCK_TIMEOUT: usize = 1013;
+const SYSCALL_MUTEX_DESTROY: usize = 1014;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_DOWN_TIMEOUT: usize = 1023;
+const SYSCALL_SEMAPHORE_DESTROY: usize = 1024;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_WAIT_TIMEOUT: usize = 1033;
+const SYSCALL_CONDVAR_DESTROY: usize = 1034;
+const SYSCALL_RWLOCK_CREATE: usize = 1040;
+const SYSCALL_RWLOCK_READ: usize = 1041;
+const SYSCALL_RWLOCK_WRITE: usize = 1042;
+const SYSCALL_RWLOCK_UNLOCK: usize = 1043;
+const SYSCALL_RWLOCK_DESTROY: usize = 1044;
 const SYSCALL_FRAMEBUFFER: usize = 2000;
 const SYSCALL_FRAMEBUFFER_FLUSH: usize = 2001;
 const SYSCALL_EVENT_GET: usize = 3000;
 const SYSCALL_KEY_PRESSED: usize = 3001;
+const SYSCALL_EVENT_OPEN: usize = 3002;
 
 mod fs;
 mod gui;
 mod input;
+mod ipc;
 mod net;
 mod process;
 mod sync;
@@ -44,47 +104,139 @@ mod thread;
 use fs::*;
 use gui::*;
 use input::*;
+use ipc::*;
 use net::*;
 use process::*;
 use sync::*;
 use thread::*;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
-    match syscall_id {
+use crate::errno::Errno;
+use crate::fs::{EpollEvent, MqAttr, MsgHdr, PollFd};
+use crate::task::{RLimit, RUsage, Tms};
+use crate::timer::TimeSpec;
+
+/// Dispatch a syscall, a failure is returned as a negated errno.
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    let result = match syscall_id {
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create1(args[0]),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3] as *const EpollEvent),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_pwait(
+            args[0],
+            args[1] as *mut EpollEvent,
+            args[2],
+            args[3] as isize,
+        ),
         SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
         SYSCALL_CONNECT => sys_connect(args[0] as _, args[1] as _, args[2] as _),
         SYSCALL_LISTEN => sys_listen(args[0] as _),
-        SYSCALL_ACCEPT => sys_accept(args[0] as _),
+        SYSCALL_ACCEPT => sys_accept(args[0] as _, args[1] as _),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
-        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
-        SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize, args[1] as u32),
+        SYSCALL_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PSELECT6 => sys_pselect6(
+            args[0],
+            args[1] as *mut usize,
+            args[2] as *mut usize,
+            args[3] as *mut usize,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_PPOLL => sys_ppoll(args[0] as *mut PollFd, args[1], args[2] as *const TimeSpec),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2], args[3], args[4]),
         SYSCALL_SLEEP => sys_sleep(args[0]),
+        SYSCALL_CLOCK_SETTIME => sys_clock_settime(args[0], args[1] as *const TimeSpec),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeSpec),
+        SYSCALL_CLOCK_NANOSLEEP => {
+            sys_clock_nanosleep(args[0], args[1], args[2] as *const TimeSpec)
+        }
+        SYSCALL_SCHED_SETSCHEDULER => sys_sched_setscheduler(args[0], args[1], args[2]),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_KILL => sys_kill(args[0], args[1] as u32),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], args[1] as _, args[2] as _),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1] as isize),
+        SYSCALL_TIMES => sys_times(args[0] as *mut Tms),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut RUsage),
         SYSCALL_GET_TIME => sys_get_time(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_MQ_OPEN => sys_mq_open(
+            args[0] as *const u8,
+            args[1] as u32,
+            args[2] as *const MqAttr,
+        ),
+        SYSCALL_MQ_UNLINK => sys_mq_unlink(args[0] as *const u8),
+        SYSCALL_MQ_SEND => sys_mq_send(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_MQ_RECEIVE => {
+            sys_mq_receive(args[0], args[1] as *mut u8, args[2], args[3] as *mut usize)
+        }
+        SYSCALL_MQ_GETATTR => sys_mq_getattr(args[0], args[1] as *mut MqAttr),
+        SYSCALL_SHMGET => sys_shmget(args[0], args[1]),
+        SYSCALL_SHMCTL => sys_shmctl(args[0], args[1]),
+        SYSCALL_SHMAT => sys_shmat(args[0], args[1]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
+        SYSCALL_SOCKET => sys_socket(args[0], args[1]),
+        SYSCALL_SOCKETPAIR => sys_socketpair(args[0], args[1], args[2] as *mut usize),
+        SYSCALL_BIND => sys_bind(args[0], args[1] as *const u8),
+        SYSCALL_UNIX_LISTEN => sys_unix_listen(args[0], args[1]),
+        SYSCALL_UNIX_ACCEPT => sys_unix_accept(args[0]),
+        SYSCALL_UNIX_CONNECT => sys_unix_connect(args[0], args[1] as *const u8),
+        SYSCALL_SENDTO => sys_sendto(args[0], args[1] as *const u8, args[2], args[3] as *const u8),
+        SYSCALL_RECVFROM => sys_recvfrom(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as *mut u8,
+            args[4],
+        ),
+        SYSCALL_SENDMSG => sys_sendmsg(args[0], args[1] as *const MsgHdr),
+        SYSCALL_RECVMSG => sys_recvmsg(args[0], args[1] as *mut MsgHdr),
         SYSCALL_FORK => sys_fork(),
         SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
+        SYSCALL_DUMP_WAIT_FOR_GRAPH => sys_dump_wait_for_graph(),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_GETTID => sys_gettid(),
-        SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
-        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
+        SYSCALL_WAITTID => sys_waittid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_THREAD_DETACH => sys_thread_detach(args[0]),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0]),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_MUTEX_LOCK_TIMEOUT => sys_mutex_lock_timeout(args[0], args[1]),
+        SYSCALL_MUTEX_DESTROY => sys_mutex_destroy(args[0]),
         SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
         SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
         SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_SEMAPHORE_DOWN_TIMEOUT => sys_semaphore_down_timeout(args[0], args[1]),
+        SYSCALL_SEMAPHORE_DESTROY => sys_semaphore_destroy(args[0]),
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        SYSCALL_CONDVAR_WAIT_TIMEOUT => sys_condvar_wait_timeout(args[0], args[1], args[2]),
+        SYSCALL_CONDVAR_DESTROY => sys_condvar_destroy(args[0]),
+        SYSCALL_RWLOCK_CREATE => sys_rwlock_create(),
+        SYSCALL_RWLOCK_READ => sys_rwlock_read(args[0]),
+        SYSCALL_RWLOCK_WRITE => sys_rwlock_write(args[0]),
+        SYSCALL_RWLOCK_UNLOCK => sys_rwlock_unlock(args[0]),
+        SYSCALL_RWLOCK_DESTROY => sys_rwlock_destroy(args[0]),
         SYSCALL_FRAMEBUFFER => sys_framebuffer(),
         SYSCALL_FRAMEBUFFER_FLUSH => sys_framebuffer_flush(),
         SYSCALL_EVENT_GET => sys_event_get(),
         SYSCALL_KEY_PRESSED => sys_key_pressed(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
-    }
+        SYSCALL_EVENT_OPEN => sys_event_open(),
+        _ => Err(Errno::ENOSYS),
+    };
+    result.unwrap_or_else(Errno::as_ret)
 }
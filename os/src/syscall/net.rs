@@ -1,53 +1,251 @@
-use crate::net::port_table::{accept, listen, port_acceptable, PortFd};
+use super::fs::install_file;
+use crate::errno::{Errno, SysResult};
+use crate::fs::{File, MsgHdr, OpenFlags, SocketKind, UnixSocket, AF_UNIX, SCM_MAX_FD};
+use crate::mm::UserBuffer;
+use crate::mm::{
+    copy_from_user, translated_byte_buffer, translated_byte_buffer_mut, translated_ref,
+    translated_refmut, translated_str,
+};
+use crate::net::port_table::{accept, cancel_accept, listen, port_acceptable, PortFd};
 use crate::net::udp::UDP;
-use crate::net::{net_interrupt_handler, IPv4};
-use crate::task::{current_process, current_task, current_trap_cx};
+use crate::net::{net_interrupt_handler, net_poll, IPv4};
+use crate::task::{current_process, current_task, current_trap_cx, current_user_token};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 // just support udp
-pub fn sys_connect(raddr: u32, lport: u16, rport: u16) -> isize {
+pub fn sys_connect(raddr: u32, lport: u16, rport: u16) -> SysResult {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    let fd = inner.alloc_fd();
+    let fd = inner.alloc_fd().ok_or(Errno::EMFILE)?;
     let udp_node = UDP::new(IPv4::from_u32(raddr), lport, rport);
     inner.fd_table[fd] = Some(Arc::new(udp_node));
-    fd as isize
+    Ok(fd as isize)
 }
 
 // listen a port
-pub fn sys_listen(port: u16) -> isize {
-    match listen(port) {
-        Some(port_index) => {
-            let process = current_process();
-            let mut inner = process.inner_exclusive_access();
-            let fd = inner.alloc_fd();
-            let port_fd = PortFd::new(port_index);
-            inner.fd_table[fd] = Some(Arc::new(port_fd));
-
-            // NOTICE: this return the port index, not the fd
-            port_index as isize
-        }
-        None => -1,
-    }
+pub fn sys_listen(port: u16) -> SysResult {
+    let port_index = listen(port).ok_or(Errno::EADDRINUSE)?;
+    // the port is released with `port_fd` if there is no fd left
+    let port_fd = PortFd::new(port_index);
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd().ok_or(Errno::EMFILE)?;
+    inner.fd_table[fd] = Some(Arc::new(port_fd));
+
+    // NOTICE: this return the port index, not the fd
+    Ok(port_index as isize)
 }
 
-// accept a tcp connection
-pub fn sys_accept(port_index: usize) -> isize {
+/// Accept a tcp connection, with `OpenFlags::NONBLOCK` in `flags` fail with
+/// `EAGAIN` unless the device has received a request already.
+pub fn sys_accept(port_index: usize, flags: u32) -> SysResult {
+    let nonblock = match OpenFlags::from_bits(flags) {
+        Some(flags) if OpenFlags::NONBLOCK.contains(flags) => !flags.is_empty(),
+        _ => return Err(Errno::EINVAL),
+    };
     println!("accepting port {}", port_index);
 
     let task = current_task().unwrap();
-    accept(port_index, task);
+    if !accept(port_index, task) {
+        return Err(Errno::EINVAL);
+    }
     // block_current_and_run_next();
 
     // NOTICE: There does not have interrupt handler, just call it munually.
-    loop {
+    if nonblock {
+        net_poll();
+        if port_acceptable(port_index) {
+            cancel_accept(port_index);
+            return Err(Errno::EAGAIN);
+        }
+    }
+    while port_acceptable(port_index) {
         net_interrupt_handler();
+    }
+
+    // the fd, or the errno if it could not be allocated
+    let cx = current_trap_cx();
+    Ok(cx.x[10] as isize)
+}
+
+/// The file of `fd`, which must be a unix domain socket.
+fn unix_socket(fd: usize) -> Result<Arc<dyn File + Send + Sync>, Errno> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = inner
+        .fd_table
+        .get(fd)
+        .and_then(Option::as_ref)
+        .ok_or(Errno::EBADF)?;
+    if file.unix_socket().is_none() {
+        return Err(Errno::ENOTSOCK);
+    }
+    Ok(Arc::clone(file))
+}
+
+/// The kind of a socket of `domain` and `type_`, only unix domain sockets
+/// can be created by fd.
+fn socket_kind(domain: usize, type_: usize) -> Result<SocketKind, Errno> {
+    if domain != AF_UNIX {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    SocketKind::from_type(type_).ok_or(Errno::EINVAL)
+}
+
+pub fn sys_socket(domain: usize, type_: usize) -> SysResult {
+    install_file(UnixSocket::new(socket_kind(domain, type_)?))
+}
+
+pub fn sys_socketpair(domain: usize, type_: usize, sv: *mut usize) -> SysResult {
+    let kind = socket_kind(domain, type_)?;
+    let token = current_user_token();
+    let sv_a = translated_refmut(token, sv)?;
+    let sv_b = translated_refmut(token, sv.wrapping_add(1))?;
+    let (a, b) = UnixSocket::pair(kind);
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd_a = inner.alloc_fd().ok_or(Errno::EMFILE)?;
+    inner.fd_table[fd_a] = Some(a);
+    let fd_b = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[fd_a] = None;
+            return Err(Errno::EMFILE);
+        }
+    };
+    inner.fd_table[fd_b] = Some(b);
+    *sv_a = fd_a;
+    *sv_b = fd_b;
+    Ok(0)
+}
+
+/// Name a unix domain socket by the file `path`.
+pub fn sys_bind(fd: usize, path: *const u8) -> SysResult {
+    let path = translated_str(current_user_token(), path)?;
+    let file = unix_socket(fd)?;
+    file.unix_socket().unwrap().bind(path)?;
+    Ok(0)
+}
+
+pub fn sys_unix_listen(fd: usize, backlog: usize) -> SysResult {
+    let file = unix_socket(fd)?;
+    file.unix_socket().unwrap().listen(backlog)?;
+    Ok(0)
+}
+
+/// Block until a connection comes, return the fd of this end.
+pub fn sys_unix_accept(fd: usize) -> SysResult {
+    let file = unix_socket(fd)?;
+    let socket = file.unix_socket().unwrap().accept()?;
+    install_file(socket)
+}
+
+pub fn sys_unix_connect(fd: usize, path: *const u8) -> SysResult {
+    let path = translated_str(current_user_token(), path)?;
+    let file = unix_socket(fd)?;
+    file.unix_socket().unwrap().connect(path.as_str())?;
+    Ok(0)
+}
+
+/// Send a datagram to the socket bound to `path`, or to the peer if
+/// `path` is null.
+pub fn sys_sendto(fd: usize, buf: *const u8, len: usize, path: *const u8) -> SysResult {
+    let file = unix_socket(fd)?;
+    let token = current_user_token();
+    let path = if path.is_null() {
+        None
+    } else {
+        Some(translated_str(token, path)?)
+    };
+    let buf = UserBuffer::new(translated_byte_buffer(token, buf, len)?);
+    let sent = file
+        .unix_socket()
+        .unwrap()
+        .send(buf, path.as_deref(), Vec::new())?;
+    Ok(sent as isize)
+}
 
-        if !port_acceptable(port_index) {
-            break;
+/// The name of the sender, if any, is written to `from` of `from_len`
+/// bytes with a '\0' at the end.
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    from: *mut u8,
+    from_len: usize,
+) -> SysResult {
+    let file = unix_socket(fd)?;
+    let token = current_user_token();
+    let buf = UserBuffer::new(translated_byte_buffer_mut(token, buf, len)?);
+    let name_buf = if !from.is_null() && from_len > 0 {
+        Some(UserBuffer::new(translated_byte_buffer_mut(
+            token, from, from_len,
+        )?))
+    } else {
+        None
+    };
+    let received = file.unix_socket().unwrap().receive(buf)?;
+    if let Some(name_buf) = name_buf {
+        let name = received.from.unwrap_or_default();
+        let mut name_buf = name_buf.into_iter();
+        for byte in name.bytes().take(from_len - 1).chain(Some(0)) {
+            unsafe {
+                *name_buf.next().unwrap() = byte;
+            }
         }
     }
+    Ok(received.len as isize)
+}
 
-    let cx = current_trap_cx();
-    cx.x[10] as isize
+/// Send the fds in `msg` along with the data, unix sockets cannot be among them.
+pub fn sys_sendmsg(fd: usize, msg: *const MsgHdr) -> SysResult {
+    let file = unix_socket(fd)?;
+    let token = current_user_token();
+    let msg = copy_from_user(token, msg)?;
+    if msg.fds_len > SCM_MAX_FD {
+        return Err(Errno::EINVAL);
+    }
+    let mut files = Vec::new();
+    for i in 0..msg.fds_len {
+        let fd = *translated_ref(token, msg.fds.wrapping_add(i))?;
+        let process = current_process();
+        let inner = process.inner_exclusive_access();
+        let file = inner
+            .fd_table
+            .get(fd)
+            .and_then(Option::as_ref)
+            .ok_or(Errno::EBADF)?;
+        files.push(Arc::clone(file));
+    }
+    let buf = UserBuffer::new(translated_byte_buffer(token, msg.buf, msg.len)?);
+    let sent = file.unix_socket().unwrap().send(buf, None, files)?;
+    Ok(sent as isize)
+}
+
+/// Fds received are written to `fds` of `msg` and their number to
+/// `fds_len`, the files not fitting in are closed.
+pub fn sys_recvmsg(fd: usize, msg: *mut MsgHdr) -> SysResult {
+    let file = unix_socket(fd)?;
+    let token = current_user_token();
+    let hdr = copy_from_user(token, msg as *const MsgHdr)?;
+    // checked before receiving, so that no message is lost
+    let fds_len_ptr = unsafe { core::ptr::addr_of_mut!((*msg).fds_len) };
+    let fds_len_ref = translated_refmut(token, fds_len_ptr)?;
+    // no more fds than `SCM_MAX_FD` are received at once
+    let fds = (0..hdr.fds_len.min(SCM_MAX_FD))
+        .map(|i| translated_refmut(token, hdr.fds.wrapping_add(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let buf = UserBuffer::new(translated_byte_buffer_mut(token, hdr.buf, hdr.len)?);
+    let received = file.unix_socket().unwrap().receive(buf)?;
+    let mut fds_len = 0;
+    for (file, fd_ref) in received.files.into_iter().zip(fds) {
+        match install_file(file) {
+            Ok(new_fd) => *fd_ref = new_fd as usize,
+            Err(_) => break,
+        }
+        fds_len += 1;
+    }
+    *fds_len_ref = fds_len;
+    Ok(received.len as isize)
 }
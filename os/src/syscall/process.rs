@@ -1,35 +1,46 @@
+use crate::config::USER_STACK_MAX_SIZE;
+use crate::errno::{Errno, SysResult};
 use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use crate::mm::{copy_from_user, copy_to_user, translated_ref, translated_refmut, translated_str};
 use crate::task::{
-    current_process, current_task, current_user_token, exit_current_and_run_next, pid2process,
-    suspend_current_and_run_next, SignalFlags,
+    all_processes, current_process, current_task, current_user_token, cycles_to_ticks,
+    exit_current_and_run_next, exit_group_and_run_next, pgid2processes, pid2process,
+    suspend_current_and_run_next, ProcessControlBlock, RLimit, RUsage, SignalAction, SignalFlags,
+    Tms, INITPROC, RLIMIT_CPU, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD,
 };
-use crate::timer::get_time_ms;
+use crate::timer::{get_time, get_time_ms};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+/// Exit current thread, the process exits with its last thread.
 pub fn sys_exit(exit_code: i32) -> ! {
     exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+/// Exit current process with all its threads.
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    exit_group_and_run_next(exit_code);
+    panic!("Unreachable in sys_exit_group!");
+}
+
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+pub fn sys_get_time() -> SysResult {
+    Ok(get_time_ms() as isize)
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().process.upgrade().unwrap().getpid() as isize
+pub fn sys_getpid() -> SysResult {
+    Ok(current_task().unwrap().process.upgrade().unwrap().getpid() as isize)
 }
 
-pub fn sys_fork() -> isize {
+pub fn sys_fork() -> SysResult {
     let current_process = current_process();
-    let new_process = current_process.fork();
+    let new_process = current_process.fork().ok_or(Errno::EAGAIN)?;
     let new_pid = new_process.getpid();
     // modify trap context of new_task, because it returns immediately after switching
     let new_process_inner = new_process.inner_exclusive_access();
@@ -38,39 +49,66 @@ pub fn sys_fork() -> isize {
     // we do not have to move to next instruction since we have done it before
     // for child process, fork returns 0
     trap_cx.x[10] = 0;
-    new_pid as isize
+    Ok(new_pid as isize)
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
+/// Space the arguments may take on the new user stack, a quarter of it as in Linux.
+const ARG_MAX: usize = USER_STACK_MAX_SIZE / 4;
+
+/// Fail with `E2BIG` if the arguments do not fit in `ARG_MAX` and with
+/// `EBUSY` if current process has more than one thread.
+pub fn sys_exec(path: *const u8, mut args: *const usize) -> SysResult {
     let token = current_user_token();
-    let path = translated_str(token, path);
+    let path = translated_str(token, path)?;
     let mut args_vec: Vec<String> = Vec::new();
+    // the null terminated argv array and the alignment
+    let mut args_size = 2 * core::mem::size_of::<usize>();
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = *translated_ref(token, args)?;
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
+        let arg = translated_str(token, arg_str_ptr as *const u8)?;
+        args_size += core::mem::size_of::<usize>() + arg.len() + 1;
+        if args_size > ARG_MAX {
+            return Err(Errno::E2BIG);
+        }
+        args_vec.push(arg);
         unsafe {
             args = args.add(1);
         }
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
+    let app_inode = open_file(path.as_str(), OpenFlags::RDONLY).ok_or(Errno::ENOENT)?;
+    let all_data = app_inode.read_all();
+    if xmas_elf::ElfFile::new(all_data.as_slice()).is_err() {
+        return Err(Errno::ENOEXEC);
     }
+    let process = current_process();
+    let argc = args_vec.len();
+    process.exec(all_data.as_slice(), args_vec)?;
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc as isize)
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Also report children stopped by a signal.
+pub const WUNTRACED: usize = 2;
+
+/// If there is not a child process whose pid is same as given, fail with
+/// `ECHILD`. Else if there is a child process but it is still running, fail
+/// with `EAGAIN`.
+///
+/// Without options the raw exit code is stored, with `WUNTRACED` the status
+/// is encoded as in POSIX: `code << 8` for an exit and `signum << 8 | 0x7f`
+/// for a stop. Nothing is stored if `exit_code_ptr` is null.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> SysResult {
     let process = current_process();
+    // checked before a child is reaped, and before the PCB is locked
+    // as the pointer may be on a stack page yet to be faulted in
+    let exit_code_ref = if exit_code_ptr.is_null() {
+        None
+    } else {
+        Some(translated_refmut(current_user_token(), exit_code_ptr)?)
+    };
     // find a child process
 
     let mut inner = process.inner_exclusive_access();
@@ -79,7 +117,7 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         .iter()
         .any(|p| pid == -1 || pid as usize == p.getpid())
     {
-        return -1;
+        return Err(Errno::ECHILD);
         // ---- release current PCB
     }
     let pair = inner.children.iter().enumerate().find(|(_, p)| {
@@ -88,30 +126,241 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         // ++++ release child PCB
     });
     if let Some((idx, _)) = pair {
+        // the exiting hart may still hold the child for a moment, it will be
+        // deallocated when that reference is dropped
         let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
         // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        let mut exit_code = child_inner.exit_code;
+        let mut child_times = child_inner.cpu_times();
+        child_times.add(&child_inner.children_times);
+        drop(child_inner);
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
+        inner.children_times.add(&child_times);
+        if options & WUNTRACED != 0 {
+            exit_code = (exit_code & 0xff) << 8;
+        }
+        if let Some(exit_code_ref) = exit_code_ref {
+            *exit_code_ref = exit_code;
+        }
+        return Ok(found_pid as isize);
+    }
+    if options & WUNTRACED != 0 {
+        for child in inner.children.iter() {
+            if pid != -1 && pid as usize != child.getpid() {
+                continue;
+            }
+            let stop_signal = child.inner_exclusive_access().unreported_stop.take();
+            if let Some(signum) = stop_signal {
+                if let Some(exit_code_ref) = exit_code_ref {
+                    *exit_code_ref = ((signum as i32) << 8) | 0x7f;
+                }
+                return Ok(child.getpid() as isize);
+            }
+        }
     }
+    Err(Errno::EAGAIN)
     // ---- release current PCB automatically
 }
 
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
-    if let Some(process) = pid2process(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            process.inner_exclusive_access().signals |= flag;
-            0
-        } else {
-            -1
-        }
+/// Send signal `signum` to process `pid`, or to process group `-pid` if `pid` is negative.
+/// `pid` 0 means the group of current process and -1 every process but init.
+/// `signum` 0 only checks that the target exists.
+pub fn sys_kill(pid: isize, signum: usize) -> SysResult {
+    let targets = match pid {
+        0 => pgid2processes(current_process().inner_exclusive_access().pgid),
+        -1 => all_processes()
+            .into_iter()
+            .filter(|process| !Arc::ptr_eq(process, &INITPROC))
+            .collect(),
+        _ if pid < 0 => pgid2processes(pid.unsigned_abs()),
+        _ => pid2process(pid as usize).into_iter().collect(),
+    };
+    if targets.is_empty() {
+        return Err(Errno::ESRCH);
+    }
+    if signum == 0 {
+        return Ok(0);
+    }
+    let flag = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    for process in targets {
+        process.inner_exclusive_access().add_signal(flag);
+    }
+    Ok(0)
+}
+
+/// Process `pid` of current process, 0 means current process itself.
+fn pid_or_current(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    if pid == 0 {
+        Some(current_process())
+    } else {
+        pid2process(pid)
+    }
+}
+
+/// Move process `pid` into group `pgid`, 0 for either means the pid of the target.
+/// The target must be current process or one of its children, and the group
+/// must be in the same session.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    let current = current_process();
+    let target = pid_or_current(pid).ok_or(Errno::ESRCH)?;
+    let is_child = current
+        .inner_exclusive_access()
+        .children
+        .iter()
+        .any(|child| Arc::ptr_eq(child, &target));
+    if !Arc::ptr_eq(&current, &target) && !is_child {
+        return Err(Errno::ESRCH);
+    }
+    let target_pid = target.getpid();
+    let pgid = if pgid == 0 { target_pid } else { pgid };
+    let sid = current.inner_exclusive_access().sid;
+    let target_inner = target.inner_exclusive_access();
+    // a session leader cannot leave its group
+    if target_inner.sid != sid || target_inner.sid == target_pid {
+        return Err(Errno::EPERM);
+    }
+    drop(target_inner);
+    if pgid != target_pid
+        && !pgid2processes(pgid)
+            .iter()
+            .any(|process| process.inner_exclusive_access().sid == sid)
+    {
+        return Err(Errno::EPERM);
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    Ok(0)
+}
+
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    let process = pid_or_current(pid).ok_or(Errno::ESRCH)?;
+    let pgid = process.inner_exclusive_access().pgid;
+    Ok(pgid as isize)
+}
+
+pub fn sys_getsid(pid: usize) -> SysResult {
+    let process = pid_or_current(pid).ok_or(Errno::ESRCH)?;
+    let sid = process.inner_exclusive_access().sid;
+    Ok(sid as isize)
+}
+
+/// Start a new session led by current process, which must not be a group leader.
+pub fn sys_setsid() -> SysResult {
+    let process = current_process();
+    let pid = process.getpid();
+    let mut inner = process.inner_exclusive_access();
+    if inner.pgid == pid {
+        return Err(Errno::EPERM);
+    }
+    inner.sid = pid;
+    inner.pgid = pid;
+    Ok(pid as isize)
+}
+
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SysResult {
+    let token = current_user_token();
+    let process = current_process();
+    match SignalFlags::from_signum(signum) {
+        Some(signal) if !SignalFlags::UNCATCHABLE.contains(signal) => {}
+        _ => return Err(Errno::EINVAL),
+    }
+    // copied without the lock, it may fault in a stack page
+    let action = if action.is_null() {
+        None
     } else {
-        -1
+        Some(copy_from_user(token, action)?)
+    };
+    let mut inner = process.inner_exclusive_access();
+    let old = inner.signal_actions.table[signum];
+    if let Some(action) = action {
+        inner.signal_actions.table[signum] = SignalAction {
+            handler: action.handler,
+            mask: SignalFlags::from_bits_truncate(action.mask.bits()),
+        };
+    }
+    drop(inner);
+    if !old_action.is_null() {
+        copy_to_user(token, old_action, &old)?;
+    }
+    Ok(0)
+}
+
+/// Replace the signal mask of current process, return the old one.
+pub fn sys_sigprocmask(mask: u32) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    inner.signal_mask = SignalFlags::from_bits_truncate(mask) - SignalFlags::UNCATCHABLE;
+    Ok(old_mask.bits() as isize)
+}
+
+/// Return from a signal handler to the interrupted context.
+pub fn sys_sigreturn() -> SysResult {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let backup = task_inner.trap_cx_backup.take().ok_or(Errno::EINVAL)?;
+    task_inner.handling_sig = None;
+    let trap_cx = task_inner.get_trap_cx();
+    *trap_cx = backup;
+    // a0 will be overwritten by the return value
+    Ok(trap_cx.x[10] as isize)
+}
+
+pub fn sys_getrlimit(resource: usize, rlimit: *mut RLimit) -> SysResult {
+    let process = current_process();
+    let limit = process
+        .inner_exclusive_access()
+        .rlimits
+        .get(resource)
+        .ok_or(Errno::EINVAL)?;
+    copy_to_user(current_user_token(), rlimit, &limit)?;
+    Ok(0)
+}
+
+/// The soft limit must not exceed the hard one, which can only be lowered.
+pub fn sys_setrlimit(resource: usize, rlimit: *const RLimit) -> SysResult {
+    let limit = copy_from_user(current_user_token(), rlimit)?;
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner.rlimits.set(resource, limit)?;
+    if resource == RLIMIT_CPU {
+        inner.xcpu_sent = false;
     }
+    Ok(0)
+}
+
+/// `who` is `RUSAGE_SELF`, `RUSAGE_CHILDREN` for the reaped children or `RUSAGE_THREAD`.
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> SysResult {
+    let task = current_task().unwrap();
+    let times = match who {
+        RUSAGE_SELF => current_process().inner_exclusive_access().cpu_times(),
+        RUSAGE_CHILDREN => current_process().inner_exclusive_access().children_times,
+        RUSAGE_THREAD => task.inner_exclusive_access().times.times,
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(current_user_token(), usage, &times.into())?;
+    Ok(0)
+}
+
+/// Return clock ticks elapsed since boot.
+pub fn sys_times(tms: *mut Tms) -> SysResult {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let times = inner.cpu_times();
+    let children_times = inner.children_times;
+    let token = inner.memory_set.token();
+    drop(inner);
+    let tms_value = Tms {
+        tms_utime: cycles_to_ticks(times.user),
+        tms_stime: cycles_to_ticks(times.kernel),
+        tms_cutime: cycles_to_ticks(children_times.user),
+        tms_cstime: cycles_to_ticks(children_times.kernel),
+    };
+    copy_to_user(token, tms, &tms_value)?;
+    Ok(cycles_to_ticks(get_time()) as isize)
 }
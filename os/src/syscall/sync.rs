@@ -1,22 +1,122 @@
-use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
-use crate::task::{block_current_and_run_next, current_process, current_task};
-use crate::timer::{add_timer, get_time_ms};
+use crate::errno::{Errno, SysResult};
+use crate::mm::{copy_from_user, copy_to_user, translated_ref, PhysAddr};
+use crate::sync::{futex_requeue, futex_wait, futex_wake};
+use crate::sync::{Condvar, DeadlockDetector, Mutex, MutexBlocking, MutexSpin, Semaphore};
+use crate::sync::{Resource, RwLock};
+use crate::sync::{FUTEX_PRIVATE_FLAG, FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE};
+use crate::task::{block_current_and_run_next, current_process, current_task, current_user_token};
+use crate::timer::{add_timer, get_time, realtime, realtime_to_cycles, set_realtime};
+use crate::timer::{us_to_cycles, TimeSpec};
+use crate::timer::{CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME};
+use crate::timer::{CLOCK_THREAD_CPUTIME_ID, TIMER_ABSTIME};
 use alloc::sync::Arc;
 
-pub fn sys_sleep(ms: usize) -> isize {
-    let expire_ms = get_time_ms() + ms;
+/// Block current thread until `expire` cycles.
+fn sleep_until(expire: usize) {
+    if expire <= get_time() {
+        return;
+    }
     let task = current_task().unwrap();
-    add_timer(expire_ms, task);
+    add_timer(expire, task);
     block_current_and_run_next();
-    0
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
-    let process = current_process();
-    let mutex: Option<Arc<dyn Mutex>> = if !blocking {
-        Some(Arc::new(MutexSpin::new()))
+pub fn sys_sleep(ms: usize) -> SysResult {
+    sleep_until(get_time() + us_to_cycles(ms * 1000));
+    Ok(0)
+}
+
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> SysResult {
+    let cycles = match clock_id {
+        CLOCK_REALTIME => {
+            copy_to_user(current_user_token(), tp, &realtime())?;
+            return Ok(0);
+        }
+        CLOCK_MONOTONIC => get_time(),
+        CLOCK_PROCESS_CPUTIME_ID => current_process()
+            .inner_exclusive_access()
+            .cpu_times()
+            .total(),
+        CLOCK_THREAD_CPUTIME_ID => current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .times
+            .times
+            .total(),
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(current_user_token(), tp, &TimeSpec::from_cycles(cycles))?;
+    Ok(0)
+}
+
+/// Only `CLOCK_REALTIME` can be set.
+pub fn sys_clock_settime(clock_id: usize, tp: *const TimeSpec) -> SysResult {
+    if clock_id != CLOCK_REALTIME {
+        return Err(Errno::EINVAL);
+    }
+    let time = copy_from_user(current_user_token(), tp)?;
+    if set_realtime(&time) {
+        Ok(0)
     } else {
-        Some(Arc::new(MutexBlocking::new()))
+        Err(Errno::EINVAL)
+    }
+}
+
+/// Sleep for `req`, or until the clock reaches `req` with `TIMER_ABSTIME`.
+pub fn sys_clock_nanosleep(clock_id: usize, flags: usize, req: *const TimeSpec) -> SysResult {
+    let req = copy_from_user(current_user_token(), req)?;
+    let expire = match (clock_id, flags & TIMER_ABSTIME != 0) {
+        (CLOCK_REALTIME, true) => realtime_to_cycles(&req),
+        (CLOCK_MONOTONIC, true) => req.to_cycles(),
+        (CLOCK_REALTIME | CLOCK_MONOTONIC, false) => req
+            .to_cycles()
+            .map(|cycles| get_time().saturating_add(cycles)),
+        _ => return Err(Errno::EINVAL),
+    };
+    sleep_until(expire.ok_or(Errno::EINVAL)?);
+    Ok(0)
+}
+
+fn current_tid() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .tid
+}
+
+/// Run `f` on the deadlock detector of current process, unless the process
+/// has exited while the thread was blocked.
+fn with_detector(f: impl FnOnce(&mut DeadlockDetector)) {
+    if let Some(process) = current_task().unwrap().process.upgrade() {
+        f(&mut process.inner_exclusive_access().deadlock);
+    }
+}
+
+/// The sync object in slot `id`, `EINVAL` if it does not exist or is destroyed.
+fn find<T: ?Sized>(list: &[Option<Arc<T>>], id: usize) -> Result<Arc<T>, Errno> {
+    list.get(id).cloned().flatten().ok_or(Errno::EINVAL)
+}
+
+/// Deadline in cycles of a relative timeout in microseconds.
+fn deadline(timeout_us: usize) -> usize {
+    get_time().saturating_add(us_to_cycles(timeout_us))
+}
+
+pub const MUTEX_SPIN: usize = 0;
+pub const MUTEX_BLOCKING: usize = 1;
+/// a blocking mutex with priority inheritance
+pub const MUTEX_PI: usize = 2;
+
+pub fn sys_mutex_create(kind: usize) -> SysResult {
+    let process = current_process();
+    let mutex: Option<Arc<dyn Mutex>> = match kind {
+        MUTEX_SPIN => Some(Arc::new(MutexSpin::new())),
+        MUTEX_BLOCKING => Some(Arc::new(MutexBlocking::new())),
+        MUTEX_PI => Some(Arc::new(MutexBlocking::with_priority_inheritance())),
+        _ => return Err(Errno::EINVAL),
     };
     let mut process_inner = process.inner_exclusive_access();
     if let Some(id) = process_inner
@@ -27,34 +127,88 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         .map(|(id, _)| id)
     {
         process_inner.mutex_list[id] = mutex;
-        id as isize
+        process_inner.deadlock.add_resource(Resource::Mutex(id), 1);
+        Ok(id as isize)
     } else {
         process_inner.mutex_list.push(mutex);
-        process_inner.mutex_list.len() as isize - 1
+        let id = process_inner.mutex_list.len() - 1;
+        process_inner.deadlock.add_resource(Resource::Mutex(id), 1);
+        Ok(id as isize)
     }
 }
 
-pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+/// Fail with `EDEADLK` if waiting for the mutex may deadlock.
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
+    let tid = current_tid();
+    let res = Resource::Mutex(mutex_id);
     let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
+    if !process_inner.deadlock.request(tid, res) {
+        return Err(Errno::EDEADLK);
+    }
     drop(process_inner);
     drop(process);
-    mutex.lock();
-    0
+    if !mutex.lock() {
+        with_detector(|detector| detector.cancel(tid, res));
+        return Err(Errno::EINVAL);
+    }
+    with_detector(|detector| detector.acquire(tid, res));
+    Ok(0)
 }
 
-pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+/// Fail with `ETIMEDOUT` if the mutex is not taken within `timeout_us` microseconds.
+pub fn sys_mutex_lock_timeout(mutex_id: usize, timeout_us: usize) -> SysResult {
+    let expire = deadline(timeout_us);
+    let tid = current_tid();
+    let res = Resource::Mutex(mutex_id);
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
+    if !process_inner.deadlock.request(tid, res) {
+        return Err(Errno::EDEADLK);
+    }
+    drop(process_inner);
+    drop(process);
+    if mutex.lock_timeout(expire) {
+        with_detector(|detector| detector.acquire(tid, res));
+        Ok(0)
+    } else {
+        with_detector(|detector| detector.cancel(tid, res));
+        Err(Errno::ETIMEDOUT)
+    }
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
     drop(process_inner);
     drop(process);
     mutex.unlock();
-    0
+    with_detector(|detector| detector.release(current_tid(), Resource::Mutex(mutex_id)));
+    Ok(0)
+}
+
+/// Fail with `EBUSY` if the mutex is locked or waited for.
+pub fn sys_mutex_destroy(mutex_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
+    if mutex.busy() {
+        return Err(Errno::EBUSY);
+    }
+    process_inner.mutex_list[mutex_id] = None;
+    process_inner
+        .deadlock
+        .remove_resource(Resource::Mutex(mutex_id));
+    drop(process_inner);
+    // a thread which has found the mutex just before fails to wait for it
+    mutex.close();
+    Ok(0)
 }
 
-pub fn sys_semaphore_create(res_count: usize) -> isize {
+pub fn sys_semaphore_create(res_count: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -72,28 +226,82 @@ pub fn sys_semaphore_create(res_count: usize) -> isize {
             .push(Some(Arc::new(Semaphore::new(res_count))));
         process_inner.semaphore_list.len() - 1
     };
-    id as isize
+    process_inner
+        .deadlock
+        .add_resource(Resource::Semaphore(id), res_count);
+    Ok(id as isize)
 }
 
-pub fn sys_semaphore_up(sem_id: usize) -> isize {
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let sem = find(&process_inner.semaphore_list, sem_id)?;
     drop(process_inner);
     sem.up();
-    0
+    with_detector(|detector| detector.release(current_tid(), Resource::Semaphore(sem_id)));
+    Ok(0)
 }
 
-pub fn sys_semaphore_down(sem_id: usize) -> isize {
+/// Fail with `EDEADLK` if waiting for the semaphore may deadlock.
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
+    let tid = current_tid();
+    let res = Resource::Semaphore(sem_id);
     let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = find(&process_inner.semaphore_list, sem_id)?;
+    if !process_inner.deadlock.request(tid, res) {
+        return Err(Errno::EDEADLK);
+    }
+    drop(process_inner);
+    drop(process);
+    if !sem.down() {
+        with_detector(|detector| detector.cancel(tid, res));
+        return Err(Errno::EINVAL);
+    }
+    with_detector(|detector| detector.acquire(tid, res));
+    Ok(0)
+}
+
+/// Fail with `ETIMEDOUT` if the semaphore is not acquired within `timeout_us` microseconds.
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout_us: usize) -> SysResult {
+    let expire = deadline(timeout_us);
+    let tid = current_tid();
+    let res = Resource::Semaphore(sem_id);
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = find(&process_inner.semaphore_list, sem_id)?;
+    if !process_inner.deadlock.request(tid, res) {
+        return Err(Errno::EDEADLK);
+    }
     drop(process_inner);
-    sem.down();
-    0
+    drop(process);
+    if sem.down_timeout(expire) {
+        with_detector(|detector| detector.acquire(tid, res));
+        Ok(0)
+    } else {
+        with_detector(|detector| detector.cancel(tid, res));
+        Err(Errno::ETIMEDOUT)
+    }
 }
 
-pub fn sys_condvar_create() -> isize {
+/// Fail with `EBUSY` if the semaphore is waited for.
+pub fn sys_semaphore_destroy(sem_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = find(&process_inner.semaphore_list, sem_id)?;
+    if sem.has_waiters() {
+        return Err(Errno::EBUSY);
+    }
+    process_inner.semaphore_list[sem_id] = None;
+    process_inner
+        .deadlock
+        .remove_resource(Resource::Semaphore(sem_id));
+    drop(process_inner);
+    sem.close();
+    Ok(0)
+}
+
+pub fn sys_condvar_create() -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -111,24 +319,208 @@ pub fn sys_condvar_create() -> isize {
             .push(Some(Arc::new(Condvar::new())));
         process_inner.condvar_list.len() - 1
     };
-    id as isize
+    Ok(id as isize)
 }
 
-pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let condvar = find(&process_inner.condvar_list, condvar_id)?;
     drop(process_inner);
     condvar.signal();
-    0
+    Ok(0)
+}
+
+/// Fail with `EINVAL` if the condvar is destroyed, the mutex is held again in any case.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let condvar = find(&process_inner.condvar_list, condvar_id)?;
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
+    drop(process_inner);
+    drop(process);
+    if condvar.is_closed() {
+        return Err(Errno::EINVAL);
+    }
+    let tid = current_tid();
+    let res = Resource::Mutex(mutex_id);
+    with_detector(|detector| detector.release(tid, res));
+    // the mutex is held again even if the condvar is closed
+    let open = condvar.wait_with_mutex(mutex);
+    with_detector(|detector| detector.acquire(tid, res));
+    if open {
+        Ok(0)
+    } else {
+        Err(Errno::EINVAL)
+    }
 }
 
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+/// Fail with `ETIMEDOUT` if not signaled within `timeout_us` microseconds, the mutex is held again anyway.
+pub fn sys_condvar_wait_timeout(
+    condvar_id: usize,
+    mutex_id: usize,
+    timeout_us: usize,
+) -> SysResult {
+    let expire = deadline(timeout_us);
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let condvar = find(&process_inner.condvar_list, condvar_id)?;
+    let mutex = find(&process_inner.mutex_list, mutex_id)?;
+    drop(process_inner);
+    drop(process);
+    if condvar.is_closed() {
+        return Err(Errno::EINVAL);
+    }
+    let tid = current_tid();
+    let res = Resource::Mutex(mutex_id);
+    with_detector(|detector| detector.release(tid, res));
+    let signaled = condvar.wait_with_mutex_timeout(mutex, expire);
+    with_detector(|detector| detector.acquire(tid, res));
+    match signaled {
+        Some(true) => Ok(0),
+        Some(false) => Err(Errno::ETIMEDOUT),
+        None => Err(Errno::EINVAL),
+    }
+}
+
+/// Fail with `EBUSY` if the condvar is waited for.
+pub fn sys_condvar_destroy(condvar_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let condvar = find(&process_inner.condvar_list, condvar_id)?;
+    if condvar.has_waiters() {
+        return Err(Errno::EBUSY);
+    }
+    process_inner.condvar_list[condvar_id] = None;
     drop(process_inner);
-    condvar.wait_with_mutex(mutex);
-    0
+    condvar.close();
+    Ok(0)
+}
+
+pub fn sys_rwlock_create() -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = if let Some(id) = process_inner
+        .rwlock_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.rwlock_list[id] = Some(Arc::new(RwLock::new()));
+        id
+    } else {
+        process_inner
+            .rwlock_list
+            .push(Some(Arc::new(RwLock::new())));
+        process_inner.rwlock_list.len() - 1
+    };
+    Ok(id as isize)
+}
+
+pub fn sys_rwlock_read(rwlock_id: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = find(&process_inner.rwlock_list, rwlock_id)?;
+    drop(process_inner);
+    drop(process);
+    if rwlock.read() {
+        Ok(0)
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+pub fn sys_rwlock_write(rwlock_id: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = find(&process_inner.rwlock_list, rwlock_id)?;
+    drop(process_inner);
+    drop(process);
+    if rwlock.write() {
+        Ok(0)
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+/// Fail with `EPERM` if the lock is held neither for reading nor for writing.
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = find(&process_inner.rwlock_list, rwlock_id)?;
+    drop(process_inner);
+    drop(process);
+    if rwlock.unlock() {
+        Ok(0)
+    } else {
+        Err(Errno::EPERM)
+    }
+}
+
+/// Fail with `EBUSY` if the lock is held or waited for.
+pub fn sys_rwlock_destroy(rwlock_id: usize) -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let rwlock = find(&process_inner.rwlock_list, rwlock_id)?;
+    if rwlock.busy() {
+        return Err(Errno::EBUSY);
+    }
+    process_inner.rwlock_list[rwlock_id] = None;
+    drop(process_inner);
+    rwlock.close();
+    Ok(0)
+}
+
+/// Refuse locks and downs leading to unsafe states if `enabled` is 1.
+pub fn sys_enable_deadlock_detect(enabled: usize) -> SysResult {
+    match enabled {
+        0 | 1 => {
+            with_detector(|detector| detector.set_enabled(enabled == 1));
+            Ok(0)
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// Print the wait-for graph of current process to the console.
+pub fn sys_dump_wait_for_graph() -> SysResult {
+    let process = current_process();
+    let pid = process.getpid();
+    process.inner_exclusive_access().deadlock.dump(pid);
+    Ok(0)
+}
+
+/// Physical address of the futex word at `uaddr`, which must be aligned and mapped.
+fn futex_key(uaddr: usize) -> Result<PhysAddr, Errno> {
+    if uaddr % core::mem::size_of::<u32>() != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let word = translated_ref(current_user_token(), uaddr as *const u32)?;
+    // physical memory is identity mapped in the kernel
+    Ok(PhysAddr::from(word as *const u32 as usize))
+}
+
+/// `FUTEX_WAIT` takes an optional relative timeout in `val2`, it fails with
+/// `EAGAIN` if the word is not `val` and with `ETIMEDOUT` on timeout.
+/// `FUTEX_REQUEUE` moves at most `val2` waiters to `uaddr2` after waking
+/// `val` of them.
+pub fn sys_futex(uaddr: usize, op: usize, val: usize, val2: usize, uaddr2: usize) -> SysResult {
+    let key = futex_key(uaddr)?;
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let expire = if val2 == 0 {
+                None
+            } else {
+                let timeout = copy_from_user(current_user_token(), val2 as *const TimeSpec)?;
+                let cycles = timeout.to_cycles().ok_or(Errno::EINVAL)?;
+                Some(get_time().saturating_add(cycles))
+            };
+            futex_wait(key, val as u32, expire)?;
+            Ok(0)
+        }
+        FUTEX_WAKE => Ok(futex_wake(key, val) as isize),
+        FUTEX_REQUEUE => Ok(futex_requeue(key, val, futex_key(uaddr2)?, val2) as isize),
+        _ => Err(Errno::ENOSYS),
+    }
 }
@@ -1,85 +1,193 @@
 use crate::{
-    mm::kernel_token,
-    task::{add_task, current_task, TaskControlBlock},
+    config::{PAGE_SIZE, USER_STACK_SIZE},
+    errno::{Errno, SysResult},
+    mm::{kernel_token, translated_refmut},
+    task::{
+        add_task, block_current_task, current_process, current_task, current_user_token, schedule,
+        wakeup_task, SchedPolicy, TaskControlBlock, NICE_MAX, NICE_MIN, RT_PRIORITY_MAX,
+    },
     trap::{trap_handler, TrapContext},
 };
 use alloc::sync::Arc;
 
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
+    // the user stack and trap context of the new thread
+    if !process
+        .inner_exclusive_access()
+        .may_grow(USER_STACK_SIZE + PAGE_SIZE)
+    {
+        return Err(Errno::EAGAIN);
+    }
     // create a new thread
-    let new_task = Arc::new(TaskControlBlock::new(
-        Arc::clone(&process),
-        task.inner_exclusive_access()
-            .res
-            .as_ref()
-            .unwrap()
-            .ustack_base,
-        true,
-    ));
-    // add new task to scheduler
-    add_task(Arc::clone(&new_task));
-    let new_task_inner = new_task.inner_exclusive_access();
+    let ustack_base = task
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .ustack_base;
+    let new_task = Arc::new(
+        TaskControlBlock::new(Arc::clone(&process), ustack_base, true).ok_or(Errno::EAGAIN)?,
+    );
+    let sched = task.inner_exclusive_access().sched.inherit();
+    let mut new_task_inner = new_task.inner_exclusive_access();
+    new_task_inner.sched = sched;
     let new_task_res = new_task_inner.res.as_ref().unwrap();
     let new_task_tid = new_task_res.tid;
-    let mut process_inner = process.inner_exclusive_access();
-    // add new thread to current process
-    let tasks = &mut process_inner.tasks;
-    while tasks.len() < new_task_tid + 1 {
-        tasks.push(None);
-    }
-    tasks[new_task_tid] = Some(Arc::clone(&new_task));
     let new_task_trap_cx = new_task_inner.get_trap_cx();
+    // the thread-local block is zeroed as the stack is freshly mapped
+    let tls_base = new_task_res.tls_base();
     *new_task_trap_cx = TrapContext::app_init_context(
         entry,
-        new_task_res.ustack_top(),
+        tls_base,
         kernel_token(),
         new_task.kstack.get_top(),
         trap_handler as usize,
     );
+    (*new_task_trap_cx).x[4] = tls_base;
     (*new_task_trap_cx).x[10] = arg;
-    new_task_tid as isize
+    drop(new_task_inner);
+    // add new thread to current process
+    let mut process_inner = process.inner_exclusive_access();
+    let tasks = &mut process_inner.tasks;
+    while tasks.len() < new_task_tid + 1 {
+        tasks.push(None);
+    }
+    tasks[new_task_tid] = Some(Arc::clone(&new_task));
+    drop(process_inner);
+    // add new task to scheduler once it is ready to run on another hart
+    add_task(new_task);
+    Ok(new_task_tid as isize)
 }
 
-pub fn sys_gettid() -> isize {
-    current_task()
+pub fn sys_gettid() -> SysResult {
+    Ok(current_task()
         .unwrap()
         .inner_exclusive_access()
         .res
         .as_ref()
         .unwrap()
-        .tid as isize
+        .tid as isize)
 }
 
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
-/// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
+/// Wait for thread `tid` to exit, reap it and return its tid, `tid == -1`
+/// waits for any joinable thread. The exit code is stored in `exit_code_ptr`
+/// if it is not null, as a negative one would look like an errno.
+/// Fail with `ESRCH` if there is no such thread, or it is current thread or detached.
+pub fn sys_waittid(tid: isize, exit_code_ptr: *mut i32) -> SysResult {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
-    let task_inner = task.inner_exclusive_access();
+    let current_tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+    // checked before a thread is reaped, so that it is not lost
+    let exit_code_ref = if exit_code_ptr.is_null() {
+        None
+    } else {
+        Some(translated_refmut(current_user_token(), exit_code_ptr)?)
+    };
+    loop {
+        let mut process_inner = process.inner_exclusive_access();
+        // the process has exited on another hart
+        if process_inner.is_zombie {
+            return Err(Errno::EINTR);
+        }
+        let mut joinable = false;
+        let mut exited = None;
+        for (id, waited) in process_inner.tasks.iter().enumerate() {
+            // a thread cannot wait for itself
+            if id == current_tid || (tid != -1 && id != tid as usize) {
+                continue;
+            }
+            let waited_inner = match waited {
+                Some(waited) => waited.inner_exclusive_access(),
+                None => continue,
+            };
+            if waited_inner.detached {
+                continue;
+            }
+            joinable = true;
+            if waited_inner.exit_code.is_some() {
+                exited = Some(id);
+                break;
+            }
+        }
+        if let Some(id) = exited {
+            let exit_code = process_inner.reap_thread(id).unwrap();
+            if let Some(exit_code_ref) = exit_code_ref {
+                *exit_code_ref = exit_code;
+            }
+            return Ok(id as isize);
+        }
+        if !joinable {
+            return Err(Errno::ESRCH);
+        }
+        // woken up whenever a thread exits or is detached
+        process_inner.join_waiters.push(Arc::clone(&task));
+        let task_cx_ptr = block_current_task();
+        drop(process_inner);
+        schedule(task_cx_ptr);
+    }
+}
+
+/// Let thread `tid` be reaped as soon as it exits, fail with `ESRCH` if it
+/// does not exist or `EINVAL` if it is detached already.
+pub fn sys_thread_detach(tid: usize) -> SysResult {
+    let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
-    // a thread cannot wait for itself
-    if task_inner.res.as_ref().unwrap().tid == tid {
-        return -1;
+    let task = process_inner
+        .tasks
+        .get(tid)
+        .and_then(Option::clone)
+        .ok_or(Errno::ESRCH)?;
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.detached {
+        return Err(Errno::EINVAL);
     }
-    let mut exit_code: Option<i32> = None;
-    let waited_task = process_inner.tasks[tid].as_ref();
-    if let Some(waited_task) = waited_task {
-        if let Some(waited_exit_code) = waited_task.inner_exclusive_access().exit_code {
-            exit_code = Some(waited_exit_code);
-        }
-    } else {
-        // waited thread does not exist
-        return -1;
+    task_inner.detached = true;
+    let exited = task_inner.exit_code.is_some();
+    drop(task_inner);
+    if exited {
+        process_inner.reap_thread(tid);
+    }
+    // threads waiting for it give up
+    for waiter in process_inner.join_waiters.drain(..) {
+        wakeup_task(waiter);
     }
-    if let Some(exit_code) = exit_code {
-        // dealloc the exited thread
-        process_inner.tasks[tid] = None;
-        exit_code
+    Ok(0)
+}
+
+fn find_thread(tid: usize) -> Option<Arc<TaskControlBlock>> {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    process_inner.tasks.get(tid).and_then(Option::clone)
+}
+
+/// Set the nice value of a thread in current process.
+pub fn sys_setpriority(tid: usize, nice: isize) -> SysResult {
+    if !(NICE_MIN..=NICE_MAX).contains(&nice) {
+        return Err(Errno::EINVAL);
+    }
+    let task = find_thread(tid).ok_or(Errno::ESRCH)?;
+    task.inner_exclusive_access().sched.nice = nice;
+    Ok(0)
+}
+
+/// Set the policy of a thread in current process, `priority` must be
+/// 1..=99 for realtime policies and 0 otherwise. A realtime thread that
+/// runs past `RLIMIT_RTTIME` without blocking is killed.
+pub fn sys_sched_setscheduler(tid: usize, policy: usize, priority: usize) -> SysResult {
+    let policy = SchedPolicy::from_raw(policy).ok_or(Errno::EINVAL)?;
+    let valid = if policy.is_realtime() {
+        (1..=RT_PRIORITY_MAX).contains(&priority)
     } else {
-        // waited thread has not exited
-        -2
+        priority == 0
+    };
+    if !valid {
+        return Err(Errno::EINVAL);
     }
+    let task = find_thread(tid).ok_or(Errno::ESRCH)?;
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.sched.policy = policy;
+    task_inner.sched.rt_priority = priority;
+    Ok(0)
 }
@@ -1,3 +1,4 @@
+use super::kthread::kthread_entry;
 use crate::trap::trap_return;
 
 #[repr(C)]
@@ -22,4 +23,11 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    pub fn goto_kthread_entry(kstack_ptr: usize) -> Self {
+        Self {
+            ra: kthread_entry as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
 }
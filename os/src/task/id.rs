@@ -1,11 +1,16 @@
+use super::process::ProcessControlBlockInner;
 use super::ProcessControlBlock;
-use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
+use crate::config::{
+    KERNEL_STACK_GUARD_SIZE, KERNEL_STACK_SIZE, PAGE_SIZE, STACK_GUARD_SIZE, TRAMPOLINE,
+    TRAP_CONTEXT_BASE, USER_STACK_MAX_SIZE, USER_STACK_SIZE, USER_TLS_SIZE,
+};
 use crate::mm::{MapPermission, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinNoIrqLock;
 use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
 };
+use core::mem::ManuallyDrop;
 use lazy_static::*;
 
 pub struct RecycleAllocator {
@@ -28,6 +33,10 @@ impl RecycleAllocator {
             self.current - 1
         }
     }
+    /// Number of ids in use.
+    pub fn allocated(&self) -> usize {
+        self.current - self.recycled.len()
+    }
     pub fn dealloc(&mut self, id: usize) {
         assert!(id < self.current);
         assert!(
@@ -40,13 +49,18 @@ impl RecycleAllocator {
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
-        unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
-    static ref KSTACK_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
-        unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
+    /// pid 0 is never handed out, it stands for the caller in `kill` and
+    /// `setpgid`, and no process group can be 0 then.
+    static ref PID_ALLOCATOR: SpinNoIrqLock<RecycleAllocator> = {
+        let mut allocator = RecycleAllocator::new();
+        allocator.alloc();
+        SpinNoIrqLock::new(allocator)
+    };
+    static ref KSTACK_ALLOCATOR: SpinNoIrqLock<RecycleAllocator> =
+        SpinNoIrqLock::new(RecycleAllocator::new());
 }
 
-pub const IDLE_PID: usize = 0;
+pub const IDLE_PID: usize = 1;
 
 pub struct PidHandle(pub usize);
 
@@ -61,12 +75,29 @@ impl Drop for PidHandle {
 }
 
 /// Return (bottom, top) of a kernel stack in kernel space.
+/// An unmapped guard lies below every kernel stack.
 pub fn kernel_stack_position(kstack_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - kstack_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+    let top = TRAMPOLINE - kstack_id * (KERNEL_STACK_SIZE + KERNEL_STACK_GUARD_SIZE);
     let bottom = top - KERNEL_STACK_SIZE;
     (bottom, top)
 }
 
+/// Return the id of the kernel stack whose guard contains `addr`.
+/// Called from the trap handler, so it must not take `KSTACK_ALLOCATOR`.
+pub fn kstack_guard_hit(addr: usize) -> Option<usize> {
+    // kernel stacks are the only mappings in the upper half below the trampoline
+    if (addr as isize) >= 0 || addr >= TRAMPOLINE {
+        return None;
+    }
+    let kstack_id = (TRAMPOLINE - 1 - addr) / (KERNEL_STACK_SIZE + KERNEL_STACK_GUARD_SIZE);
+    let (bottom, _) = kernel_stack_position(kstack_id);
+    if addr < bottom {
+        Some(kstack_id)
+    } else {
+        None
+    }
+}
+
 pub struct KernelStack(pub usize);
 
 pub fn kstack_alloc() -> KernelStack {
@@ -110,6 +141,16 @@ impl KernelStack {
     }
 }
 
+/// How a page fault relates to the user stack of a thread.
+pub enum StackFault {
+    /// the stack has been grown to cover the address
+    Grown,
+    /// the address lies in the guard page below the stack
+    Overflow,
+    /// not a stack access
+    Other,
+}
+
 pub struct TaskUserRes {
     pub tid: usize,
     pub ustack_base: usize,
@@ -120,8 +161,19 @@ fn trap_cx_bottom_from_tid(tid: usize) -> usize {
     TRAP_CONTEXT_BASE - tid * PAGE_SIZE
 }
 
+/// Every thread owns a guard page followed by up to `USER_STACK_MAX_SIZE` of stack,
+/// of which only the top `USER_STACK_SIZE` is mapped at first.
 fn ustack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
-    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+    ustack_base + tid * (STACK_GUARD_SIZE + USER_STACK_MAX_SIZE) + STACK_GUARD_SIZE
+}
+
+/// The range the user stacks of up to `max_threads` threads may take,
+/// whether they have been mapped or grown yet or not.
+pub fn ustacks_range(ustack_base: usize, max_threads: usize) -> (usize, usize) {
+    (
+        ustack_base,
+        ustack_bottom_from_tid(ustack_base, max_threads) - STACK_GUARD_SIZE,
+    )
 }
 
 impl TaskUserRes {
@@ -129,8 +181,8 @@ impl TaskUserRes {
         process: Arc<ProcessControlBlock>,
         ustack_base: usize,
         alloc_user_res: bool,
-    ) -> Self {
-        let tid = process.inner_exclusive_access().alloc_tid();
+    ) -> Option<Self> {
+        let tid = process.inner_exclusive_access().alloc_tid()?;
         let task_user_res = Self {
             tid,
             ustack_base,
@@ -139,17 +191,16 @@ impl TaskUserRes {
         if alloc_user_res {
             task_user_res.alloc_user_res();
         }
-        task_user_res
+        Some(task_user_res)
     }
 
     pub fn alloc_user_res(&self) {
         let process = self.process.upgrade().unwrap();
         let mut process_inner = process.inner_exclusive_access();
         // alloc user stack
-        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
-        let ustack_top = ustack_bottom + USER_STACK_SIZE;
+        let ustack_top = self.ustack_top();
         process_inner.memory_set.insert_framed_area(
-            ustack_bottom.into(),
+            (ustack_top - USER_STACK_SIZE).into(),
             ustack_top.into(),
             MapPermission::R | MapPermission::W | MapPermission::U,
         );
@@ -167,11 +218,11 @@ impl TaskUserRes {
         // dealloc tid
         let process = self.process.upgrade().unwrap();
         let mut process_inner = process.inner_exclusive_access();
-        // dealloc ustack manually
-        let ustack_bottom_va: VirtAddr = ustack_bottom_from_tid(self.ustack_base, self.tid).into();
+        // dealloc ustack manually, its bottom may have been moved by growing
+        let ustack_top_va: VirtAddr = self.ustack_top().into();
         process_inner
             .memory_set
-            .remove_area_with_start_vpn(ustack_bottom_va.into());
+            .remove_area_with_end_vpn(ustack_top_va.into());
         // dealloc trap_cx manually
         let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_tid(self.tid).into();
         process_inner
@@ -179,14 +230,12 @@ impl TaskUserRes {
             .remove_area_with_start_vpn(trap_cx_bottom_va.into());
     }
 
-    #[allow(unused)]
-    pub fn alloc_tid(&mut self) {
-        self.tid = self
-            .process
-            .upgrade()
-            .unwrap()
-            .inner_exclusive_access()
-            .alloc_tid();
+    /// Free the user stack and trap context of an exiting thread, its tid is
+    /// kept until the thread is reaped so that `waittid` cannot mistake a new thread for it.
+    pub fn release_user_res(self) {
+        self.dealloc_user_res();
+        let mut res = ManuallyDrop::new(self);
+        drop(core::mem::take(&mut res.process));
     }
 
     pub fn dealloc_tid(&self) {
@@ -214,7 +263,48 @@ impl TaskUserRes {
         self.ustack_base
     }
     pub fn ustack_top(&self) -> usize {
-        ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
+        ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_MAX_SIZE
+    }
+
+    /// The zeroed thread-local block for `tp`, the stack grows down from here.
+    pub fn tls_base(&self) -> usize {
+        self.ustack_top() - USER_TLS_SIZE
+    }
+
+    /// Grow the user stack down to `fault_va` if it lies within the maximum stack size.
+    pub fn handle_stack_fault(
+        &self,
+        fault_va: usize,
+        process_inner: &mut ProcessControlBlockInner,
+    ) -> StackFault {
+        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
+        let ustack_top = self.ustack_top();
+        if fault_va >= ustack_bottom - STACK_GUARD_SIZE && fault_va < ustack_bottom {
+            return StackFault::Overflow;
+        }
+        if fault_va < ustack_bottom || fault_va >= ustack_top {
+            return StackFault::Other;
+        }
+        let ustack_top_va: VirtAddr = ustack_top.into();
+        let new_start_vpn = VirtAddr::from(fault_va).floor();
+        // the grown pages count against the address space limit
+        if let Some(old_start_vpn) = process_inner
+            .memory_set
+            .area_start_with_end_vpn(ustack_top_va.into())
+        {
+            let grow_size = old_start_vpn.0.saturating_sub(new_start_vpn.0) * PAGE_SIZE;
+            if !process_inner.may_grow(grow_size) {
+                return StackFault::Other;
+            }
+        }
+        if process_inner
+            .memory_set
+            .extend_area_down(ustack_top_va.into(), new_start_vpn)
+        {
+            StackFault::Grown
+        } else {
+            StackFault::Other
+        }
     }
 }
 
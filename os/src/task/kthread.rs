@@ -0,0 +1,40 @@
+//! Threads running only in kernel mode.
+//!
+//! A kernel thread belongs to no process and has no user resources, it is
+//! scheduled like any other thread but never preempted, so it should block
+//! or yield when it has nothing to do.
+
+use super::{add_task, current_task, exit_current_and_run_next, TaskControlBlock};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use riscv::register::sstatus;
+
+/// Body of a kernel thread.
+pub type KernelThreadFn = Box<dyn FnOnce() + Send>;
+
+/// Create a kernel thread running `f` and make it ready.
+pub fn kthread_spawn<F>(f: F) -> Arc<TaskControlBlock>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let task = Arc::new(TaskControlBlock::new_kernel(Box::new(f)));
+    add_task(Arc::clone(&task));
+    task
+}
+
+/// First code run by every kernel thread.
+pub fn kthread_entry() -> ! {
+    let f = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .kthread_fn
+        .take()
+        .unwrap();
+    // interruptible like syscalls are
+    unsafe {
+        sstatus::set_sie();
+    }
+    f();
+    exit_current_and_run_next(0);
+    panic!("Unreachable in kthread_entry!");
+}
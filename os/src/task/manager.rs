@@ -1,37 +1,59 @@
+use super::scheduler::{
+    default_scheduler, RealtimeScheduler, RoundRobinScheduler, SchedPolicy, Scheduler,
+};
 use super::{ProcessControlBlock, TaskControlBlock, TaskStatus};
-use crate::sync::UPIntrFreeCell;
-use alloc::collections::{BTreeMap, VecDeque};
+use crate::smp::kick_idle_hart;
+use crate::sync::SpinNoIrqLock;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 
+/// One scheduler for each class of threads, higher classes run first.
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    realtime: RealtimeScheduler,
+    normal: Box<dyn Scheduler>,
+    idle: RoundRobinScheduler,
 }
 
-/// A simple FIFO scheduler.
 impl TaskManager {
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            realtime: RealtimeScheduler::new(),
+            normal: default_scheduler(),
+            idle: RoundRobinScheduler::new(),
         }
     }
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        let policy = task.inner_exclusive_access().sched.effective_policy();
+        match policy {
+            SchedPolicy::Fifo | SchedPolicy::RoundRobin => self.realtime.add(task),
+            SchedPolicy::Other => self.normal.add(task),
+            SchedPolicy::Idle => self.idle.add(task),
+        }
     }
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.realtime
+            .fetch()
+            .or_else(|| self.normal.fetch())
+            .or_else(|| self.idle.fetch())
+    }
+    pub fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.realtime.remove(task) || self.normal.remove(task) || self.idle.remove(task)
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPIntrFreeCell<TaskManager> =
-        unsafe { UPIntrFreeCell::new(TaskManager::new()) };
-    pub static ref PID2PCB: UPIntrFreeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
-        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+    pub static ref TASK_MANAGER: SpinNoIrqLock<TaskManager> =
+        SpinNoIrqLock::new(TaskManager::new());
+    pub static ref PID2PCB: SpinNoIrqLock<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
     TASK_MANAGER.exclusive_access().add(task);
+    kick_idle_hart();
 }
 
 pub fn wakeup_task(task: Arc<TaskControlBlock>) {
@@ -41,6 +63,14 @@ pub fn wakeup_task(task: Arc<TaskControlBlock>) {
     add_task(task);
 }
 
+/// Queue a ready task again after its priority has changed.
+pub fn requeue_task(task: &Arc<TaskControlBlock>) {
+    let mut manager = TASK_MANAGER.exclusive_access();
+    if manager.remove(task) {
+        manager.add(Arc::clone(task));
+    }
+}
+
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
 }
@@ -50,6 +80,19 @@ pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
     map.get(&pid).map(Arc::clone)
 }
 
+/// Return all processes in the process group `pgid`.
+pub fn pgid2processes(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    let map = PID2PCB.exclusive_access();
+    map.values()
+        .filter(|process| process.inner_exclusive_access().pgid == pgid)
+        .cloned()
+        .collect()
+}
+
+pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB.exclusive_access().values().cloned().collect()
+}
+
 pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
     PID2PCB.exclusive_access().insert(pid, process);
 }
@@ -1,31 +1,55 @@
 mod context;
 mod id;
+mod kthread;
 mod manager;
 mod process;
 mod processor;
+mod resource;
+mod scheduler;
 mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
+mod workqueue;
 
 use self::id::TaskUserRes;
 use crate::fs::{open_file, OpenFlags};
 use crate::sbi::shutdown;
+use crate::smp::tlb_shootdown;
+use crate::sync::futex_cancel;
+use crate::timer::cancel_task_timers;
 use alloc::{sync::Arc, vec::Vec};
+use core::hint::spin_loop;
+use core::mem;
+use core::sync::atomic::Ordering;
 use lazy_static::*;
 use manager::fetch_task;
-use process::ProcessControlBlock;
+pub use process::ProcessControlBlock;
 use switch::__switch;
 
 pub use context::TaskContext;
-pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle, IDLE_PID};
-pub use manager::{add_task, pid2process, remove_from_pid2process, wakeup_task};
+pub use id::{
+    kstack_alloc, kstack_guard_hit, pid_alloc, KernelStack, PidHandle, StackFault, IDLE_PID,
+};
+pub use kthread::kthread_spawn;
+pub use manager::{
+    add_task, all_processes, pgid2processes, pid2process, remove_from_pid2process, requeue_task,
+    wakeup_task,
+};
 pub use processor::{
     current_kstack_top, current_process, current_task, current_trap_cx, current_trap_cx_user_va,
     current_user_token, run_tasks, schedule, take_current_task,
 };
-pub use signal::SignalFlags;
+use resource::RLIM_INFINITY;
+pub use resource::{
+    cycles_to_ticks, RLimit, RUsage, Tms, RLIMIT_CPU, RLIMIT_RTTIME, RUSAGE_CHILDREN, RUSAGE_SELF,
+    RUSAGE_THREAD,
+};
+pub use scheduler::{SchedPolicy, NICE_MAX, NICE_MIN, RT_PRIORITY_MAX};
+use signal::DefaultAction;
+pub use signal::{SignalAction, SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
 pub use task::{TaskControlBlock, TaskStatus};
+pub use workqueue::{init_workqueue, schedule_work, WorkQueue};
 
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
@@ -36,6 +60,8 @@ pub fn suspend_current_and_run_next() {
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.sched.stop();
+    task_inner.times.charge_kernel();
     drop(task_inner);
     // ---- release current TCB
 
@@ -50,6 +76,9 @@ pub fn block_current_task() -> *mut TaskContext {
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     task_inner.task_status = TaskStatus::Blocked;
+    task_inner.sched.stop();
+    task_inner.times.charge_kernel();
+    task_inner.times.mark_blocked();
     &mut task_inner.task_cx as *mut TaskContext
 }
 
@@ -58,22 +87,73 @@ pub fn block_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
-/// Exit the current 'Running' task and run the next task in task list.
+/// Exit the current 'Running' thread and run the next task in task list,
+/// the process exits with its last thread.
 pub fn exit_current_and_run_next(exit_code: i32) {
+    exit_current(exit_code, false);
+}
+
+/// Exit the process of the current thread at once and run the next task.
+pub fn exit_group_and_run_next(exit_code: i32) {
+    exit_current(exit_code, true);
+}
+
+fn exit_current(exit_code: i32, group: bool) {
     let task = take_current_task().unwrap();
+    // stale timers must not wake the thread up
+    cancel_task_timers(&task);
+    let process = match task.process.upgrade() {
+        Some(process) => process,
+        None => {
+            // a kernel thread, or the process has exited and been reaped
+            // while this thread was ready, `run_tasks` keeps the kernel stack
+            drop(task);
+            let mut _unused = TaskContext::zero_init();
+            schedule(&mut _unused as *mut _);
+            return;
+        }
+    };
     let mut task_inner = task.inner_exclusive_access();
-    let process = task.process.upgrade().unwrap();
-    let tid = task_inner.res.as_ref().unwrap().tid;
+    // user resources are gone if the process has exited on another hart
+    let res = task_inner.res.take();
+    let tid = res.as_ref().map(|res| res.tid);
     // record exit code
     task_inner.exit_code = Some(exit_code);
-    task_inner.res = None;
-    // here we do not remove the thread since we are still using the kstack
-    // it will be deallocated when sys_waittid is called
+    task_inner.times.charge_kernel();
     drop(task_inner);
-    drop(task);
-    // however, if this is the main thread of current process
-    // the process should terminate at once
-    if tid == 0 {
+    // freeing user resources locks the process, which is locked before its threads
+    if let Some(res) = res {
+        res.release_user_res();
+    }
+    let mut process_inner = process.inner_exclusive_access();
+    let mut exit_process = false;
+    if let Some(tid) = tid {
+        process_inner.deadlock.remove_thread(tid);
+        // the process exits with its last thread, or with any thread on `exit_group`
+        exit_process = !process_inner.is_zombie
+            && (group
+                || process_inner
+                    .tasks
+                    .iter()
+                    .flatten()
+                    .all(|task| task.inner_exclusive_access().exit_code.is_some()));
+        if exit_process {
+            // mark this process as a zombie process
+            process_inner.is_zombie = true;
+            process_inner.exit_code = exit_code;
+        } else {
+            // the kernel stack is deallocated when the thread is reaped,
+            // `run_tasks` keeps it until we have switched away
+            if task.inner_exclusive_access().detached {
+                process_inner.reap_thread(tid);
+            }
+            for waiter in process_inner.join_waiters.drain(..) {
+                wakeup_task(waiter);
+            }
+        }
+    }
+    drop(process_inner);
+    if exit_process {
         let pid = process.getpid();
         if pid == IDLE_PID {
             println!(
@@ -90,10 +170,7 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         }
         remove_from_pid2process(pid);
         let mut process_inner = process.inner_exclusive_access();
-        // mark this process as a zombie process
-        process_inner.is_zombie = true;
-        // record exit code of main process
-        process_inner.exit_code = exit_code;
+        process_inner.notify_parent();
 
         {
             // move all child processes under init process
@@ -104,6 +181,29 @@ pub fn exit_current_and_run_next(exit_code: i32) {
             }
         }
 
+        let token = process_inner.memory_set.token();
+        drop(process_inner);
+        // other threads leave user mode and find the process exited,
+        // wait for them to get off their harts before freeing their resources
+        tlb_shootdown(token);
+        let tasks: Vec<_> = process
+            .inner_exclusive_access()
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|other| !Arc::ptr_eq(other, &task))
+            .cloned()
+            .collect();
+        for task in tasks.iter() {
+            while task.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+            cancel_task_timers(task);
+            futex_cancel(task);
+        }
+        drop(tasks);
+        let process_inner = process.inner_exclusive_access();
+
         // deallocate user res (including tid/trap_cx/ustack) of all threads
         // it has to be done before we dealloc the whole memory_set
         // otherwise they will be deallocated twice
@@ -127,7 +227,29 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         process_inner.memory_set.recycle_data_pages();
         // drop file descriptors
         process_inner.fd_table.clear();
+        // threads in `waittid` find the process exited
+        for waiter in process_inner.join_waiters.drain(..) {
+            wakeup_task(waiter);
+        }
+        // stopped threads resume to exit
+        for task in process_inner.stopped_threads.drain(..) {
+            wakeup_task(task);
+        }
+        // threads blocked on sync objects fail their waits and exit
+        let mutexes = mem::take(&mut process_inner.mutex_list);
+        let semaphores = mem::take(&mut process_inner.semaphore_list);
+        let condvars = mem::take(&mut process_inner.condvar_list);
+        let rwlocks = mem::take(&mut process_inner.rwlock_list);
+        drop(process_inner);
+        mutexes.iter().flatten().for_each(|mutex| mutex.close());
+        semaphores.iter().flatten().for_each(|sem| sem.close());
+        condvars
+            .iter()
+            .flatten()
+            .for_each(|condvar| condvar.close());
+        rwlocks.iter().flatten().for_each(|rwlock| rwlock.close());
     }
+    drop(task);
     drop(process);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
@@ -146,14 +268,178 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
-pub fn check_signals_of_current() -> Option<(i32, &'static str)> {
-    let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    process_inner.signals.check_error()
+/// Deliver pending signals of current process before returning to user mode.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let process = task.process.upgrade().unwrap();
+        let mut process_inner = process.inner_exclusive_access();
+        let mut task_inner = task.inner_exclusive_access();
+        let mut blocked = process_inner.signal_mask;
+        if let Some(signum) = task_inner.handling_sig {
+            blocked |= process_inner.signal_actions.table[signum].mask
+                | SignalFlags::from_signum(signum).unwrap();
+        }
+        let mut pending = process_inner.signals - (blocked - SignalFlags::UNCATCHABLE);
+        // a stopped process acts on nothing but SIGKILL and SIGCONT, the rest stay pending
+        if process_inner.stopped {
+            pending &= SignalFlags::SIGKILL | SignalFlags::SIGCONT;
+        }
+        // user handlers cannot be nested, such signals are delivered after sigreturn
+        let signal = (1..=MAX_SIG)
+            .map(|signum| SignalFlags::from_signum(signum).unwrap())
+            .find(|signal| {
+                pending.contains(*signal)
+                    && (task_inner.handling_sig.is_none()
+                        || process_inner.signal_actions.table[signal.signum()].handler <= SIG_IGN)
+            });
+        let signal = match signal {
+            Some(signal) => signal,
+            None if process_inner.stopped => {
+                // blocked until SIGCONT or SIGKILL wakes it up
+                drop(task_inner);
+                process_inner.stopped_threads.push(Arc::clone(&task));
+                let task_cx_ptr = block_current_task();
+                drop(process_inner);
+                drop(process);
+                drop(task);
+                schedule(task_cx_ptr);
+                // left for `trap_return` to exit
+                if current_process_exited() {
+                    return;
+                }
+                continue;
+            }
+            None => return,
+        };
+        process_inner.signals -= signal;
+        let signum = signal.signum();
+        match process_inner.signal_actions.table[signum].handler {
+            SIG_IGN => {}
+            SIG_DFL => match signal.default_action() {
+                DefaultAction::Terminate => {
+                    drop(task_inner);
+                    drop(process_inner);
+                    drop(process);
+                    drop(task);
+                    println!("[kernel] {}", signal.describe());
+                    exit_group_and_run_next(-(signum as i32));
+                    return;
+                }
+                DefaultAction::Stop => {
+                    process_inner.stopped = true;
+                    process_inner.unreported_stop = Some(signum);
+                    process_inner.notify_parent();
+                }
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+            },
+            handler => {
+                // save the trap context and return to the handler instead
+                let trap_cx = task_inner.get_trap_cx();
+                task_inner.trap_cx_backup = Some(trap_cx.clone());
+                trap_cx.sepc = handler;
+                trap_cx.x[10] = signum;
+                task_inner.handling_sig = Some(signum);
+                return;
+            }
+        }
+    }
+}
+
+/// Try to resolve a user page fault at `fault_va` by growing the stack of the current thread.
+pub fn current_stack_fault(fault_va: usize) -> StackFault {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    // the process is locked before its threads, as process exit does
+    let mut process_inner = process.inner_exclusive_access();
+    let task_inner = task.inner_exclusive_access();
+    // the process may have exited on another hart
+    task_inner.res.as_ref().map_or(StackFault::Other, |res| {
+        res.handle_stack_fault(fault_va, &mut process_inner)
+    })
+}
+
+/// Whether the process of current thread has exited on another hart,
+/// the thread must not touch its user resources then.
+pub fn current_process_exited() -> bool {
+    let task = current_task().unwrap();
+    let exited = task.inner_exclusive_access().res.is_none();
+    exited
+        || task
+            .process
+            .upgrade()
+            .map_or(true, |process| process.inner_exclusive_access().is_zombie)
+}
+
+/// A `SCHED_FIFO` thread keeps the CPU until it blocks or yields,
+/// `RLIMIT_RTTIME` bounds how long it may run without blocking.
+pub fn current_preemptible() -> bool {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .sched
+        .policy
+        != SchedPolicy::Fifo
 }
 
 pub fn current_add_signal(signal: SignalFlags) {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
-    process_inner.signals |= signal;
+    process_inner.add_signal(signal);
+}
+
+/// Charge the time since the last mode switch of current thread to user mode.
+pub fn current_charge_user_time() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .times
+        .charge_user();
+}
+
+/// Charge the time since the last mode switch of current thread to kernel mode.
+pub fn current_charge_kernel_time() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .times
+        .charge_kernel();
+}
+
+/// Called on every timer tick in user mode, send SIGXCPU once the soft
+/// `RLIMIT_CPU` or, for a realtime thread, `RLIMIT_RTTIME` is exceeded
+/// and SIGKILL at the hard one.
+pub fn current_check_cpu_limit() {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let mut rt_signal = None;
+    if task_inner.sched.policy.is_realtime() {
+        let usecs = task_inner.times.usecs_since_blocked();
+        if usecs >= process_inner.rlimits.max(RLIMIT_RTTIME) {
+            rt_signal = Some(SignalFlags::SIGKILL);
+        } else if usecs >= process_inner.rlimits.cur(RLIMIT_RTTIME)
+            && !task_inner.times.rt_xcpu_sent
+        {
+            task_inner.times.rt_xcpu_sent = true;
+            rt_signal = Some(SignalFlags::SIGXCPU);
+        }
+    }
+    drop(task_inner);
+    if let Some(signal) = rt_signal {
+        process_inner.add_signal(signal);
+    }
+    let soft = process_inner.rlimits.cur(RLIMIT_CPU);
+    let hard = process_inner.rlimits.max(RLIMIT_CPU);
+    if soft == RLIM_INFINITY && hard == RLIM_INFINITY {
+        return;
+    }
+    let secs = process_inner.cpu_times().total_secs();
+    if secs >= hard {
+        process_inner.add_signal(SignalFlags::SIGKILL);
+    } else if secs >= soft && !process_inner.xcpu_sent {
+        process_inner.xcpu_sent = true;
+        process_inner.add_signal(SignalFlags::SIGXCPU);
+    }
 }
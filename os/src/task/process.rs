@@ -1,12 +1,17 @@
-use super::id::RecycleAllocator;
+use super::id::{ustacks_range, RecycleAllocator, TaskUserRes};
 use super::manager::insert_into_pid2process;
+use super::resource::{CpuTimes, RLimits, RLIMIT_AS, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_NTHREAD};
+use super::signal::SignalActions;
 use super::TaskControlBlock;
-use super::{add_task, SignalFlags};
+use super::{add_task, current_task, wakeup_task, SignalFlags};
 use super::{pid_alloc, PidHandle};
+use crate::errno::Errno;
 use crate::fs::{File, Stdin, Stdout};
 use crate::mm::{translated_refmut, MemorySet, KERNEL_SPACE};
-use crate::sync::{Condvar, Mutex, Semaphore, UPIntrFreeCell, UPIntrRefMut};
+use crate::sync::{Condvar, DeadlockDetector, Mutex, RwLock, Semaphore};
+use crate::sync::{SpinNoIrqGuard, SpinNoIrqLock};
 use crate::trap::{trap_handler, TrapContext};
+use alloc::collections::BTreeSet;
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
@@ -16,7 +21,7 @@ pub struct ProcessControlBlock {
     // immutable
     pub pid: PidHandle,
     // mutable
-    inner: UPIntrFreeCell<ProcessControlBlockInner>,
+    inner: SpinNoIrqLock<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
@@ -26,48 +31,160 @@ pub struct ProcessControlBlockInner {
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// fds closed by `exec`
+    pub cloexec: BTreeSet<usize>,
+    /// pending signals
     pub signals: SignalFlags,
+    pub signal_mask: SignalFlags,
+    pub signal_actions: SignalActions,
+    /// stopped by SIGSTOP and friends until SIGCONT
+    pub stopped: bool,
+    /// threads blocked while the process is stopped
+    pub stopped_threads: Vec<Arc<TaskControlBlock>>,
+    /// the stop signal which has not been reported to `waitpid` yet
+    pub unreported_stop: Option<usize>,
+    /// process group id
+    pub pgid: usize,
+    /// session id
+    pub sid: usize,
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    /// threads blocked in `waittid`, woken whenever a joinable thread exits
+    pub join_waiters: Vec<Arc<TaskControlBlock>>,
     pub task_res_allocator: RecycleAllocator,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub rwlock_list: Vec<Option<Arc<RwLock>>>,
+    /// tracks `mutex_list` and `semaphore_list` even while disabled
+    pub deadlock: DeadlockDetector,
+    pub rlimits: RLimits,
+    /// CPU time of the threads already reaped
+    pub thread_times: CpuTimes,
+    /// CPU time of the children already reaped and their descendants
+    pub children_times: CpuTimes,
+    /// SIGXCPU has been sent for exceeding the soft CPU limit
+    pub xcpu_sent: bool,
 }
 
 impl ProcessControlBlockInner {
-    #[allow(unused)]
     pub fn get_user_token(&self) -> usize {
         self.memory_set.token()
     }
 
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
+    /// Return `None` if the lowest free fd reaches `RLIMIT_NOFILE`.
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        self.alloc_fd_from(0)
+    }
+
+    /// Allocate the lowest free fd not below `min`.
+    pub fn alloc_fd_from(&mut self, min: usize) -> Option<usize> {
+        let fd = (min..self.fd_table.len())
+            .find(|fd| self.fd_table[*fd].is_none())
+            .unwrap_or_else(|| self.fd_table.len().max(min));
+        if fd >= self.rlimits.cur(RLIMIT_NOFILE) {
+            return None;
+        }
+        while fd >= self.fd_table.len() {
             self.fd_table.push(None);
-            self.fd_table.len() - 1
         }
+        // a new fd is kept by `exec` unless told otherwise
+        self.cloexec.remove(&fd);
+        Some(fd)
     }
 
-    pub fn alloc_tid(&mut self) -> usize {
-        self.task_res_allocator.alloc()
+    /// Return `None` if the process has `RLIMIT_NTHREAD` threads already.
+    pub fn alloc_tid(&mut self) -> Option<usize> {
+        if self.task_res_allocator.allocated() >= self.rlimits.cur(RLIMIT_NTHREAD) {
+            return None;
+        }
+        Some(self.task_res_allocator.alloc())
     }
 
     pub fn dealloc_tid(&mut self, tid: usize) {
         self.task_res_allocator.dealloc(tid)
     }
 
+    /// Threads not reaped yet.
     pub fn thread_count(&self) -> usize {
-        self.tasks.len()
+        self.tasks.iter().flatten().count()
     }
 
     pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
         self.tasks[tid].as_ref().unwrap().clone()
     }
+
+    /// Release thread `tid` which has exited and its tid, return its exit code.
+    pub fn reap_thread(&mut self, tid: usize) -> Option<i32> {
+        let task = self.tasks[tid].take()?;
+        let task_inner = task.inner_exclusive_access();
+        self.thread_times.add(&task_inner.times.times);
+        self.dealloc_tid(tid);
+        task_inner.exit_code
+    }
+
+    /// Whether `[start, end)` meets the range kept for the user stacks of the
+    /// threads this process may have.
+    pub fn overlaps_ustacks(&self, start: usize, end: usize) -> bool {
+        let ustack_base = self.tasks[0].as_ref().and_then(|task| {
+            task.inner_exclusive_access()
+                .res
+                .as_ref()
+                .map(TaskUserRes::ustack_base)
+        });
+        match ustack_base {
+            Some(ustack_base) => {
+                let (bottom, top) = ustacks_range(ustack_base, self.rlimits.max(RLIMIT_NTHREAD));
+                start < top && bottom < end
+            }
+            // the main thread is exiting along with the process
+            None => true,
+        }
+    }
+
+    /// Whether the address space can grow by `size` bytes within `RLIMIT_AS`.
+    pub fn may_grow(&self, size: usize) -> bool {
+        self.memory_set.mapped_size().saturating_add(size) <= self.rlimits.cur(RLIMIT_AS)
+    }
+
+    /// CPU time of all threads of the process, the children excluded.
+    pub fn cpu_times(&self) -> CpuTimes {
+        let mut times = self.thread_times;
+        for task in self.tasks.iter().flatten() {
+            times.add(&task.inner_exclusive_access().times.times);
+        }
+        times
+    }
+
+    /// Make `signal` pending, SIGCONT resumes a stopped process at once.
+    /// SIGCONT and SIGKILL wake the threads up to act on them.
+    pub fn add_signal(&mut self, signal: SignalFlags) {
+        if signal.contains(SignalFlags::SIGCONT) {
+            self.stopped = false;
+            self.unreported_stop = None;
+            self.signals -= SignalFlags::STOP_SIGNALS;
+        } else if signal.intersects(SignalFlags::STOP_SIGNALS) {
+            self.signals -= SignalFlags::SIGCONT;
+        }
+        self.signals |= signal;
+        if signal.intersects(SignalFlags::SIGCONT | SignalFlags::SIGKILL) {
+            for task in self.stopped_threads.drain(..) {
+                wakeup_task(task);
+            }
+        }
+    }
+
+    /// Send SIGCHLD to the parent process.
+    pub fn notify_parent(&self) {
+        if let Some(parent) = self.parent.as_ref().and_then(|parent| parent.upgrade()) {
+            parent
+                .inner_exclusive_access()
+                .add_signal(SignalFlags::SIGCHLD);
+        }
+    }
 }
 
 impl ProcessControlBlock {
-    pub fn inner_exclusive_access(&self) -> UPIntrRefMut<'_, ProcessControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, ProcessControlBlockInner> {
         self.inner.exclusive_access()
     }
 
@@ -76,51 +193,64 @@ impl ProcessControlBlock {
         let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
         // allocate a pid
         let pid_handle = pid_alloc();
+        let pid = pid_handle.0;
         let process = Arc::new(Self {
             pid: pid_handle,
-            inner: unsafe {
-                UPIntrFreeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: vec![
+                    // 0 -> stdin
+                    Some(Arc::new(Stdin::new())),
+                    // 1 -> stdout
+                    Some(Arc::new(Stdout)),
+                    // 2 -> stderr
+                    Some(Arc::new(Stdout)),
+                ],
+                cloexec: BTreeSet::new(),
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                signal_actions: SignalActions::default(),
+                stopped: false,
+                stopped_threads: Vec::new(),
+                unreported_stop: None,
+                // init process leads the first session
+                pgid: pid,
+                sid: pid,
+                tasks: Vec::new(),
+                join_waiters: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                deadlock: DeadlockDetector::default(),
+                rlimits: RLimits::default(),
+                thread_times: CpuTimes::default(),
+                children_times: CpuTimes::default(),
+                xcpu_sent: false,
+            }),
         });
         // create a main thread, we should allocate ustack and trap_cx here
-        let task = Arc::new(TaskControlBlock::new(
-            Arc::clone(&process),
-            ustack_base,
-            true,
-        ));
+        let task =
+            Arc::new(TaskControlBlock::new(Arc::clone(&process), ustack_base, true).unwrap());
         // prepare trap_cx of main thread
         let task_inner = task.inner_exclusive_access();
         let trap_cx = task_inner.get_trap_cx();
-        let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
+        let tls_base = task_inner.res.as_ref().unwrap().tls_base();
         let kstack_top = task.kstack.get_top();
         drop(task_inner);
         *trap_cx = TrapContext::app_init_context(
             entry_point,
-            ustack_top,
+            tls_base,
             KERNEL_SPACE.exclusive_access().token(),
             kstack_top,
             trap_handler as usize,
         );
+        trap_cx.x[4] = tls_base;
         // add main thread to the process
         let mut process_inner = process.inner_exclusive_access();
         process_inner.tasks.push(Some(Arc::clone(&task)));
@@ -131,14 +261,23 @@ impl ProcessControlBlock {
         process
     }
 
-    /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
-        assert_eq!(self.inner_exclusive_access().thread_count(), 1);
+    /// Only support processes with a single thread, fail with `EBUSY` otherwise.
+    /// The arguments must fit in the user stack.
+    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) -> Result<(), Errno> {
+        if self.inner_exclusive_access().thread_count() != 1 {
+            return Err(Errno::EBUSY);
+        }
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
         let new_token = memory_set.token();
         // substitute memory_set
-        self.inner_exclusive_access().memory_set = memory_set;
+        let mut process_inner = self.inner_exclusive_access();
+        process_inner.memory_set = memory_set;
+        process_inner.signal_actions.reset_handlers();
+        for fd in core::mem::take(&mut process_inner.cloexec) {
+            process_inner.fd_table[fd] = None;
+        }
+        drop(process_inner);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
         let task = self.inner_exclusive_access().get_task(0);
@@ -146,8 +285,11 @@ impl ProcessControlBlock {
         task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
         task_inner.res.as_mut().unwrap().alloc_user_res();
         task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
-        // push arguments on user stack
-        let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
+        // push arguments on user stack below the thread-local block
+        let tls_base = task_inner.res.as_mut().unwrap().tls_base();
+        // long arguments may grow the stack, which takes the lock of the thread
+        drop(task_inner);
+        let mut user_sp = tls_base;
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
         let mut argv: Vec<_> = (0..=args.len())
@@ -157,17 +299,17 @@ impl ProcessControlBlock {
                     (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
                 )
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
         *argv[args.len()] = 0;
         for i in 0..args.len() {
             user_sp -= args[i].len() + 1;
             *argv[i] = user_sp;
             let mut p = user_sp;
             for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
+                *translated_refmut(new_token, p as *mut u8)? = *c;
                 p += 1;
             }
-            *translated_refmut(new_token, p as *mut u8) = 0;
+            *translated_refmut(new_token, p as *mut u8)? = 0;
         }
         // make the user_sp aligned to 8B for k210 platform
         user_sp -= user_sp % core::mem::size_of::<usize>();
@@ -179,15 +321,21 @@ impl ProcessControlBlock {
             task.kstack.get_top(),
             trap_handler as usize,
         );
+        trap_cx.x[4] = tls_base;
         trap_cx.x[10] = args.len();
         trap_cx.x[11] = argv_base;
-        *task_inner.get_trap_cx() = trap_cx;
+        *task.inner_exclusive_access().get_trap_cx() = trap_cx;
+        Ok(())
     }
 
     /// Only support processes with a single thread.
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+    /// Return `None` if current process has `RLIMIT_NPROC` children already.
+    pub fn fork(self: &Arc<Self>) -> Option<Arc<Self>> {
         let mut parent = self.inner_exclusive_access();
         assert_eq!(parent.thread_count(), 1);
+        if parent.children.len() >= parent.rlimits.cur(RLIMIT_NPROC) {
+            return None;
+        }
         // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
         let memory_set = MemorySet::from_existed_user(&parent.memory_set);
         // alloc a pid
@@ -204,39 +352,61 @@ impl ProcessControlBlock {
         // create child process pcb
         let child = Arc::new(Self {
             pid,
-            inner: unsafe {
-                UPIntrFreeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: new_fd_table,
+                cloexec: parent.cloexec.clone(),
+                signals: SignalFlags::empty(),
+                // handlers and mask are inherited
+                signal_mask: parent.signal_mask,
+                signal_actions: parent.signal_actions.clone(),
+                stopped: false,
+                stopped_threads: Vec::new(),
+                unreported_stop: None,
+                pgid: parent.pgid,
+                sid: parent.sid,
+                tasks: Vec::new(),
+                join_waiters: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                deadlock: DeadlockDetector::default(),
+                rlimits: parent.rlimits,
+                thread_times: CpuTimes::default(),
+                children_times: CpuTimes::default(),
+                xcpu_sent: false,
+            }),
         });
         // add child
         parent.children.push(Arc::clone(&child));
         // create main thread of child process
-        let task = Arc::new(TaskControlBlock::new(
-            Arc::clone(&child),
-            parent
-                .get_task(0)
-                .inner_exclusive_access()
-                .res
-                .as_ref()
-                .unwrap()
-                .ustack_base(),
-            // here we do not allocate trap_cx or ustack again
-            // but mention that we allocate a new kstack here
-            false,
-        ));
+        let task = Arc::new(
+            TaskControlBlock::new(
+                Arc::clone(&child),
+                parent
+                    .get_task(0)
+                    .inner_exclusive_access()
+                    .res
+                    .as_ref()
+                    .unwrap()
+                    .ustack_base(),
+                // here we do not allocate trap_cx or ustack again
+                // but mention that we allocate a new kstack here
+                false,
+            )
+            .unwrap(),
+        );
+        task.inner_exclusive_access().sched = current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .sched
+            .inherit();
         // attach task to child process
         let mut child_inner = child.inner_exclusive_access();
         child_inner.tasks.push(Some(Arc::clone(&task)));
@@ -249,7 +419,7 @@ impl ProcessControlBlock {
         insert_into_pid2process(child.getpid(), Arc::clone(&child));
         // add this thread to scheduler
         add_task(task);
-        child
+        Some(child)
     }
 
     pub fn getpid(&self) -> usize {
@@ -1,10 +1,18 @@
 use super::__switch;
 use super::{fetch_task, TaskStatus};
 use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
-use crate::sync::UPIntrFreeCell;
+use crate::config::MAX_HARTS;
+use crate::smp::{hart_id, set_idle};
+use crate::sync::SpinNoIrqLock;
+use crate::timer::{start_slice, stop_slice};
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::hint::spin_loop;
+use core::sync::atomic::Ordering;
 use lazy_static::*;
+use riscv::asm::wfi;
+use riscv::register::sstatus;
 
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
@@ -30,38 +38,72 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPIntrFreeCell<Processor> =
-        unsafe { UPIntrFreeCell::new(Processor::new()) };
+    static ref PROCESSORS: Vec<SpinNoIrqLock<Processor>> = (0..MAX_HARTS)
+        .map(|_| SpinNoIrqLock::new(Processor::new()))
+        .collect();
+}
+
+/// Processor of current hart.
+fn processor() -> &'static SpinNoIrqLock<Processor> {
+    &PROCESSORS[hart_id()]
+}
+
+/// Idle loop of each hart.
+/// Sleep until an interrupt, return a thread which became ready meanwhile.
+fn idle() -> Option<Arc<TaskControlBlock>> {
+    set_idle(true);
+    stop_slice();
+    // a thread added before the hart is marked idle does not kick it
+    let task = fetch_task();
+    if task.is_none() {
+        unsafe {
+            sstatus::set_sie();
+            wfi();
+            sstatus::clear_sie();
+        }
+    }
+    set_idle(false);
+    task
 }
 
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
-        if let Some(task) = fetch_task() {
-            let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
-            // access coming task TCB exclusively
-            let next_task_cx_ptr = task.inner.exclusive_session(|task_inner| {
-                task_inner.task_status = TaskStatus::Running;
-                &task_inner.task_cx as *const TaskContext
-            });
-            processor.current = Some(task);
-            // release processor manually
-            drop(processor);
-            unsafe {
-                __switch(idle_task_cx_ptr, next_task_cx_ptr);
-            }
-        } else {
-            println!("no tasks available in run_tasks");
+        let task = match fetch_task().or_else(idle) {
+            Some(task) => task,
+            None => continue,
+        };
+        // the task may still be switching out on the hart it ran before
+        while task.on_cpu.load(Ordering::Acquire) {
+            spin_loop();
+        }
+        task.on_cpu.store(true, Ordering::Relaxed);
+        let mut processor = processor().exclusive_access();
+        let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+        // access coming task TCB exclusively
+        let next_task_cx_ptr = task.inner.exclusive_session(|task_inner| {
+            task_inner.task_status = TaskStatus::Running;
+            task_inner.sched.start();
+            task_inner.times.restart();
+            &task_inner.task_cx as *const TaskContext
+        });
+        processor.current = Some(Arc::clone(&task));
+        // release processor manually
+        drop(processor);
+        start_slice();
+        unsafe {
+            __switch(idle_task_cx_ptr, next_task_cx_ptr);
         }
+        // the context of the task has been saved
+        task.on_cpu.store(false, Ordering::Release);
     }
 }
 
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    processor().exclusive_access().take_current()
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    processor().exclusive_access().current()
 }
 
 pub fn current_process() -> Arc<ProcessControlBlock> {
@@ -96,7 +138,7 @@ pub fn current_kstack_top() -> usize {
 
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     let idle_task_cx_ptr =
-        PROCESSOR.exclusive_session(|processor| processor.get_idle_task_cx_ptr());
+        processor().exclusive_session(|processor| processor.get_idle_task_cx_ptr());
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
@@ -0,0 +1,213 @@
+//! Resource limits and CPU accounting of processes.
+
+use crate::config::CLOCK_FREQ;
+use crate::errno::Errno;
+use crate::timer::{get_time, TICKS_PER_SEC, USEC_PER_SEC};
+
+/// CPU time in seconds, SIGXCPU at the soft limit and SIGKILL at the hard one
+pub const RLIMIT_CPU: usize = 0;
+/// number of children not reaped yet
+pub const RLIMIT_NPROC: usize = 6;
+/// number of open files, i.e. the largest fd plus one
+pub const RLIMIT_NOFILE: usize = 7;
+/// size of the address space in bytes
+pub const RLIMIT_AS: usize = 9;
+/// CPU time in microseconds a realtime thread may use without blocking,
+/// SIGXCPU at the soft limit and SIGKILL at the hard one
+pub const RLIMIT_RTTIME: usize = 15;
+/// number of threads, not in Linux
+pub const RLIMIT_NTHREAD: usize = 16;
+pub const RLIM_NLIMITS: usize = 17;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
+/// Layout shared with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RLimit {
+    /// soft limit, enforced
+    pub cur: usize,
+    /// hard limit, the ceiling of the soft limit
+    pub max: usize,
+}
+
+impl RLimit {
+    const fn new(cur: usize, max: usize) -> Self {
+        Self { cur, max }
+    }
+    const fn unlimited() -> Self {
+        Self::new(RLIM_INFINITY, RLIM_INFINITY)
+    }
+}
+
+/// Limits of a process, inherited by `fork` and kept across `exec`.
+#[derive(Copy, Clone)]
+pub struct RLimits {
+    table: [RLimit; RLIM_NLIMITS],
+}
+
+impl Default for RLimits {
+    fn default() -> Self {
+        let mut table = [RLimit::unlimited(); RLIM_NLIMITS];
+        table[RLIMIT_NPROC] = RLimit::new(64, 256);
+        table[RLIMIT_NOFILE] = RLimit::new(64, 256);
+        table[RLIMIT_NTHREAD] = RLimit::new(64, 256);
+        // a busy realtime thread must not hold a hart forever
+        table[RLIMIT_RTTIME] = RLimit::new(USEC_PER_SEC, 2 * USEC_PER_SEC);
+        Self { table }
+    }
+}
+
+impl RLimits {
+    pub fn get(&self, resource: usize) -> Option<RLimit> {
+        self.table.get(resource).copied()
+    }
+    /// The hard limit can only be lowered, fail with `EPERM` on raising it.
+    pub fn set(&mut self, resource: usize, limit: RLimit) -> Result<(), Errno> {
+        let old = self.table.get_mut(resource).ok_or(Errno::EINVAL)?;
+        if limit.cur > limit.max {
+            return Err(Errno::EINVAL);
+        }
+        if limit.max > old.max {
+            return Err(Errno::EPERM);
+        }
+        // a process always keeps its main thread
+        if resource == RLIMIT_NTHREAD && limit.cur == 0 {
+            return Err(Errno::EINVAL);
+        }
+        *old = limit;
+        Ok(())
+    }
+    /// The soft limit of `resource`.
+    pub fn cur(&self, resource: usize) -> usize {
+        self.table[resource].cur
+    }
+    pub fn max(&self, resource: usize) -> usize {
+        self.table[resource].max
+    }
+}
+
+/// CPU time spent in user and kernel mode, in cycles of the `time` CSR.
+#[derive(Copy, Clone, Default)]
+pub struct CpuTimes {
+    pub user: usize,
+    pub kernel: usize,
+}
+
+impl CpuTimes {
+    pub fn add(&mut self, other: &Self) {
+        self.user += other.user;
+        self.kernel += other.kernel;
+    }
+    pub fn total(&self) -> usize {
+        self.user + self.kernel
+    }
+    pub fn total_secs(&self) -> usize {
+        self.total() / CLOCK_FREQ
+    }
+}
+
+/// CPU accounting of a thread, the time since the last mode switch is
+/// charged to user or kernel mode when the thread leaves that mode.
+pub struct ThreadTimes {
+    pub times: CpuTimes,
+    /// when the current period began
+    since: usize,
+    /// total CPU time when the thread last blocked
+    blocked_at: usize,
+    /// SIGXCPU has been sent for the soft `RLIMIT_RTTIME`
+    pub rt_xcpu_sent: bool,
+}
+
+impl ThreadTimes {
+    pub fn new() -> Self {
+        Self {
+            times: CpuTimes::default(),
+            since: get_time(),
+            blocked_at: 0,
+            rt_xcpu_sent: false,
+        }
+    }
+    /// Called when the thread blocks, which restarts the `RLIMIT_RTTIME` count.
+    pub fn mark_blocked(&mut self) {
+        self.blocked_at = self.times.total();
+        self.rt_xcpu_sent = false;
+    }
+    /// CPU time in microseconds used since the thread last blocked.
+    pub fn usecs_since_blocked(&self) -> usize {
+        cycles_to_usecs(self.times.total() - self.blocked_at)
+    }
+    /// Called when the thread is dispatched, the time it waited is not charged.
+    pub fn restart(&mut self) {
+        self.since = get_time();
+    }
+    /// Called on every trap from user mode.
+    pub fn charge_user(&mut self) {
+        let now = get_time();
+        self.times.user += now - self.since;
+        self.since = now;
+    }
+    /// Called before returning to user mode and when giving up the CPU.
+    pub fn charge_kernel(&mut self) {
+        let now = get_time();
+        self.times.kernel += now - self.since;
+        self.since = now;
+    }
+}
+
+/// Layout shared with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_cycles(cycles: usize) -> Self {
+        Self {
+            sec: cycles / CLOCK_FREQ,
+            usec: (cycles % CLOCK_FREQ) * USEC_PER_SEC / CLOCK_FREQ,
+        }
+    }
+}
+
+/// Layout shared with user space, the leading fields of `struct rusage`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RUsage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+}
+
+impl From<CpuTimes> for RUsage {
+    fn from(times: CpuTimes) -> Self {
+        Self {
+            ru_utime: TimeVal::from_cycles(times.user),
+            ru_stime: TimeVal::from_cycles(times.kernel),
+        }
+    }
+}
+
+/// Layout shared with user space, in clock ticks of `TICKS_PER_SEC`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tms {
+    pub tms_utime: usize,
+    pub tms_stime: usize,
+    pub tms_cutime: usize,
+    pub tms_cstime: usize,
+}
+
+/// Convert cycles of the `time` CSR to microseconds.
+pub fn cycles_to_usecs(cycles: usize) -> usize {
+    (cycles as u128 * USEC_PER_SEC as u128 / CLOCK_FREQ as u128) as usize
+}
+
+/// Convert cycles of the `time` CSR to clock ticks.
+pub fn cycles_to_ticks(cycles: usize) -> usize {
+    cycles / (CLOCK_FREQ / TICKS_PER_SEC)
+}
@@ -0,0 +1,295 @@
+//! Scheduling policies behind `TaskManager`.
+//!
+//! Realtime threads (`SCHED_FIFO`/`SCHED_RR`) always run before normal
+//! threads, which run before `SCHED_IDLE` threads. Normal threads are
+//! scheduled by the policy chosen at build time with the `sched-rr` or
+//! `sched-fair` feature, stride by default. Round-robin ignores nice.
+
+use super::TaskControlBlock;
+use crate::timer::get_time;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_FIFO: usize = 1;
+pub const SCHED_RR: usize = 2;
+pub const SCHED_IDLE: usize = 5;
+
+pub const NICE_MIN: isize = -20;
+pub const NICE_MAX: isize = 19;
+pub const RT_PRIORITY_MAX: usize = 99;
+
+/// Weight of each nice value, from -20 to 19, a step changes the CPU share by about 10%.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+const NICE_0_WEIGHT: u64 = 1024;
+const BIG_STRIDE: u64 = 1 << 32;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SchedPolicy {
+    Other,
+    Fifo,
+    RoundRobin,
+    Idle,
+}
+
+impl SchedPolicy {
+    pub fn from_raw(policy: usize) -> Option<Self> {
+        match policy {
+            SCHED_OTHER => Some(Self::Other),
+            SCHED_FIFO => Some(Self::Fifo),
+            SCHED_RR => Some(Self::RoundRobin),
+            SCHED_IDLE => Some(Self::Idle),
+            _ => None,
+        }
+    }
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, Self::Fifo | Self::RoundRobin)
+    }
+}
+
+/// Scheduling state of a thread.
+pub struct SchedEntity {
+    pub policy: SchedPolicy,
+    /// used by normal threads
+    pub nice: isize,
+    /// used by realtime threads, higher runs first
+    pub rt_priority: usize,
+    /// realtime priority inherited from the threads waiting for
+    /// priority-inheritance mutexes it holds, 0 if none
+    pub inherited_priority: usize,
+    pass: u64,
+    vruntime: u64,
+    /// when the thread was dispatched last time
+    exec_start: usize,
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Other,
+            nice: 0,
+            rt_priority: 0,
+            inherited_priority: 0,
+            pass: 0,
+            vruntime: 0,
+            exec_start: 0,
+        }
+    }
+    /// Parameters of a new thread are inherited from its creator.
+    pub fn inherit(&self) -> Self {
+        Self {
+            policy: self.policy,
+            nice: self.nice,
+            rt_priority: self.rt_priority,
+            ..Self::new()
+        }
+    }
+    /// The realtime priority the thread runs at, 0 for a normal thread not boosted.
+    pub fn effective_priority(&self) -> usize {
+        self.rt_priority.max(self.inherited_priority)
+    }
+    /// A boosted normal thread is scheduled as a realtime one.
+    pub fn effective_policy(&self) -> SchedPolicy {
+        if self.inherited_priority > 0 && !self.policy.is_realtime() {
+            SchedPolicy::RoundRobin
+        } else {
+            self.policy
+        }
+    }
+    fn weight(&self) -> u64 {
+        NICE_TO_WEIGHT[(self.nice - NICE_MIN) as usize]
+    }
+    /// Called when the thread is dispatched.
+    pub fn start(&mut self) {
+        self.exec_start = get_time();
+    }
+    /// Called when the thread gives up the CPU, account its virtual runtime.
+    pub fn stop(&mut self) {
+        let delta = (get_time() - self.exec_start) as u64;
+        self.vruntime += delta * NICE_0_WEIGHT / self.weight();
+    }
+}
+
+pub trait Scheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+    /// Take `task` out of the ready queue, return false if it is not there.
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+}
+
+/// Remove `task` from a ready queue keyed by `K`.
+fn remove_keyed<K: Ord + Copy>(
+    ready_queue: &mut BTreeMap<K, Arc<TaskControlBlock>>,
+    task: &Arc<TaskControlBlock>,
+) -> bool {
+    let key = ready_queue
+        .iter()
+        .find(|(_, queued)| Arc::ptr_eq(queued, task))
+        .map(|(&key, _)| key);
+    match key {
+        Some(key) => ready_queue.remove(&key).is_some(),
+        None => false,
+    }
+}
+
+/// A simple FIFO scheduler.
+pub struct RoundRobinScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        match self
+            .ready_queue
+            .iter()
+            .position(|queued| Arc::ptr_eq(queued, task))
+        {
+            Some(idx) => self.ready_queue.remove(idx).is_some(),
+            None => false,
+        }
+    }
+}
+
+/// Run the thread with the smallest pass, which advances by a stride
+/// inversely proportional to its weight.
+pub struct StrideScheduler {
+    /// keyed by (pass, seq), `seq` keeps the order of equal passes
+    ready_queue: BTreeMap<(u64, usize), Arc<TaskControlBlock>>,
+    min_pass: u64,
+    seq: usize,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            min_pass: 0,
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut task_inner = task.inner_exclusive_access();
+        // a thread coming back from sleep should not monopolize the CPU
+        task_inner.sched.pass = task_inner.sched.pass.max(self.min_pass);
+        let pass = task_inner.sched.pass;
+        drop(task_inner);
+        self.seq += 1;
+        self.ready_queue.insert((pass, self.seq), task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (&key, _) = self.ready_queue.iter().next()?;
+        let task = self.ready_queue.remove(&key).unwrap();
+        self.min_pass = key.0;
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.sched.pass += BIG_STRIDE / task_inner.sched.weight();
+        drop(task_inner);
+        Some(task)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        remove_keyed(&mut self.ready_queue, task)
+    }
+}
+
+/// Run the thread with the smallest virtual runtime, which grows
+/// inversely proportional to its weight.
+pub struct FairScheduler {
+    /// keyed by (vruntime, seq), `seq` keeps the order of equal vruntimes
+    ready_queue: BTreeMap<(u64, usize), Arc<TaskControlBlock>>,
+    min_vruntime: u64,
+    seq: usize,
+}
+
+impl FairScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            min_vruntime: 0,
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for FairScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut task_inner = task.inner_exclusive_access();
+        // a thread coming back from sleep should not monopolize the CPU
+        task_inner.sched.vruntime = task_inner.sched.vruntime.max(self.min_vruntime);
+        let vruntime = task_inner.sched.vruntime;
+        drop(task_inner);
+        self.seq += 1;
+        self.ready_queue.insert((vruntime, self.seq), task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (&key, _) = self.ready_queue.iter().next()?;
+        self.min_vruntime = key.0;
+        self.ready_queue.remove(&key)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        remove_keyed(&mut self.ready_queue, task)
+    }
+}
+
+/// Realtime threads in order of priority, FIFO among equal priorities.
+pub struct RealtimeScheduler {
+    /// keyed by (RT_PRIORITY_MAX - priority, seq)
+    ready_queue: BTreeMap<(usize, usize), Arc<TaskControlBlock>>,
+    seq: usize,
+}
+
+impl RealtimeScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for RealtimeScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let priority = task.inner_exclusive_access().sched.effective_priority();
+        self.seq += 1;
+        self.ready_queue
+            .insert((RT_PRIORITY_MAX - priority, self.seq), task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (&key, _) = self.ready_queue.iter().next()?;
+        self.ready_queue.remove(&key)
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        remove_keyed(&mut self.ready_queue, task)
+    }
+}
+
+/// The policy of normal threads chosen at build time.
+pub fn default_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "sched-rr") {
+        Box::new(RoundRobinScheduler::new())
+    } else if cfg!(feature = "sched-fair") {
+        Box::new(FairScheduler::new())
+    } else {
+        Box::new(StrideScheduler::new())
+    }
+}
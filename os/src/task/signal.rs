@@ -1,29 +1,158 @@
 use bitflags::*;
 
+pub const MAX_SIG: usize = 31;
+
+/// Handler value which applies the default action.
+pub const SIG_DFL: usize = 0;
+/// Handler value which ignores the signal.
+pub const SIG_IGN: usize = 1;
+
 bitflags! {
+    /// Signal `n` is represented by bit `1 << n`.
     pub struct SignalFlags: u32 {
+        const SIGHUP    = 1 << 1;
         const SIGINT    = 1 << 2;
+        const SIGQUIT   = 1 << 3;
         const SIGILL    = 1 << 4;
+        const SIGTRAP   = 1 << 5;
         const SIGABRT   = 1 << 6;
+        const SIGBUS    = 1 << 7;
         const SIGFPE    = 1 << 8;
+        const SIGKILL   = 1 << 9;
+        const SIGUSR1   = 1 << 10;
         const SIGSEGV   = 1 << 11;
+        const SIGUSR2   = 1 << 12;
+        const SIGPIPE   = 1 << 13;
+        const SIGALRM   = 1 << 14;
+        const SIGTERM   = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD   = 1 << 17;
+        const SIGCONT   = 1 << 18;
+        const SIGSTOP   = 1 << 19;
+        const SIGTSTP   = 1 << 20;
+        const SIGTTIN   = 1 << 21;
+        const SIGTTOU   = 1 << 22;
+        const SIGURG    = 1 << 23;
+        const SIGXCPU   = 1 << 24;
+        const SIGXFSZ   = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF   = 1 << 27;
+        const SIGWINCH  = 1 << 28;
+        const SIGIO     = 1 << 29;
+        const SIGPWR    = 1 << 30;
+        const SIGSYS    = 1 << 31;
     }
 }
 
+/// What happens to a process when a signal with `SIG_DFL` is delivered.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
+    /// Signals which can neither be caught, ignored nor blocked.
+    pub const UNCATCHABLE: Self =
+        Self::from_bits_truncate(Self::SIGKILL.bits() | Self::SIGSTOP.bits());
+    pub const STOP_SIGNALS: Self = Self::from_bits_truncate(
+        Self::SIGSTOP.bits() | Self::SIGTSTP.bits() | Self::SIGTTIN.bits() | Self::SIGTTOU.bits(),
+    );
+
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Self::from_bits(1 << signum)
         } else {
             None
         }
     }
+    /// Return the number of the lowest signal in the set.
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+    pub fn default_action(&self) -> DefaultAction {
+        if self.intersects(Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH) {
+            DefaultAction::Ignore
+        } else if self.intersects(Self::STOP_SIGNALS) {
+            DefaultAction::Stop
+        } else if self.contains(Self::SIGCONT) {
+            DefaultAction::Continue
+        } else {
+            DefaultAction::Terminate
+        }
+    }
+    /// Message printed when a process is terminated by the signal.
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Self::SIGHUP => "Hangup, SIGHUP=1",
+            Self::SIGINT => "Killed, SIGINT=2",
+            Self::SIGQUIT => "Quit, SIGQUIT=3",
+            Self::SIGILL => "Illegal Instruction, SIGILL=4",
+            Self::SIGTRAP => "Trace/Breakpoint Trap, SIGTRAP=5",
+            Self::SIGABRT => "Aborted, SIGABRT=6",
+            Self::SIGBUS => "Bus Error, SIGBUS=7",
+            Self::SIGFPE => "Erroneous Arithmetic Operation, SIGFPE=8",
+            Self::SIGKILL => "Killed, SIGKILL=9",
+            Self::SIGUSR1 => "User Defined Signal 1, SIGUSR1=10",
+            Self::SIGSEGV => "Segmentation Fault, SIGSEGV=11",
+            Self::SIGUSR2 => "User Defined Signal 2, SIGUSR2=12",
+            Self::SIGPIPE => "Broken Pipe, SIGPIPE=13",
+            Self::SIGALRM => "Alarm Clock, SIGALRM=14",
+            Self::SIGTERM => "Terminated, SIGTERM=15",
+            Self::SIGSTKFLT => "Stack Fault, SIGSTKFLT=16",
+            Self::SIGXCPU => "CPU Time Limit Exceeded, SIGXCPU=24",
+            Self::SIGXFSZ => "File Size Limit Exceeded, SIGXFSZ=25",
+            Self::SIGVTALRM => "Virtual Timer Expired, SIGVTALRM=26",
+            Self::SIGPROF => "Profiling Timer Expired, SIGPROF=27",
+            Self::SIGIO => "I/O Possible, SIGIO=29",
+            Self::SIGPWR => "Power Failure, SIGPWR=30",
+            Self::SIGSYS => "Bad System Call, SIGSYS=31",
+            _ => "Killed by a signal",
+        }
+    }
+}
+
+/// Layout shared with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SignalAction {
+    /// `SIG_DFL`, `SIG_IGN` or the entry of a user handler
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
+
+impl SignalActions {
+    /// Handlers are reset by `exec` while ignored signals stay ignored.
+    pub fn reset_handlers(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
 }
@@ -1,22 +1,29 @@
 use super::id::TaskUserRes;
+use super::kthread::KernelThreadFn;
+use super::resource::ThreadTimes;
+use super::scheduler::SchedEntity;
 use super::{kstack_alloc, KernelStack, ProcessControlBlock, TaskContext};
 use crate::trap::TrapContext;
 use crate::{
     mm::PhysPageNum,
-    sync::{UPIntrFreeCell, UPIntrRefMut},
+    sync::{SpinNoIrqGuard, SpinNoIrqLock},
 };
 use alloc::sync::{Arc, Weak};
+use core::sync::atomic::AtomicBool;
 
 pub struct TaskControlBlock {
     // immutable
+    /// dangling for kernel threads
     pub process: Weak<ProcessControlBlock>,
     pub kstack: KernelStack,
+    /// set while a hart runs the thread, until its context is saved
+    pub on_cpu: AtomicBool,
     // mutable
-    pub inner: UPIntrFreeCell<TaskControlBlockInner>,
+    pub inner: SpinNoIrqLock<TaskControlBlockInner>,
 }
 
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> UPIntrRefMut<'_, TaskControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
 
@@ -33,6 +40,16 @@ pub struct TaskControlBlockInner {
     pub task_cx: TaskContext,
     pub task_status: TaskStatus,
     pub exit_code: Option<i32>,
+    /// reaped as soon as it exits instead of by `waittid`
+    pub detached: bool,
+    pub sched: SchedEntity,
+    /// the signal whose user handler is running
+    pub handling_sig: Option<usize>,
+    /// trap context to be restored by `sigreturn`
+    pub trap_cx_backup: Option<TrapContext>,
+    pub times: ThreadTimes,
+    /// body of a kernel thread not started yet
+    pub kthread_fn: Option<KernelThreadFn>,
 }
 
 impl TaskControlBlockInner {
@@ -47,27 +64,58 @@ impl TaskControlBlockInner {
 }
 
 impl TaskControlBlock {
+    /// Return `None` if the process has reached its thread limit.
     pub fn new(
         process: Arc<ProcessControlBlock>,
         ustack_base: usize,
         alloc_user_res: bool,
-    ) -> Self {
-        let res = TaskUserRes::new(Arc::clone(&process), ustack_base, alloc_user_res);
+    ) -> Option<Self> {
+        let res = TaskUserRes::new(Arc::clone(&process), ustack_base, alloc_user_res)?;
         let trap_cx_ppn = res.trap_cx_ppn();
         let kstack = kstack_alloc();
         let kstack_top = kstack.get_top();
-        Self {
+        Some(Self {
             process: Arc::downgrade(&process),
             kstack,
-            inner: unsafe {
-                UPIntrFreeCell::new(TaskControlBlockInner {
-                    res: Some(res),
-                    trap_cx_ppn,
-                    task_cx: TaskContext::goto_trap_return(kstack_top),
-                    task_status: TaskStatus::Ready,
-                    exit_code: None,
-                })
-            },
+            on_cpu: AtomicBool::new(false),
+            inner: SpinNoIrqLock::new(TaskControlBlockInner {
+                res: Some(res),
+                trap_cx_ppn,
+                task_cx: TaskContext::goto_trap_return(kstack_top),
+                task_status: TaskStatus::Ready,
+                exit_code: None,
+                detached: false,
+                sched: SchedEntity::new(),
+                handling_sig: None,
+                trap_cx_backup: None,
+                times: ThreadTimes::new(),
+                kthread_fn: None,
+            }),
+        })
+    }
+
+    /// Create a kernel thread running `f`, which has neither a process nor user resources.
+    pub fn new_kernel(f: KernelThreadFn) -> Self {
+        let kstack = kstack_alloc();
+        let kstack_top = kstack.get_top();
+        Self {
+            process: Weak::new(),
+            kstack,
+            on_cpu: AtomicBool::new(false),
+            inner: SpinNoIrqLock::new(TaskControlBlockInner {
+                res: None,
+                // never used since the thread does not trap from user mode
+                trap_cx_ppn: PhysPageNum(0),
+                task_cx: TaskContext::goto_kthread_entry(kstack_top),
+                task_status: TaskStatus::Ready,
+                exit_code: None,
+                detached: false,
+                sched: SchedEntity::new(),
+                handling_sig: None,
+                trap_cx_backup: None,
+                times: ThreadTimes::new(),
+                kthread_fn: Some(f),
+            }),
         }
     }
 }
@@ -0,0 +1,87 @@
+//! Deferred work run by kernel threads.
+//!
+//! Interrupt handlers only acknowledge their devices and queue the rest of
+//! the work, which a worker thread runs later in a schedulable context
+//! where it is allowed to block.
+
+use super::kthread::kthread_spawn;
+use super::{block_current_task, current_task, schedule, suspend_current_and_run_next};
+use super::{wakeup_task, TaskControlBlock};
+use crate::sync::SpinNoIrqLock;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+type Work = Box<dyn FnOnce() + Send>;
+
+pub struct WorkQueue {
+    inner: SpinNoIrqLock<WorkQueueInner>,
+}
+
+struct WorkQueueInner {
+    works: VecDeque<Work>,
+    /// the worker waiting for works
+    idle_worker: Option<Arc<TaskControlBlock>>,
+}
+
+impl WorkQueue {
+    /// Create a work queue served by a new worker thread.
+    pub fn new() -> Arc<Self> {
+        let queue = Arc::new(Self {
+            inner: SpinNoIrqLock::new(WorkQueueInner {
+                works: VecDeque::new(),
+                idle_worker: None,
+            }),
+        });
+        let worker_queue = Arc::clone(&queue);
+        kthread_spawn(move || worker_queue.run());
+        queue
+    }
+
+    /// Queue `work` to be run by the worker, it can be called in interrupt handlers.
+    pub fn queue_work<F>(&self, work: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut inner = self.inner.exclusive_access();
+        inner.works.push_back(Box::new(work));
+        if let Some(worker) = inner.idle_worker.take() {
+            wakeup_task(worker);
+        }
+    }
+
+    fn run(&self) {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if let Some(work) = inner.works.pop_front() {
+                drop(inner);
+                work();
+                // the worker is not preempted, let others run between works
+                suspend_current_and_run_next();
+            } else {
+                inner.idle_worker = Some(current_task().unwrap());
+                let task_cx_ptr = block_current_task();
+                drop(inner);
+                schedule(task_cx_ptr);
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref SYSTEM_WQ: Arc<WorkQueue> = WorkQueue::new();
+}
+
+/// Start the worker of the system work queue, it must be called before any work is queued.
+pub fn init_workqueue() {
+    let _system_wq = SYSTEM_WQ.clone();
+}
+
+/// Queue `work` on the system work queue.
+pub fn schedule_work<F>(work: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    SYSTEM_WQ.queue_work(work);
+}
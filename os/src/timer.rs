@@ -1,16 +1,40 @@
-use core::cmp::Ordering;
+//! Tickless timers.
+//!
+//! Each hart programs its SBI timer for the earlier of the end of the
+//! time slice of the running thread and the earliest pending timer, an
+//! idle hart has no time slice and sleeps until a timer expires. Every
+//! hart includes the earliest timer whenever it reprograms, so the hart
+//! adding a new earliest timer only has to reprogram itself.
+//!
+//! `CLOCK_REALTIME` is the monotonic clock plus an offset read from the
+//! RTC at boot, setting it writes the RTC as well.
 
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, MAX_HARTS};
+use crate::drivers::{rtc::RtcDevice, RTC};
 use crate::sbi::set_timer;
-use crate::sync::UPIntrFreeCell;
+use crate::smp::hart_id;
+use crate::sync::SpinNoIrqLock;
 use crate::task::{wakeup_task, TaskControlBlock};
-use alloc::collections::BinaryHeap;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 use riscv::register::time;
 
-const TICKS_PER_SEC: usize = 100;
+pub const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+pub const USEC_PER_SEC: usize = 1_000_000;
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+
+/// `clock_nanosleep` flag, the request is an absolute time of the clock.
+pub const TIMER_ABSTIME: usize = 1;
 
 pub fn get_time() -> usize {
     time::read()
@@ -20,55 +44,257 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// Convert microseconds to cycles of the `time` CSR, the frequency may not be a multiple of 1MHz.
+pub fn us_to_cycles(us: usize) -> usize {
+    (us as u128 * CLOCK_FREQ as u128 / USEC_PER_SEC as u128) as usize
+}
+
+/// Length of a time slice in cycles.
+const SLICE_CYCLES: usize = CLOCK_FREQ / TICKS_PER_SEC;
+/// No deadline.
+const NEVER: usize = usize::MAX;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_SLICE: AtomicUsize = AtomicUsize::new(NEVER);
+
+/// End of the time slice on each hart, `NEVER` while idle.
+static SLICE_END: [AtomicUsize; MAX_HARTS] = [NO_SLICE; MAX_HARTS];
+
+/// Program the timer of current hart for its next deadline.
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let slice_end = SLICE_END[hart_id()].load(Ordering::Relaxed);
+    let timer = TIMERS
+        .exclusive_access()
+        .timers
+        .keys()
+        .next()
+        .map_or(NEVER, |key| key.0);
+    set_timer(slice_end.min(timer));
 }
 
-pub struct TimerCondVar {
-    pub expire_ms: usize,
-    pub task: Arc<TaskControlBlock>,
+/// Called when a thread is dispatched on current hart.
+pub fn start_slice() {
+    SLICE_END[hart_id()].store(get_time() + SLICE_CYCLES, Ordering::Relaxed);
+    set_next_trigger();
+}
+
+/// Called when current hart goes idle, only timers can wake it up then.
+pub fn stop_slice() {
+    SLICE_END[hart_id()].store(NEVER, Ordering::Relaxed);
+    set_next_trigger();
 }
 
-impl PartialEq for TimerCondVar {
-    fn eq(&self, other: &Self) -> bool {
-        self.expire_ms == other.expire_ms
+/// Handle a timer interrupt, return whether the time slice is used up.
+pub fn handle_timer_interrupt() -> bool {
+    check_timer();
+    let now = get_time();
+    let slice_end = &SLICE_END[hart_id()];
+    let expired = now >= slice_end.load(Ordering::Relaxed);
+    if expired {
+        // a new slice begins if the thread keeps running
+        slice_end.store(now + SLICE_CYCLES, Ordering::Relaxed);
     }
+    set_next_trigger();
+    expired
+}
+
+fn monotonic_ns() -> usize {
+    (get_time() as u128 * NSEC_PER_SEC as u128 / CLOCK_FREQ as u128) as usize
+}
+
+/// `CLOCK_REALTIME` minus `CLOCK_MONOTONIC` in nanoseconds.
+static REALTIME_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init_realtime() {
+    let offset = (RTC.read_ns() as usize).saturating_sub(monotonic_ns());
+    REALTIME_OFFSET.store(offset, Ordering::Relaxed);
+}
+
+pub fn realtime() -> TimeSpec {
+    TimeSpec::from_ns(monotonic_ns() + REALTIME_OFFSET.load(Ordering::Relaxed))
 }
-impl Eq for TimerCondVar {}
-impl PartialOrd for TimerCondVar {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let a = -(self.expire_ms as isize);
-        let b = -(other.expire_ms as isize);
-        Some(a.cmp(&b))
+
+/// Return false if `time` is invalid or earlier than the boot.
+pub fn set_realtime(time: &TimeSpec) -> bool {
+    let ns = match time.to_ns() {
+        Some(ns) => ns,
+        None => return false,
+    };
+    let monotonic = monotonic_ns();
+    if ns < monotonic {
+        return false;
     }
+    RTC.set_ns(ns as u64);
+    REALTIME_OFFSET.store(ns - monotonic, Ordering::Relaxed);
+    true
+}
+
+/// Convert an absolute time of `CLOCK_REALTIME` to cycles of the `time` CSR.
+pub fn realtime_to_cycles(time: &TimeSpec) -> Option<usize> {
+    let ns = time
+        .to_ns()?
+        .saturating_sub(REALTIME_OFFSET.load(Ordering::Relaxed));
+    Some((ns as u128 * CLOCK_FREQ as u128 / NSEC_PER_SEC as u128) as usize)
 }
 
-impl Ord for TimerCondVar {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+/// Layout shared with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_cycles(cycles: usize) -> Self {
+        Self {
+            sec: cycles / CLOCK_FREQ,
+            nsec: ((cycles % CLOCK_FREQ) as u128 * NSEC_PER_SEC as u128 / CLOCK_FREQ as u128)
+                as usize,
+        }
     }
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            sec: ns / NSEC_PER_SEC,
+            nsec: ns % NSEC_PER_SEC,
+        }
+    }
+    /// Return `None` if `nsec` is out of range or the time overflows.
+    pub fn to_ns(&self) -> Option<usize> {
+        if self.nsec >= NSEC_PER_SEC {
+            return None;
+        }
+        self.sec.checked_mul(NSEC_PER_SEC)?.checked_add(self.nsec)
+    }
+    /// Return `None` if `nsec` is out of range.
+    pub fn to_cycles(&self) -> Option<usize> {
+        if self.nsec >= NSEC_PER_SEC {
+            return None;
+        }
+        let nsec_cycles = self.nsec as u128 * CLOCK_FREQ as u128 / NSEC_PER_SEC as u128;
+        Some(
+            self.sec
+                .saturating_mul(CLOCK_FREQ)
+                .saturating_add(nsec_cycles as usize),
+        )
+    }
+}
+
+pub type TimerCallback = Box<dyn FnOnce() + Send>;
+
+pub struct TimerCondVar {
+    /// in cycles of the `time` CSR
+    pub expire: usize,
+    /// `None` for one-shot timers of drivers
+    pub task: Option<Arc<TaskControlBlock>>,
+    /// run instead of waking `task` up when the timer fires
+    pub callback: Option<TimerCallback>,
+}
+
+struct Timers {
+    /// ordered by expiry, the id tells timers with the same expiry apart
+    timers: BTreeMap<(usize, usize), TimerCondVar>,
+    next_id: usize,
 }
 
 lazy_static! {
-    static ref TIMERS: UPIntrFreeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPIntrFreeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+    static ref TIMERS: SpinNoIrqLock<Timers> = SpinNoIrqLock::new(Timers {
+        timers: BTreeMap::new(),
+        next_id: 0,
+    });
 }
 
-pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+/// Identifies a pending timer for `cancel_timer`.
+#[derive(Copy, Clone)]
+pub struct TimerId(usize, usize);
+
+fn push_timer(timer: TimerCondVar) -> TimerId {
     let mut timers = TIMERS.exclusive_access();
-    timers.push(TimerCondVar { expire_ms, task });
+    let id = timers.next_id;
+    timers.next_id += 1;
+    let expire = timer.expire;
+    timers.timers.insert((expire, id), timer);
+    let earliest = timers.timers.keys().next() == Some(&(expire, id));
+    drop(timers);
+    if earliest {
+        set_next_trigger();
+    }
+    TimerId(expire, id)
 }
 
-pub fn check_timer() {
-    let current_ms = get_time_ms();
+/// Wake `task` up at `expire` cycles.
+pub fn add_timer(expire: usize, task: Arc<TaskControlBlock>) -> TimerId {
+    push_timer(TimerCondVar {
+        expire,
+        task: Some(task),
+        callback: None,
+    })
+}
+
+/// Run `callback` on behalf of `task` at `expire` cycles, it runs in interrupt context.
+pub fn add_timer_callback<F>(expire: usize, task: Arc<TaskControlBlock>, callback: F) -> TimerId
+where
+    F: FnOnce() + Send + 'static,
+{
+    push_timer(TimerCondVar {
+        expire,
+        task: Some(task),
+        callback: Some(Box::new(callback)),
+    })
+}
+
+/// Run `callback` at `expire` cycles in interrupt context, for drivers.
+pub fn add_oneshot_timer<F>(expire: usize, callback: F) -> TimerId
+where
+    F: FnOnce() + Send + 'static,
+{
+    push_timer(TimerCondVar {
+        expire,
+        task: None,
+        callback: Some(Box::new(callback)),
+    })
+}
+
+/// Remove a timer, return false if it has fired already.
+pub fn cancel_timer(id: TimerId) -> bool {
+    let timer = TIMERS.exclusive_access().timers.remove(&(id.0, id.1));
+    timer.is_some()
+}
+
+/// Remove all timers of an exiting task.
+pub fn cancel_task_timers(task: &Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.exclusive_access();
+    let keys: Vec<_> = timers
+        .timers
+        .iter()
+        .filter(|(_, timer)| matches!(&timer.task, Some(t) if Arc::ptr_eq(t, task)))
+        .map(|(key, _)| *key)
+        .collect();
+    let removed: Vec<_> = keys
+        .iter()
+        .filter_map(|key| timers.timers.remove(key))
+        .collect();
+    drop(timers);
+    // they may hold the last references of other objects
+    drop(removed);
+}
+
+fn check_timer() {
+    let current = get_time();
+    let mut expired = Vec::new();
     TIMERS.exclusive_session(|timers| {
-        while let Some(timer) = timers.peek() {
-            if timer.expire_ms <= current_ms {
-                wakeup_task(Arc::clone(&timer.task));
-                timers.pop();
-            } else {
+        while let Some(&key) = timers.timers.keys().next() {
+            if key.0 > current {
                 break;
             }
+            expired.push(timers.timers.remove(&key).unwrap());
         }
     });
+    // callbacks take other locks, run them without holding the timers
+    for timer in expired {
+        match timer.callback {
+            Some(callback) => callback(),
+            None => wakeup_task(timer.task.unwrap()),
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub sstatus: Sstatus,
@@ -9,6 +9,8 @@ pub struct TrapContext {
     pub kernel_satp: usize,
     pub kernel_sp: usize,
     pub trap_handler: usize,
+    /// hart the thread returned to user mode on, loaded into tp on trap
+    pub hart_id: usize,
 }
 
 impl TrapContext {
@@ -32,6 +34,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            hart_id: 0,
         };
         cx.set_sp(sp);
         cx
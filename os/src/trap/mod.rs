@@ -1,17 +1,20 @@
 mod context;
 
 use crate::config::TRAMPOLINE;
+use crate::smp::{enter_kernel, handle_ipi, hart_id, leave_kernel};
 use crate::syscall::syscall;
 use crate::task::{
-    check_signals_of_current, current_add_signal, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, exit_current_and_run_next, suspend_current_and_run_next, SignalFlags,
+    current_add_signal, current_charge_kernel_time, current_charge_user_time,
+    current_check_cpu_limit, current_preemptible, current_process_exited, current_stack_fault,
+    current_trap_cx, current_trap_cx_user_va, current_user_token, exit_current_and_run_next,
+    handle_signals, kstack_guard_hit, suspend_current_and_run_next, SignalFlags, StackFault,
 };
-use crate::timer::{check_timer, set_next_trigger};
+use crate::timer::handle_timer_interrupt;
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, sip, sscratch, sstatus, stval, stvec,
+    sie, sstatus, stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
@@ -28,7 +31,6 @@ fn set_kernel_trap_entry() {
     let __alltraps_k_va = __alltraps_k as usize - __alltraps as usize + TRAMPOLINE;
     unsafe {
         stvec::write(__alltraps_k_va, TrapMode::Direct);
-        sscratch::write(trap_from_kernel as usize);
     }
 }
 
@@ -44,6 +46,13 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// Software interrupts are used as IPIs between harts.
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
+
 fn enable_supervisor_interrupt() {
     unsafe {
         sstatus::set_sie();
@@ -59,6 +68,12 @@ fn disable_supervisor_interrupt() {
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    enter_kernel();
+    current_charge_user_time();
+    // the main thread has exited on another hart and kicked this one out of user mode
+    if current_process_exited() {
+        exit_current_and_run_next(0);
+    }
     let scause = scause::read();
     let stval = stval::read();
     // println!("into {:?}", scause.cause());
@@ -71,17 +86,37 @@ pub fn trap_handler() -> ! {
             enable_supervisor_interrupt();
 
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            // the trap context is freed if the process exited during the syscall
+            if current_process_exited() {
+                exit_current_and_run_next(0);
+            }
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::LoadPageFault) => {
+            match current_stack_fault(stval) {
+                StackFault::Grown => {}
+                StackFault::Overflow => {
+                    println!(
+                        "[kernel] Stack overflow in application, bad addr = {:#x} hits the guard page, kernel killed it.",
+                        stval
+                    );
+                    current_add_signal(SignalFlags::SIGSEGV);
+                }
+                StackFault::Other => {
+                    current_add_signal(SignalFlags::SIGSEGV);
+                }
+            }
+        }
         Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
         | Trap::Exception(Exception::InstructionPageFault)
-        | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        | Trap::Exception(Exception::LoadFault) => {
             /*
             println!(
                 "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
@@ -96,13 +131,18 @@ pub fn trap_handler() -> ! {
             current_add_signal(SignalFlags::SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            check_timer();
-            suspend_current_and_run_next();
+            let slice_expired = handle_timer_interrupt();
+            current_check_cpu_limit();
+            if slice_expired && current_preemptible() {
+                suspend_current_and_run_next();
+            }
         }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
             crate::board::irq_handler();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            handle_ipi();
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -111,20 +151,23 @@ pub fn trap_handler() -> ! {
             );
         }
     }
-    // check signals
-    if let Some((errno, msg)) = check_signals_of_current() {
-        println!("[kernel] {}", msg);
-        exit_current_and_run_next(errno);
-    }
+    handle_signals();
     trap_return();
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    if current_process_exited() {
+        exit_current_and_run_next(0);
+    }
+    current_charge_kernel_time();
     disable_supervisor_interrupt();
     set_user_trap_entry();
     let trap_cx_user_va = current_trap_cx_user_va();
     let user_satp = current_user_token();
+    // the thread may run on another hart next time
+    current_trap_cx().hart_id = hart_id();
+    leave_kernel(user_satp);
     extern "C" {
         fn __alltraps();
         fn __restore();
@@ -152,10 +195,21 @@ pub fn trap_from_kernel(_trap_cx: &TrapContext) {
             crate::board::irq_handler();
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            check_timer();
+            handle_timer_interrupt();
             // do not schedule now
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            handle_ipi();
+        }
+        Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::LoadPageFault)
+            if kstack_guard_hit(stval).is_some() =>
+        {
+            panic!(
+                "Kernel stack {} overflow, stval = {:#x} hits the guard page!",
+                kstack_guard_hit(stval).unwrap(),
+                stval
+            );
+        }
         _ => {
             panic!(
                 "Unsupported trap from kernel: {:?}, stval = {:#x}!",
@@ -59,10 +59,10 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
     let start = get_time();
     let mut v = Vec::new();
     for _ in 0..thread_count {
-        v.push(thread_create(f as usize, 0) as usize);
+        v.push(thread_create(f as usize, 0).unwrap());
     }
     for tid in v.into_iter() {
-        waittid(tid);
+        waittid(tid).unwrap();
     }
     println!("time cost is {}ms", get_time() - start);
     assert_eq!(unsafe { A }, unsafe { PER_THREAD } * thread_count);
@@ -24,13 +24,13 @@ struct Barrier {
 impl Barrier {
     pub fn new() -> Self {
         Self {
-            mutex_id: mutex_create() as usize,
-            condvar_id: condvar_create() as usize,
+            mutex_id: mutex_create().unwrap(),
+            condvar_id: condvar_create().unwrap(),
             count: UnsafeCell::new(0),
         }
     }
     pub fn block(&self) {
-        mutex_lock(self.mutex_id);
+        mutex_lock(self.mutex_id).unwrap();
         let count = self.count.get();
         // SAFETY: Here, the accesses of the count is in the
         // critical section protected by the mutex.
@@ -71,12 +71,12 @@ fn thread_fn() {
 
 #[no_mangle]
 pub fn main() -> i32 {
-    let mut v: Vec<isize> = Vec::new();
+    let mut v: Vec<usize> = Vec::new();
     for _ in 0..THREAD_NUM {
-        v.push(thread_create(thread_fn as usize, 0));
+        v.push(thread_create(thread_fn as usize, 0).unwrap());
     }
     for tid in v.into_iter() {
-        waittid(tid as usize);
+        waittid(tid).unwrap();
     }
     println!("\nOK!");
     0
@@ -21,12 +21,12 @@ fn thread_fn() {
 
 #[no_mangle]
 pub fn main() -> i32 {
-    let mut v: Vec<isize> = Vec::new();
+    let mut v: Vec<usize> = Vec::new();
     for _ in 0..THREAD_NUM {
-        v.push(thread_create(thread_fn as usize, 0));
+        v.push(thread_create(thread_fn as usize, 0).unwrap());
     }
     for tid in v.into_iter() {
-        waittid(tid as usize);
+        waittid(tid).unwrap();
     }
     println!("\nOK!");
     0
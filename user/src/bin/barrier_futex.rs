@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use user_lib::futex::{Barrier, Condvar, Mutex};
+use user_lib::{exit, get_time, thread_create, waittid};
+
+const THREAD_NUM: usize = 3;
+const ROUNDS: usize = 1000;
+
+/// The barrier of `barrier_condvar` on top of futexes.
+struct CondvarBarrier {
+    mutex: Mutex,
+    condvar: Condvar,
+    count: UnsafeCell<usize>,
+}
+
+impl CondvarBarrier {
+    const fn new() -> Self {
+        Self {
+            mutex: Mutex::new(),
+            condvar: Condvar::new(),
+            count: UnsafeCell::new(0),
+        }
+    }
+    fn block(&self) {
+        self.mutex.lock();
+        let count = self.count.get();
+        // SAFETY: the count is protected by the mutex
+        unsafe {
+            *count += 1;
+            if *count % THREAD_NUM == 0 {
+                self.condvar.notify_all();
+            } else {
+                let target = (*count + THREAD_NUM - 1) / THREAD_NUM * THREAD_NUM;
+                while *count < target {
+                    self.condvar.wait(&self.mutex);
+                }
+            }
+        }
+        self.mutex.unlock();
+    }
+}
+
+unsafe impl Sync for CondvarBarrier {}
+
+static CONDVAR_BARRIER: CondvarBarrier = CondvarBarrier::new();
+static BARRIER: Barrier = Barrier::new(THREAD_NUM as u32);
+
+fn condvar_barrier_fn() -> ! {
+    for _ in 0..ROUNDS {
+        CONDVAR_BARRIER.block();
+    }
+    exit(0)
+}
+
+fn barrier_fn() -> ! {
+    for _ in 0..ROUNDS {
+        BARRIER.wait();
+    }
+    exit(0)
+}
+
+fn run(f: fn() -> !) -> isize {
+    let start = get_time();
+    let v: Vec<usize> = (0..THREAD_NUM)
+        .map(|_| thread_create(f as usize, 0).unwrap())
+        .collect();
+    for tid in v.into_iter() {
+        waittid(tid).unwrap();
+    }
+    get_time() - start
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!(
+        "{} rounds of mutex and condvar barrier: {}ms",
+        ROUNDS,
+        run(condvar_barrier_fn)
+    );
+    println!("{} rounds of futex barrier: {}ms", ROUNDS, run(barrier_fn));
+    println!("barrier_futex passed!");
+    0
+}
@@ -14,19 +14,18 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
         println!("argv[{}] = {}", i, arg);
     }
     assert!(argc == 2);
-    let fd = open(argv[1], OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occurred when opening file");
-    }
-    let fd = fd as usize;
+    let fd = match open(argv[1], OpenFlags::RDONLY) {
+        Ok(fd) => fd,
+        Err(errno) => panic!("Error occurred when opening file: {:?}", errno),
+    };
     let mut buf = [0u8; 256];
     loop {
-        let size = read(fd, &mut buf) as usize;
+        let size = read(fd, &mut buf).unwrap();
         if size == 0 {
             break;
         }
         print!("{}", core::str::from_utf8(&buf[..size]).unwrap());
     }
-    close(fd);
+    close(fd).unwrap();
     0
 }
@@ -21,7 +21,7 @@ const MUTEX_ID: usize = 0;
 unsafe fn first() -> ! {
     sleep(10);
     println!("First work, Change A --> 1 and wakeup Second");
-    mutex_lock(MUTEX_ID);
+    mutex_lock(MUTEX_ID).unwrap();
     A = 1;
     condvar_signal(CONDVAR_ID);
     mutex_unlock(MUTEX_ID);
@@ -30,7 +30,7 @@ unsafe fn first() -> ! {
 
 unsafe fn second() -> ! {
     println!("Second want to continue,but need to wait A=1");
-    mutex_lock(MUTEX_ID);
+    mutex_lock(MUTEX_ID).unwrap();
     while A == 0 {
         println!("Second: A is {}", A);
         condvar_wait(CONDVAR_ID, MUTEX_ID);
@@ -43,16 +43,16 @@ unsafe fn second() -> ! {
 #[no_mangle]
 pub fn main() -> i32 {
     // create condvar & mutex
-    assert_eq!(condvar_create() as usize, CONDVAR_ID);
-    assert_eq!(mutex_blocking_create() as usize, MUTEX_ID);
+    assert_eq!(condvar_create().unwrap(), CONDVAR_ID);
+    assert_eq!(mutex_blocking_create().unwrap(), MUTEX_ID);
     // create threads
     let threads = vec![
-        thread_create(first as usize, 0),
-        thread_create(second as usize, 0),
+        thread_create(first as usize, 0).unwrap(),
+        thread_create(second as usize, 0).unwrap(),
     ];
     // wait for all threads to complete
     for thread in threads.iter() {
-        waittid(*thread as usize);
+        waittid(*thread).unwrap();
     }
     println!("test_condvar passed!");
     0
@@ -21,7 +21,7 @@ const MUTEX_ID: usize = 0;
 unsafe fn first() -> ! {
     sleep(10);
     println!("First work, Change A --> 1 and wakeup Second");
-    mutex_lock(MUTEX_ID);
+    mutex_lock(MUTEX_ID).unwrap();
     A = 1;
     semaphore_up(SEM_ID);
     mutex_unlock(MUTEX_ID);
@@ -31,11 +31,11 @@ unsafe fn first() -> ! {
 unsafe fn second() -> ! {
     println!("Second want to continue,but need to wait A=1");
     loop {
-        mutex_lock(MUTEX_ID);
+        mutex_lock(MUTEX_ID).unwrap();
         if A == 0 {
             println!("Second: A is {}", A);
             mutex_unlock(MUTEX_ID);
-            semaphore_down(SEM_ID);
+            semaphore_down(SEM_ID).unwrap();
         } else {
             mutex_unlock(MUTEX_ID);
             break;
@@ -48,16 +48,16 @@ unsafe fn second() -> ! {
 #[no_mangle]
 pub fn main() -> i32 {
     // create semaphore & mutex
-    assert_eq!(semaphore_create(0) as usize, SEM_ID);
-    assert_eq!(mutex_blocking_create() as usize, MUTEX_ID);
+    assert_eq!(semaphore_create(0).unwrap(), SEM_ID);
+    assert_eq!(mutex_blocking_create().unwrap(), MUTEX_ID);
     // create threads
     let threads = vec![
-        thread_create(first as usize, 0),
-        thread_create(second as usize, 0),
+        thread_create(first as usize, 0).unwrap(),
+        thread_create(second as usize, 0).unwrap(),
     ];
     // wait for all threads to complete
     for thread in threads.iter() {
-        waittid(*thread as usize);
+        waittid(*thread).unwrap();
     }
     println!("test_condvar passed!");
     0
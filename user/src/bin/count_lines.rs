@@ -12,7 +12,7 @@ pub fn main(_argc: usize, _argv: &[&str]) -> i32 {
     let mut lines = 0usize;
     let mut total_size = 0usize;
     loop {
-        let len = read(0, &mut buf) as usize;
+        let len = read(0, &mut buf).unwrap();
         if len == 0 {
             break;
         }
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{clock_gettime, clock_settime, TimeSpec, CLOCK_REALTIME};
+
+const SECS_PER_DAY: usize = 86400;
+
+/// Convert days since 1970-01-01 to (year, month, day) of the proleptic Gregorian calendar.
+/// Ref: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: usize) -> (usize, usize, usize) {
+    // shift the epoch to 0000-03-01 so that leap days end a year
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as usize;
+    (year, month, day)
+}
+
+fn usage() -> i32 {
+    println!("usage: date [-s SECONDS_SINCE_EPOCH]");
+    -1
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    match argc {
+        1 => {}
+        3 if argv[1] == "-s" => {
+            let sec = match argv[2].parse() {
+                Ok(sec) => sec,
+                Err(_) => return usage(),
+            };
+            if let Err(errno) = clock_settime(CLOCK_REALTIME, &TimeSpec { sec, nsec: 0 }) {
+                println!("date: cannot set the time: {:?}", errno);
+                return -1;
+            }
+        }
+        _ => return usage(),
+    }
+    let mut now = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut now), Ok(()));
+    let (year, month, day) = civil_from_days(now.sec / SECS_PER_DAY);
+    let secs = now.sec % SECS_PER_DAY;
+    println!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    );
+    0
+}
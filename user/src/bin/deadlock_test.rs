@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    dump_wait_for_graph, enable_deadlock_detect, exit, mutex_blocking_create, mutex_lock,
+    mutex_unlock, semaphore_create, semaphore_down, semaphore_up, sleep, thread_create, waittid,
+    Errno,
+};
+
+const MUTEX_A: usize = 0;
+const MUTEX_B: usize = 1;
+const SEM: usize = 0;
+
+static REFUSED: AtomicUsize = AtomicUsize::new(0);
+
+/// Take `first`, then `second` after `delay` ms, give up if refused.
+fn lock_both(first: usize, second: usize, delay: usize) {
+    assert_eq!(mutex_lock(first), Ok(()));
+    sleep(delay);
+    match mutex_lock(second) {
+        Ok(()) => mutex_unlock(second),
+        Err(Errno::EDEADLK) => {
+            REFUSED.fetch_add(1, Ordering::SeqCst);
+        }
+        Err(errno) => panic!("unexpected {:?}", errno),
+    }
+    mutex_unlock(first);
+}
+
+fn a_then_b() -> ! {
+    lock_both(MUTEX_A, MUTEX_B, 20);
+    exit(0)
+}
+
+fn b_then_a() -> ! {
+    lock_both(MUTEX_B, MUTEX_A, 40);
+    exit(0)
+}
+
+fn test_mutex() {
+    assert_eq!(mutex_blocking_create(), Ok(MUTEX_A));
+    assert_eq!(mutex_blocking_create(), Ok(MUTEX_B));
+    let tids = [
+        thread_create(a_then_b as usize, 0).unwrap(),
+        thread_create(b_then_a as usize, 0).unwrap(),
+    ];
+    // a_then_b waits for b_then_a now
+    sleep(30);
+    dump_wait_for_graph();
+    for tid in tids {
+        waittid(tid).unwrap();
+    }
+    // the second request closing the cycle is refused
+    assert_eq!(REFUSED.load(Ordering::SeqCst), 1);
+    println!("mutex ok");
+}
+
+fn holder() -> ! {
+    assert_eq!(semaphore_down(SEM), Ok(()));
+    sleep(20);
+    semaphore_up(SEM);
+    exit(0)
+}
+
+fn test_semaphore() {
+    assert_eq!(semaphore_create(2), Ok(SEM));
+    let tids = [
+        thread_create(holder as usize, 0).unwrap(),
+        thread_create(holder as usize, 0).unwrap(),
+    ];
+    sleep(10);
+    // both units are held by threads which will give them back
+    assert_eq!(semaphore_down(SEM), Ok(()));
+    semaphore_up(SEM);
+    for tid in tids {
+        waittid(tid).unwrap();
+    }
+    // a down no thread can ever satisfy
+    assert_eq!(semaphore_down(SEM), Ok(()));
+    assert_eq!(semaphore_down(SEM), Ok(()));
+    assert_eq!(semaphore_down(SEM), Err(Errno::EDEADLK));
+    println!("semaphore ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(enable_deadlock_detect(true), Ok(()));
+    test_mutex();
+    test_semaphore();
+    println!("deadlock_test passed!");
+    0
+}
@@ -0,0 +1,121 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::{
+    clock_gettime, close, exec, exit, open, read, sleep, thread_create, waittid, write, Errno,
+    OpenFlags, TimeSpec, CLOCK_MONOTONIC,
+};
+
+/// Below the program, never mapped.
+const BAD_ADDR: usize = 0x1000;
+const NO_SUCH_SYSCALL: usize = 9999;
+const SYSCALL_PIPE: usize = 59;
+const PAGE_SIZE: usize = 4096;
+/// Longer than the arguments may be.
+const LONG_ARG_LEN: usize = 32 * 1024;
+
+static mut LONG_ARG: [u8; LONG_ARG_LEN] = [b'a'; LONG_ARG_LEN];
+
+/// Two pages, to place values across the boundary between them.
+#[repr(C, align(4096))]
+struct Pages([u8; PAGE_SIZE * 2]);
+
+static mut PAGES: Pages = Pages([0; PAGE_SIZE * 2]);
+
+fn unknown_syscall() {
+    let ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") 0isize => ret,
+            in("x17") NO_SUCH_SYSCALL
+        );
+    }
+    assert_eq!(ret, -Errno::ENOSYS.0);
+    println!("unknown_syscall passed!");
+}
+
+fn bad_pointer() {
+    // the kernel checks the buffer instead of faulting on it
+    let buf = unsafe { core::slice::from_raw_parts(BAD_ADDR as *const u8, 16) };
+    assert_eq!(write(1, buf), Err(Errno::EFAULT));
+    // nor does it write into the code of the program
+    let code = unsafe { core::slice::from_raw_parts_mut(bad_pointer as usize as *mut u8, 16) };
+    assert_eq!(read(0, code), Err(Errno::EFAULT));
+    println!("bad_pointer passed!");
+}
+
+fn crossing_pointer() {
+    // a struct across two pages is written as a whole
+    let tp = unsafe { &mut *(PAGES.0.as_mut_ptr().add(PAGE_SIZE - 8) as *mut TimeSpec) };
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, tp), Ok(()));
+    assert!(tp.sec > 0 || tp.nsec > 0);
+    // a misaligned pair of fds is refused
+    let ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") PAGES.0.as_ptr().add(1) as isize => ret,
+            in("x11") 0,
+            in("x17") SYSCALL_PIPE
+        );
+    }
+    assert_eq!(ret, -Errno::EFAULT.0);
+    println!("crossing_pointer passed!");
+}
+
+fn bad_fd() {
+    let mut buf = [0u8; 4];
+    assert_eq!(read(99, &mut buf), Err(Errno::EBADF));
+    assert_eq!(close(99), Err(Errno::EBADF));
+    println!("bad_fd passed!");
+}
+
+fn bad_open() {
+    assert_eq!(
+        open("errno_no_such_file\0", OpenFlags::RDONLY),
+        Err(Errno::ENOENT)
+    );
+    let flags = unsafe { OpenFlags::from_bits_unchecked(1 << 20) };
+    assert_eq!(open("errno_test\0", flags), Err(Errno::EINVAL));
+    println!("bad_open passed!");
+}
+
+fn sleeping_thread() -> ! {
+    sleep(50);
+    exit(0)
+}
+
+fn bad_exec() {
+    unsafe {
+        LONG_ARG[LONG_ARG_LEN - 1] = 0;
+    }
+    let args = [
+        "errno_test\0".as_ptr(),
+        unsafe { LONG_ARG.as_ptr() },
+        core::ptr::null::<u8>(),
+    ];
+    assert_eq!(exec("errno_test\0", &args), Errno::E2BIG);
+    // only a single thread may exec
+    let tid = thread_create(sleeping_thread as usize, 0).unwrap();
+    let args = ["errno_test\0".as_ptr(), core::ptr::null::<u8>()];
+    assert_eq!(exec("errno_test\0", &args), Errno::EBUSY);
+    assert_eq!(waittid(tid), Ok(0));
+    println!("bad_exec passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    unknown_syscall();
+    bad_pointer();
+    crossing_pointer();
+    bad_fd();
+    bad_open();
+    bad_exec();
+    println!("errno_test passed!");
+    0
+}
@@ -0,0 +1,146 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, fcntl, fork, pipe, pipe2, read, waitpid, write, OpenFlags};
+use user_lib::{socketpair, Errno, AF_UNIX, SOCK_DGRAM, SOCK_STREAM};
+use user_lib::{FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL};
+
+/// Where the fds checked after `exec` are placed.
+const KEPT_FD: usize = 20;
+const CLOEXEC_FD: usize = 21;
+
+fn nonblock_pipe() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::NONBLOCK), Ok(()));
+    let mut buf = [0u8; 64];
+    assert_eq!(read(fds[0], &mut buf), Err(Errno::EAGAIN));
+    // the write stops where the pipe is full
+    let written = write(fds[1], &buf).unwrap();
+    assert!(written > 0 && written < buf.len());
+    assert_eq!(write(fds[1], &buf), Err(Errno::EAGAIN));
+    assert_eq!(read(fds[0], &mut buf), Ok(written));
+    assert_eq!(read(fds[0], &mut buf), Err(Errno::EAGAIN));
+    // a closed write end is not a reason to wait
+    close(fds[1]).unwrap();
+    assert_eq!(read(fds[0], &mut buf), Ok(0));
+    close(fds[0]).unwrap();
+    println!("nonblock_pipe passed!");
+}
+
+fn set_flags() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), Ok(()));
+    assert_eq!(
+        fcntl(fds[0], F_GETFL, 0),
+        Ok(OpenFlags::RDONLY.bits() as usize)
+    );
+    assert_eq!(
+        fcntl(fds[1], F_GETFL, 0),
+        Ok(OpenFlags::WRONLY.bits() as usize)
+    );
+    assert_eq!(
+        fcntl(fds[0], F_SETFL, OpenFlags::NONBLOCK.bits() as usize),
+        Ok(0)
+    );
+    assert_eq!(
+        fcntl(fds[0], F_GETFL, 0),
+        Ok(OpenFlags::NONBLOCK.bits() as usize)
+    );
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fds[0], &mut buf), Err(Errno::EAGAIN));
+    // a duplicate shares the open file and so its flags
+    let new_fd = fcntl(fds[0], F_DUPFD, 10).unwrap();
+    assert!(new_fd >= 10);
+    assert_eq!(
+        fcntl(new_fd, F_GETFL, 0),
+        Ok(OpenFlags::NONBLOCK.bits() as usize)
+    );
+    assert_eq!(fcntl(fds[0], F_SETFL, 0), Ok(0));
+    assert_eq!(fcntl(new_fd, F_GETFL, 0), Ok(0));
+    assert_eq!(fcntl(fds[0], 1000, 0), Err(Errno::EINVAL));
+    close(new_fd).unwrap();
+    assert_eq!(fcntl(new_fd, F_GETFL, 0), Err(Errno::EBADF));
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+    println!("set_flags passed!");
+}
+
+fn nonblock_socket() {
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), Ok(()));
+    assert_eq!(
+        fcntl(sv[0], F_SETFL, OpenFlags::NONBLOCK.bits() as usize),
+        Ok(0)
+    );
+    let mut buf = [0u8; 4];
+    assert_eq!(read(sv[0], &mut buf), Err(Errno::EAGAIN));
+    assert_eq!(write(sv[1], b"ping"), Ok(4));
+    assert_eq!(read(sv[0], &mut buf), Ok(4));
+    assert_eq!(&buf, b"ping");
+    close(sv[0]).unwrap();
+    close(sv[1]).unwrap();
+    assert_eq!(socketpair(AF_UNIX, SOCK_DGRAM, &mut sv), Ok(()));
+    assert_eq!(
+        fcntl(sv[1], F_SETFL, OpenFlags::NONBLOCK.bits() as usize),
+        Ok(0)
+    );
+    assert_eq!(read(sv[1], &mut buf), Err(Errno::EAGAIN));
+    close(sv[0]).unwrap();
+    close(sv[1]).unwrap();
+    println!("nonblock_socket passed!");
+}
+
+fn close_on_exec() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::CLOEXEC), Ok(()));
+    assert_eq!(fcntl(fds[0], F_GETFD, 0), Ok(FD_CLOEXEC));
+    assert_eq!(fcntl(fds[0], F_SETFD, 0), Ok(0));
+    assert_eq!(fcntl(fds[0], F_GETFD, 0), Ok(0));
+    // the flag belongs to the fd, not to the file
+    assert_eq!(fcntl(fds[0], F_DUPFD, KEPT_FD), Ok(KEPT_FD));
+    assert_eq!(fcntl(fds[0], F_DUPFD_CLOEXEC, KEPT_FD), Ok(CLOEXEC_FD));
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), Ok(0));
+    assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), Ok(FD_CLOEXEC));
+    let pid = fork().unwrap();
+    if pid == 0 {
+        // the flags are inherited on fork
+        assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), Ok(FD_CLOEXEC));
+        let args = [
+            "fcntl_test\0".as_ptr(),
+            "exec\0".as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        exec("fcntl_test\0", &args);
+        panic!("unreachable!");
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    for fd in [fds[0], fds[1], KEPT_FD, CLOEXEC_FD] {
+        close(fd).unwrap();
+    }
+    println!("close_on_exec passed!");
+}
+
+/// Run after `exec` by `close_on_exec`.
+fn after_exec() -> i32 {
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), Ok(0));
+    assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), Err(Errno::EBADF));
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc == 2 && argv[1] == "exec" {
+        return after_exec();
+    }
+    nonblock_pipe();
+    set_flags();
+    nonblock_socket();
+    close_on_exec();
+    println!("fcntl_test passed!");
+    0
+}
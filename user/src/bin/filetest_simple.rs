@@ -10,18 +10,16 @@ use user_lib::{close, open, read, write, OpenFlags};
 pub fn main() -> i32 {
     let test_str = "Hello, world!";
     let filea = "filea\0";
-    let fd = open(filea, OpenFlags::CREATE | OpenFlags::WRONLY);
+    let fd = open(filea, OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
     assert!(fd > 0);
-    let fd = fd as usize;
-    write(fd, test_str.as_bytes());
-    close(fd);
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
 
-    let fd = open(filea, OpenFlags::RDONLY);
+    let fd = open(filea, OpenFlags::RDONLY).unwrap();
     assert!(fd > 0);
-    let fd = fd as usize;
     let mut buffer = [0u8; 100];
-    let read_len = read(fd, &mut buffer) as usize;
-    close(fd);
+    let read_len = read(fd, &mut buffer).unwrap();
+    close(fd).unwrap();
 
     assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap(),);
     println!("file_test passed!");
@@ -16,7 +16,7 @@ fn fork_child(cur: &str, branch: char) {
     }
     next[..l].copy_from_slice(cur.as_bytes());
     next[l] = branch as u8;
-    if fork() == 0 {
+    if fork().unwrap() == 0 {
         fork_tree(core::str::from_utf8(&next[..l + 1]).unwrap());
         yield_();
         exit(0);
@@ -29,7 +29,7 @@ fn fork_tree(cur: &str) {
     fork_child(cur, '1');
     let mut exit_code: i32 = 0;
     for _ in 0..2 {
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
 }
 
@@ -38,7 +38,7 @@ pub fn main() -> i32 {
     fork_tree("");
     let mut exit_code: i32 = 0;
     for _ in 0..2 {
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
     sleep(3000);
     0
@@ -0,0 +1,160 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use user_lib::futex::{futex_wait, futex_wake, Condvar, Mutex, Once, OnceCell, RwLock};
+use user_lib::{exit, get_time, sleep, thread_create, waittid, Errno, TimeSpec};
+
+static WORD: AtomicU32 = AtomicU32::new(0);
+
+fn waker() -> ! {
+    sleep(10);
+    WORD.store(1, Ordering::SeqCst);
+    futex_wake(&WORD, 1).unwrap();
+    exit(0)
+}
+
+fn test_wait_wake() {
+    // the word does not hold the expected value
+    assert_eq!(futex_wait(&WORD, 1, None), Err(Errno::EAGAIN));
+    let start = get_time();
+    assert_eq!(
+        futex_wait(&WORD, 0, Some(&TimeSpec::from_us(20_000))),
+        Err(Errno::ETIMEDOUT)
+    );
+    assert!(get_time() - start >= 20);
+    let tid = thread_create(waker as usize, 0).unwrap();
+    while WORD.load(Ordering::SeqCst) == 0 {
+        futex_wait(&WORD, 0, None).unwrap();
+    }
+    waittid(tid).unwrap();
+    // no waiters
+    assert_eq!(futex_wake(&WORD, 1), Ok(0));
+    println!("wait/wake ok");
+}
+
+const THREADS: usize = 4;
+
+static MUTEX: Mutex = Mutex::new();
+static CONDVAR: Condvar = Condvar::new();
+static READY: AtomicUsize = AtomicUsize::new(0);
+static WOKEN: AtomicUsize = AtomicUsize::new(0);
+static mut GO: bool = false;
+
+fn waiter() -> ! {
+    MUTEX.lock();
+    READY.fetch_add(1, Ordering::SeqCst);
+    while unsafe { !GO } {
+        CONDVAR.wait(&MUTEX);
+    }
+    WOKEN.fetch_add(1, Ordering::SeqCst);
+    MUTEX.unlock();
+    exit(0)
+}
+
+fn test_condvar() {
+    MUTEX.lock();
+    assert!(!CONDVAR.wait_timeout(&MUTEX, Some(&TimeSpec::from_us(10_000))));
+    MUTEX.unlock();
+    let mut tids = [0; THREADS];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(waiter as usize, 0).unwrap();
+    }
+    while READY.load(Ordering::SeqCst) < THREADS {
+        sleep(1);
+    }
+    MUTEX.lock();
+    unsafe {
+        GO = true;
+    }
+    // the waiters are moved to the mutex and woken up one by one
+    CONDVAR.notify_all();
+    MUTEX.unlock();
+    for tid in tids {
+        waittid(tid).unwrap();
+    }
+    assert_eq!(WOKEN.load(Ordering::SeqCst), THREADS);
+    println!("condvar ok");
+}
+
+static RWLOCK: RwLock = RwLock::new();
+static mut SHARED: [usize; 2] = [0; 2];
+const WRITES: usize = 1000;
+
+fn writer() -> ! {
+    for _ in 0..WRITES {
+        RWLOCK.write();
+        unsafe {
+            SHARED[0] += 1;
+            SHARED[1] += 1;
+        }
+        RWLOCK.write_unlock();
+    }
+    exit(0)
+}
+
+fn reader() -> ! {
+    for _ in 0..WRITES {
+        RWLOCK.read();
+        // writers update both halves together
+        unsafe {
+            assert_eq!(SHARED[0], SHARED[1]);
+        }
+        RWLOCK.read_unlock();
+    }
+    exit(0)
+}
+
+fn test_rwlock() {
+    let tids = [
+        thread_create(writer as usize, 0).unwrap(),
+        thread_create(reader as usize, 0).unwrap(),
+        thread_create(writer as usize, 0).unwrap(),
+        thread_create(reader as usize, 0).unwrap(),
+    ];
+    for tid in tids {
+        waittid(tid).unwrap();
+    }
+    assert_eq!(unsafe { SHARED[0] }, 2 * WRITES);
+    println!("rwlock ok");
+}
+
+static ONCE: Once = Once::new();
+static INIT_COUNT: AtomicUsize = AtomicUsize::new(0);
+static CELL: OnceCell<usize> = OnceCell::new();
+
+fn init() -> ! {
+    ONCE.call_once(|| {
+        sleep(10);
+        INIT_COUNT.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(INIT_COUNT.load(Ordering::SeqCst), 1);
+    assert_eq!(*CELL.get_or_init(|| 42), 42);
+    exit(0)
+}
+
+fn test_once() {
+    let mut tids = [0; THREADS];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(init as usize, 0).unwrap();
+    }
+    for tid in tids {
+        waittid(tid).unwrap();
+    }
+    assert!(ONCE.is_completed());
+    assert_eq!(CELL.get(), Some(&42));
+    println!("once ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_wait_wake();
+    test_condvar();
+    test_rwlock();
+    test_once();
+    println!("futex_test passed!");
+    0
+}
@@ -5,7 +5,9 @@ extern crate alloc;
 extern crate user_lib;
 
 use user_lib::console::getchar;
-use user_lib::{key_pressed, sleep, Display, VIRTGPU_XRES, VIRTGPU_YRES};
+use user_lib::{
+    poll, tcgetattr, tcsetattr, Display, PollFd, Termios, POLLIN, VIRTGPU_XRES, VIRTGPU_YRES,
+};
 
 use embedded_graphics::pixelcolor::*;
 use embedded_graphics::prelude::{Drawable, Point, RgbColor, Size};
@@ -329,9 +331,17 @@ const CR: u8 = 0x0du8;
 pub fn main() -> i32 {
     let mut disp = Display::new(Size::new(VIRTGPU_XRES, VIRTGPU_YRES));
     let mut game = SnakeGame::<20, Rgb888>::new(1280, 800, 20, 20, Rgb888::RED, Rgb888::YELLOW, 50);
+    // keys are handled one by one without echo
+    let mut termios = Termios::default();
+    tcgetattr(0, &mut termios).unwrap();
+    let saved_termios = termios;
+    termios.make_raw();
+    tcsetattr(0, &termios).unwrap();
     let _ = disp.clear(Rgb888::BLACK).unwrap();
     loop {
-        if key_pressed() {
+        // a frame lasts until a key comes or 10ms pass
+        let mut fds = [PollFd::new(0, POLLIN)];
+        if poll(&mut fds, 10) > 0 {
             let c = getchar();
             match c {
                 LF => break,
@@ -345,7 +355,7 @@ pub fn main() -> i32 {
         }
         let _ = disp.clear(Rgb888::BLACK).unwrap();
         game.draw(&mut disp);
-        sleep(10);
     }
+    tcsetattr(0, &saved_termios).unwrap();
     0
 }
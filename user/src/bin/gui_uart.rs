@@ -6,7 +6,7 @@ extern crate user_lib;
 extern crate alloc;
 
 use user_lib::console::getchar;
-use user_lib::{framebuffer, framebuffer_flush};
+use user_lib::{framebuffer, framebuffer_flush, tcgetattr, tcsetattr, Termios};
 
 use embedded_graphics::pixelcolor::Rgb888;
 use embedded_graphics::prelude::{Drawable, Point, RgbColor, Size};
@@ -31,7 +31,7 @@ pub struct Display {
 
 impl Display {
     pub fn new(size: Size, point: Point) -> Self {
-        let fb_ptr = framebuffer() as *mut u8;
+        let fb_ptr = framebuffer().unwrap() as *mut u8;
         println!(
             "Hello world from user mode program! 0x{:X} , len {}",
             fb_ptr as usize, VIRTGPU_LEN
@@ -111,6 +111,12 @@ const CR: u8 = 0x0du8;
 pub fn main() -> i32 {
     // let fb_ptr = framebuffer() as *mut u8;
     let mut board = DrawingBoard::new();
+    // keys are handled one by one without echo
+    let mut termios = Termios::default();
+    tcgetattr(0, &mut termios).unwrap();
+    let saved_termios = termios;
+    termios.make_raw();
+    tcsetattr(0, &termios).unwrap();
     let _ = board.disp.clear(Rgb888::BLACK).unwrap();
     for i in 0..20 {
         let c = getchar();
@@ -121,5 +127,6 @@ pub fn main() -> i32 {
         board.latest_pos.y += i;
         board.paint();
     }
+    tcsetattr(0, &saved_termios).unwrap();
     0
 }
@@ -12,17 +12,13 @@ pub fn main() -> i32 {
     for (i, ch) in buffer.iter_mut().enumerate() {
         *ch = i as u8;
     }
-    let f = open("testf\0", OpenFlags::CREATE | OpenFlags::WRONLY);
-    if f < 0 {
-        panic!("Open test file failed!");
-    }
-    let f = f as usize;
+    let f = open("testf\0", OpenFlags::CREATE | OpenFlags::WRONLY).expect("Open test file failed!");
     let start = get_time();
     let size_mb = 1usize;
     for _ in 0..1024 * size_mb {
-        write(f, &buffer);
+        write(f, &buffer).unwrap();
     }
-    close(f);
+    close(f).unwrap();
     let time_ms = (get_time() - start) as usize;
     let speed_kbs = (size_mb << 20) / time_ms;
     println!(
@@ -15,15 +15,12 @@ fn worker(size_kib: usize) {
         *ch = i as u8;
     }
     let filename = format(format_args!("testf{}\0", gettid()));
-    let f = open(filename.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY);
-    if f < 0 {
-        panic!("Open test file failed!");
-    }
-    let f = f as usize;
+    let f = open(filename.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY)
+        .expect("Open test file failed!");
     for _ in 0..size_kib {
-        write(f, &buffer);
+        write(f, &buffer).unwrap();
     }
-    close(f);
+    close(f).unwrap();
     exit(0)
 }
 
@@ -40,10 +37,10 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
     let mut v = Vec::new();
     let size_mb = 1usize;
     for _ in 0..workers {
-        v.push(thread_create(worker as usize, size_kb / workers));
+        v.push(thread_create(worker as usize, size_kb / workers).unwrap());
     }
     for tid in v.iter() {
-        assert_eq!(0, waittid(*tid as usize));
+        assert_eq!(Ok(0), waittid(*tid));
     }
 
     let time_ms = (get_time() - start) as usize;
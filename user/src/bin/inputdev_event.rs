@@ -1,7 +1,9 @@
 #![no_std]
 #![no_main]
 
-use user_lib::{event_get, DecodeType, Key, KeyType};
+use user_lib::{
+    close, event_open, poll, read, DecodeType, InputEvent, Key, KeyType, PollFd, POLLIN,
+};
 
 #[macro_use]
 extern crate user_lib;
@@ -9,17 +11,25 @@ extern crate user_lib;
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Input device event test");
-    loop {
-        if let Some(event) = event_get() {
+    let fd = event_open().unwrap();
+    let mut events = [0u8; 8 * 16];
+    'outer: loop {
+        // sleep until some events come instead of asking again and again
+        let mut fds = [PollFd::new(fd, POLLIN)];
+        assert_eq!(poll(&mut fds, -1), Ok(1));
+        let len = read(fd, &mut events).unwrap();
+        for raw in events[..len].chunks_exact(8) {
+            let event: InputEvent = u64::from_le_bytes(raw.try_into().unwrap()).into();
             if let Some(decoder_type) = event.decode() {
                 println!("{:?}", decoder_type);
                 if let DecodeType::Key(key, keytype) = decoder_type {
                     if key == Key::Enter && keytype == KeyType::Press {
-                        break;
+                        break 'outer;
                     }
                 }
             }
         }
     }
+    close(fd).unwrap();
     0
 }
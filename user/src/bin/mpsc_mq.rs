@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::println_empty_string)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, mq_getattr, mq_open, mq_receive, mq_send, mq_unlink, waitpid};
+use user_lib::{Errno, MqAttr, OpenFlags};
+
+const QUEUE_NAME: &str = "/mpsc_mq\0";
+const BUFFER_SIZE: usize = 8;
+const MSG_SIZE: usize = 2;
+const PRODUCER_COUNT: usize = 4;
+const NUMBER_PER_PRODUCER: usize = 100;
+
+fn producer(id: usize) -> ! {
+    let fd = mq_open(QUEUE_NAME, OpenFlags::WRONLY, None).unwrap();
+    for seq in 0..NUMBER_PER_PRODUCER {
+        // blocks while the queue is full
+        assert_eq!(mq_send(fd, &[id as u8, seq as u8], 0), Ok(()));
+    }
+    close(fd).unwrap();
+    exit(0)
+}
+
+fn consumer(fd: usize) {
+    let mut next_seq = [0u8; PRODUCER_COUNT];
+    let mut msg = [0u8; MSG_SIZE];
+    for _ in 0..PRODUCER_COUNT * NUMBER_PER_PRODUCER {
+        assert_eq!(mq_receive(fd, &mut msg, None), Ok(MSG_SIZE));
+        let (id, seq) = (msg[0] as usize, msg[1]);
+        // messages of the same priority keep their order
+        assert_eq!(seq, next_seq[id]);
+        next_seq[id] += 1;
+        print!("{} ", id);
+    }
+    println!("");
+}
+
+fn priorities() {
+    let fd = mq_open(QUEUE_NAME, OpenFlags::RDWR | OpenFlags::NONBLOCK, None).unwrap();
+    let mut msg = [0u8; MSG_SIZE];
+    assert_eq!(mq_receive(fd, &mut msg, None), Err(Errno::EAGAIN));
+    for prio in [1, 5, 3, 5, 0, 2, 4, 1] {
+        assert_eq!(mq_send(fd, &[prio as u8], prio), Ok(()));
+    }
+    // bounded
+    assert_eq!(mq_send(fd, &[0], 0), Err(Errno::EAGAIN));
+    let mut attr = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut attr), Ok(()));
+    assert_eq!(attr.cur_msgs, BUFFER_SIZE);
+    // too long
+    assert_eq!(
+        mq_receive(fd, &mut [0u8; MSG_SIZE - 1], None),
+        Err(Errno::EMSGSIZE)
+    );
+    for expected in [5, 5, 4, 3, 2, 1, 1, 0] {
+        let mut prio = 0;
+        assert_eq!(mq_receive(fd, &mut msg, Some(&mut prio)), Ok(1));
+        assert_eq!(prio, expected);
+        assert_eq!(msg[0] as usize, expected);
+    }
+    assert_eq!(mq_receive(fd, &mut msg, None), Err(Errno::EAGAIN));
+    close(fd).unwrap();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let attr = MqAttr::new(BUFFER_SIZE, MSG_SIZE);
+    let fd = mq_open(
+        QUEUE_NAME,
+        OpenFlags::RDONLY | OpenFlags::CREATE,
+        Some(&attr),
+    )
+    .unwrap();
+    let mut pids = [0; PRODUCER_COUNT];
+    for (id, pid) in pids.iter_mut().enumerate() {
+        *pid = fork().unwrap();
+        if *pid == 0 {
+            producer(id);
+        }
+    }
+    consumer(fd);
+    let mut exit_code = 0;
+    for pid in pids {
+        assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+        assert_eq!(exit_code, 0);
+    }
+    close(fd).unwrap();
+    priorities();
+    assert_eq!(mq_unlink(QUEUE_NAME), Ok(()));
+    assert_eq!(
+        mq_open(QUEUE_NAME, OpenFlags::RDONLY, None),
+        Err(Errno::ENOENT)
+    );
+    println!("mpsc_mq passed!");
+    0
+}
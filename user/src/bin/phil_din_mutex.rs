@@ -47,8 +47,8 @@ fn philosopher_dining_problem(id: *const usize) {
             THINK[id][2 * round + 1] = get_time_u();
         }
         // wait for forks
-        mutex_lock(min);
-        mutex_lock(max);
+        mutex_lock(min).unwrap();
+        mutex_lock(max).unwrap();
         // eating
         unsafe {
             EAT[id][2 * round] = get_time_u();
@@ -69,14 +69,17 @@ pub fn main() -> i32 {
     let ids: Vec<_> = (0..N).collect();
     let start = get_time_u();
     for i in 0..N {
-        assert_eq!(mutex_blocking_create(), i as isize);
-        v.push(thread_create(
-            philosopher_dining_problem as usize,
-            &ids.as_slice()[i] as *const _ as usize,
-        ));
+        assert_eq!(mutex_blocking_create(), Ok(i));
+        v.push(
+            thread_create(
+                philosopher_dining_problem as usize,
+                &ids.as_slice()[i] as *const _ as usize,
+            )
+            .unwrap(),
+        );
     }
     for tid in v.iter() {
-        waittid(*tid as usize);
+        waittid(*tid).unwrap();
     }
     let time_cost = get_time_u() - start;
     println!("time cost = {}", time_cost);
@@ -0,0 +1,132 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, sleep, waitpid, write};
+use user_lib::{epoll_create, epoll_ctl, epoll_wait, EpollEvent, EPOLLIN, EPOLL_CTL_ADD};
+use user_lib::{mq_open, mq_receive, mq_send, mq_unlink, socketpair, Errno, MqAttr, OpenFlags};
+use user_lib::{poll, select, FdSet, PollFd, AF_UNIX, POLLHUP, POLLIN, POLLOUT, SOCK_STREAM};
+
+const QUEUE_NAME: &str = "/poll_test\0";
+
+/// Write `data` to `fd` after a while in a child, return its pid.
+fn write_later(fd: usize, data: &[u8]) -> usize {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        sleep(20);
+        assert_eq!(write(fd, data), Ok(data.len()));
+        exit(0);
+    }
+    pid
+}
+
+fn wait_child(pid: usize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+}
+
+fn poll_pipe() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), Ok(()));
+    let mut buf = [0u8; 4];
+    // nothing to read before the timeout
+    let mut pollfds = [PollFd::new(fds[0], POLLIN), PollFd::new(fds[1], POLLOUT)];
+    assert_eq!(poll(&mut pollfds[..1], 10), Ok(0));
+    assert_eq!(pollfds[0].revents, 0);
+    assert_eq!(poll(&mut pollfds, 0), Ok(1));
+    assert_eq!(pollfds[1].revents, POLLOUT);
+    // woken up by the write of another process
+    let pid = write_later(fds[1], b"x");
+    assert_eq!(poll(&mut pollfds[..1], -1), Ok(1));
+    assert_eq!(pollfds[0].revents, POLLIN);
+    assert_eq!(read(fds[0], &mut buf), Ok(1));
+    wait_child(pid);
+    // the write end goes away
+    close(fds[1]).unwrap();
+    assert_eq!(poll(&mut pollfds[..1], -1), Ok(1));
+    assert_eq!(pollfds[0].revents, POLLHUP);
+    assert_eq!(read(fds[0], &mut buf), Ok(0));
+    close(fds[0]).unwrap();
+    println!("poll_pipe passed!");
+}
+
+fn select_pipe() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), Ok(()));
+    let nfds = fds[0].max(fds[1]) + 1;
+    let (mut readfds, mut writefds) = (FdSet::new(), FdSet::new());
+    readfds.set(fds[0]);
+    writefds.set(fds[1]);
+    assert_eq!(
+        select(nfds, Some(&mut readfds), Some(&mut writefds), None, 0),
+        Ok(1)
+    );
+    assert!(!readfds.is_set(fds[0]));
+    assert!(writefds.is_set(fds[1]));
+    assert_eq!(write(fds[1], b"x"), Ok(1));
+    readfds.set(fds[0]);
+    assert_eq!(select(nfds, Some(&mut readfds), None, None, -1), Ok(1));
+    assert!(readfds.is_set(fds[0]));
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+    println!("select_pipe passed!");
+}
+
+fn epoll_files() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), Ok(()));
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), Ok(()));
+    let mq = mq_open(
+        QUEUE_NAME,
+        OpenFlags::CREATE | OpenFlags::RDWR,
+        Some(&MqAttr::new(4, 8)),
+    )
+    .unwrap();
+    let epfd = epoll_create().unwrap();
+    for (data, fd) in [fds[0], sv[1], mq].into_iter().enumerate() {
+        let event = EpollEvent::new(EPOLLIN, data as u64);
+        assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fd, Some(&event)), Ok(()));
+    }
+    // added only once
+    let event = EpollEvent::new(EPOLLIN, 0);
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, fds[0], Some(&event)),
+        Err(Errno::EEXIST)
+    );
+    let mut events = [EpollEvent::default(); 4];
+    assert_eq!(epoll_wait(epfd, &mut events, 10), Ok(0));
+    // woken up by the socket
+    let pid = write_later(sv[0], b"ping");
+    assert_eq!(epoll_wait(epfd, &mut events, -1), Ok(1));
+    assert_eq!((events[0].events, events[0].data), (EPOLLIN, 1));
+    let mut buf = [0u8; 8];
+    assert_eq!(read(sv[1], &mut buf), Ok(4));
+    wait_child(pid);
+    // the pipe and the queue at once
+    assert_eq!(write(fds[1], b"x"), Ok(1));
+    assert_eq!(mq_send(mq, b"msg", 0), Ok(()));
+    assert_eq!(epoll_wait(epfd, &mut events, -1), Ok(2));
+    assert_eq!((events[0].data, events[1].data), (0, 2));
+    assert_eq!(mq_receive(mq, &mut buf, None), Ok(3));
+    // a closed file leaves the interest list
+    close(fds[0]).unwrap();
+    assert_eq!(epoll_wait(epfd, &mut events, 10), Ok(0));
+    for fd in [epfd, mq, fds[1], sv[0], sv[1]] {
+        close(fd).unwrap();
+    }
+    assert_eq!(mq_unlink(QUEUE_NAME), Ok(()));
+    println!("epoll_files passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    poll_pipe();
+    select_pipe();
+    epoll_files();
+    println!("poll_test passed!");
+    0
+}
@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, get_time, mutex_blocking_create, mutex_lock, mutex_pi_create, mutex_unlock,
+    sched_setscheduler, sleep, thread_create, waittid, SCHED_FIFO, SCHED_RR,
+};
+
+/// enough to keep every hart busy
+const MEDIUM_THREADS: usize = 4;
+const MEDIUM_MS: isize = 300;
+const LOW_MS: isize = 30;
+
+static MUTEX_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn busy_until(deadline: isize) {
+    while get_time() < deadline {}
+}
+
+fn low() -> ! {
+    let mutex_id = MUTEX_ID.load(Ordering::Acquire);
+    mutex_lock(mutex_id).unwrap();
+    busy_until(get_time() + LOW_MS);
+    mutex_unlock(mutex_id);
+    exit(0)
+}
+
+fn medium(deadline: usize) -> ! {
+    busy_until(deadline as isize);
+    exit(0)
+}
+
+fn high() -> ! {
+    let mutex_id = MUTEX_ID.load(Ordering::Acquire);
+    let start = get_time();
+    mutex_lock(mutex_id).unwrap();
+    let waited = get_time() - start;
+    mutex_unlock(mutex_id);
+    exit(waited as i32)
+}
+
+fn spawn(entry: usize, arg: usize, priority: usize) -> usize {
+    let tid = thread_create(entry, arg).unwrap();
+    assert_eq!(sched_setscheduler(tid, SCHED_RR, priority), Ok(()));
+    tid
+}
+
+/// A low priority thread holds the mutex while medium priority threads keep
+/// every hart busy, return how long a high priority thread waits for the mutex.
+fn inversion(mutex_id: usize) -> isize {
+    MUTEX_ID.store(mutex_id, Ordering::Release);
+    let low_tid = spawn(low as usize, 0, 10);
+    // let it take the mutex
+    sleep(5);
+    let deadline = get_time() + MEDIUM_MS;
+    let medium_tids = [0; MEDIUM_THREADS].map(|_| spawn(medium as usize, deadline as usize, 50));
+    let high_tid = spawn(high as usize, 0, 90);
+    let waited = waittid(high_tid).unwrap() as isize;
+    assert_eq!(waittid(low_tid), Ok(0));
+    for tid in medium_tids {
+        assert_eq!(waittid(tid), Ok(0));
+    }
+    waited
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // stay ahead of the threads created
+    assert_eq!(sched_setscheduler(0, SCHED_FIFO, 99), Ok(()));
+    let waited = inversion(mutex_blocking_create().unwrap());
+    println!("without priority inheritance: waited {}ms", waited);
+    assert!(waited >= MEDIUM_MS / 2);
+    let waited = inversion(mutex_pi_create().unwrap());
+    println!("with priority inheritance: waited {}ms", waited);
+    assert!(waited < MEDIUM_MS / 2);
+    println!("priority_inversion passed!");
+    0
+}
@@ -44,11 +44,11 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
     let start = get_time();
     let mut v = Vec::new();
     for _ in 0..THREAD_COUNT {
-        v.push(thread_create(f as usize, count) as usize);
+        v.push(thread_create(f as usize, count).unwrap());
     }
     let mut time_cost = Vec::new();
     for tid in v.iter() {
-        time_cost.push(waittid(*tid));
+        time_cost.push(waittid(*tid).unwrap());
     }
     println!("time cost is {}ms", get_time() - start);
     assert_eq!(unsafe { A }, PER_THREAD * THREAD_COUNT);
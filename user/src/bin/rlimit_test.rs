@@ -0,0 +1,155 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup, exit, fork, get_time, getrlimit, getrusage, sched_setscheduler, setrlimit,
+    thread_create, times, waitpid, waittid, Errno, RLimit, RUsage, Tms, CLK_TCK, RLIMIT_CPU,
+    RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_NTHREAD, RUSAGE_CHILDREN, RUSAGE_SELF, SCHED_FIFO, SIGXCPU,
+};
+
+const NOFILE: usize = 8;
+const NPROC: usize = 3;
+
+fn test_nofile() {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut limit), Ok(()));
+    // the hard limit cannot be raised
+    let raised = RLimit {
+        cur: limit.max,
+        max: limit.max + 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &raised), Err(Errno::EPERM));
+    let lowered = RLimit {
+        cur: NOFILE,
+        max: limit.max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &lowered), Ok(()));
+    // 0, 1 and 2 are open already
+    for fd in 3..NOFILE {
+        assert_eq!(dup(1), Ok(fd));
+    }
+    assert_eq!(dup(1), Err(Errno::EMFILE));
+    for fd in 3..NOFILE {
+        close(fd).unwrap();
+    }
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &limit), Ok(()));
+    println!("nofile ok");
+}
+
+fn idle_thread() -> ! {
+    exit(0)
+}
+
+fn test_nthread() {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NTHREAD, &mut limit), Ok(()));
+    let lowered = RLimit {
+        cur: 2,
+        max: limit.max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NTHREAD, &lowered), Ok(()));
+    let tid = thread_create(idle_thread as usize, 0).unwrap();
+    assert!(tid > 0);
+    assert_eq!(thread_create(idle_thread as usize, 0), Err(Errno::EAGAIN));
+    assert_eq!(waittid(tid), Ok(0));
+    // the tid is free again after the thread is reaped
+    let tid = thread_create(idle_thread as usize, 0).unwrap();
+    assert!(tid > 0);
+    assert_eq!(waittid(tid), Ok(0));
+    assert_eq!(setrlimit(RLIMIT_NTHREAD, &limit), Ok(()));
+    println!("nthread ok");
+}
+
+fn test_nproc() {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NPROC, &mut limit), Ok(()));
+    let lowered = RLimit {
+        cur: NPROC,
+        max: limit.max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NPROC, &lowered), Ok(()));
+    let mut pids = [0; NPROC];
+    for pid in pids.iter_mut() {
+        *pid = fork().unwrap();
+        if *pid == 0 {
+            exit(0);
+        }
+    }
+    // unreaped children still count
+    assert_eq!(fork(), Err(Errno::EAGAIN));
+    let mut exit_code = 0;
+    for pid in pids {
+        assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    }
+    assert_eq!(setrlimit(RLIMIT_NPROC, &limit), Ok(()));
+    println!("nproc ok");
+}
+
+fn test_cpu() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        let limit = RLimit { cur: 1, max: 2 };
+        assert_eq!(setrlimit(RLIMIT_CPU, &limit), Ok(()));
+        loop {}
+    }
+    let start = get_time();
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, -SIGXCPU);
+    println!("cpu limit hit after {}ms", get_time() - start);
+    let mut usage = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut usage), Ok(()));
+    assert!(usage.ru_utime.as_ms() + usage.ru_stime.as_ms() >= 1000);
+    println!("cpu ok");
+}
+
+fn test_rttime() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        // a busy realtime thread is never preempted, the default limit stops it
+        assert_eq!(sched_setscheduler(0, SCHED_FIFO, 99), Ok(()));
+        loop {}
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, -SIGXCPU);
+    println!("rttime ok");
+}
+
+fn test_times() {
+    let mut before = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut before), Ok(()));
+    let deadline = get_time() + 100;
+    while get_time() < deadline {}
+    let mut after = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut after), Ok(()));
+    let spent = after.ru_utime.as_ms() + after.ru_stime.as_ms()
+        - before.ru_utime.as_ms()
+        - before.ru_stime.as_ms();
+    println!("spinning 100ms took {}ms of cpu", spent);
+    let mut tms = Tms::default();
+    let elapsed = times(&mut tms).unwrap();
+    assert!(elapsed > 0);
+    // the child of test_cpu has been reaped
+    assert!(tms.tms_cutime + tms.tms_cstime + 1 >= CLK_TCK);
+    println!(
+        "times: up {} ticks, user {}, system {}",
+        elapsed, tms.tms_utime, tms.tms_stime
+    );
+    println!("times ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_nofile();
+    test_nthread();
+    test_nproc();
+    test_cpu();
+    test_rttime();
+    test_times();
+    println!("rlimit_test passed!");
+    0
+}
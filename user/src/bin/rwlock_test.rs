@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, rwlock_create, rwlock_read, rwlock_unlock, rwlock_write, sleep, thread_create, waittid,
+    Errno,
+};
+
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Readers share the lock, return how many writes it saw.
+fn reader(rwlock_id: usize) -> ! {
+    rwlock_read(rwlock_id);
+    let writes = WRITES.load(Ordering::Acquire);
+    assert_eq!(rwlock_unlock(rwlock_id), Ok(()));
+    exit(writes as i32)
+}
+
+fn writer(rwlock_id: usize) -> ! {
+    rwlock_write(rwlock_id);
+    let writes = WRITES.load(Ordering::Acquire);
+    sleep(10);
+    WRITES.store(writes + 1, Ordering::Release);
+    assert_eq!(rwlock_unlock(rwlock_id), Ok(()));
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let rwlock_id = rwlock_create().unwrap();
+    assert_eq!(rwlock_unlock(rwlock_id), Err(Errno::EPERM));
+
+    // readers do not wait for each other
+    rwlock_read(rwlock_id);
+    let tid = thread_create(reader as usize, rwlock_id).unwrap();
+    assert_eq!(waittid(tid), Ok(0));
+
+    // a waiting writer goes before readers coming after it
+    let writer_tid = thread_create(writer as usize, rwlock_id).unwrap();
+    sleep(10);
+    let reader_tid = thread_create(reader as usize, rwlock_id).unwrap();
+    sleep(10);
+    assert_eq!(WRITES.load(Ordering::Acquire), 0);
+    assert_eq!(rwlock_unlock(rwlock_id), Ok(()));
+    assert_eq!(waittid(reader_tid), Ok(1));
+    assert_eq!(waittid(writer_tid), Ok(0));
+
+    // writers exclude each other
+    let tids = [0; 4].map(|_| thread_create(writer as usize, rwlock_id).unwrap());
+    for tid in tids {
+        assert_eq!(waittid(tid), Ok(0));
+    }
+    assert_eq!(WRITES.load(Ordering::Acquire), 5);
+    assert_eq!(rwlock_unlock(rwlock_id), Err(Errno::EPERM));
+    println!("rwlock_test passed!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{exit, get_time, setpriority, thread_create, waittid, Errno};
+
+const NICES: [isize; 3] = [0, 5, 10];
+/// More threads than harts, so that they compete for the CPU.
+const THREADS_PER_NICE: usize = 4;
+const DURATION_MS: isize = 500;
+
+/// Count loops until the deadline, each thread gets a share of the CPU
+/// according to its nice value under stride or fair scheduling.
+pub fn thread_fn(deadline: usize) -> ! {
+    let mut count = 0;
+    while get_time() < deadline as isize {
+        count += 1;
+    }
+    exit((count / 1000) as i32)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let deadline = get_time() + DURATION_MS;
+    let mut v = Vec::new();
+    for &nice in NICES.iter() {
+        for _ in 0..THREADS_PER_NICE {
+            let tid = thread_create(thread_fn as usize, deadline as usize).unwrap();
+            assert_eq!(setpriority(tid, nice), Ok(()));
+            v.push(tid);
+        }
+    }
+    assert_eq!(setpriority(0, 20), Err(Errno::EINVAL));
+    let counts: Vec<i32> = v
+        .chunks(THREADS_PER_NICE)
+        .zip(NICES.iter())
+        .map(|(tids, nice)| {
+            let count: i32 = tids.iter().map(|tid| waittid(*tid).unwrap()).sum();
+            println!("threads nice = {}, count = {}k", nice, count);
+            count
+        })
+        .collect();
+    // each 5 steps of nice cut the share to about a third, leave a wide margin
+    for pair in counts.windows(2) {
+        assert!(pair[0] * 2 > pair[1] * 3);
+    }
+    println!("sched_prio passed!");
+    0
+}
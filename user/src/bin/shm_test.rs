@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, shmat, shmctl, shmdt, shmget, wait, yield_, Errno, IPC_PRIVATE, IPC_RMID};
+
+const SHM_SIZE: usize = 8192;
+const COUNT: usize = 1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let shm_id = shmget(IPC_PRIVATE, SHM_SIZE).unwrap();
+    let addr = shmat(shm_id, 0).unwrap();
+    assert!(addr > 0);
+    // an explicit address must be aligned, in user space and off the stack
+    assert_eq!(shmat(shm_id, addr + 1), Err(Errno::EINVAL));
+    assert_eq!(shmat(shm_id, usize::MAX & !0xfff), Err(Errno::EINVAL));
+    let below_sp = (&shm_id as *const usize as usize & !0xfff) - 4 * 4096;
+    assert_eq!(shmat(shm_id, below_sp), Err(Errno::EINVAL));
+    let shared = unsafe { core::slice::from_raw_parts_mut(addr as *mut usize, COUNT + 1) };
+    shared[COUNT] = 0;
+    if fork().unwrap() == 0 {
+        // the child inherits the attachment
+        for (i, v) in shared.iter_mut().take(COUNT).enumerate() {
+            *v = i * i;
+        }
+        unsafe {
+            (&mut shared[COUNT] as *mut usize).write_volatile(1);
+        }
+        assert_eq!(shmdt(addr), Ok(()));
+        return 0;
+    }
+    while unsafe { (&shared[COUNT] as *const usize).read_volatile() } == 0 {
+        yield_();
+    }
+    for (i, v) in shared.iter().take(COUNT).enumerate() {
+        assert_eq!(*v, i * i);
+    }
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code).unwrap();
+    assert_eq!(exit_code, 0);
+    // frames are released after the last detach
+    assert_eq!(shmctl(shm_id, IPC_RMID), Ok(()));
+    assert_eq!(shmdt(addr), Ok(()));
+    assert_eq!(shmat(shm_id, 0), Err(Errno::EINVAL));
+    println!("shm_test passed!");
+    0
+}
@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+static USR1_COUNT: AtomicUsize = AtomicUsize::new(0);
+static CHLD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn usr1_handler(signum: i32) {
+    assert_eq!(signum, SIGUSR1);
+    USR1_COUNT.fetch_add(1, Ordering::SeqCst);
+    sigreturn();
+}
+
+fn chld_handler(signum: i32) {
+    assert_eq!(signum, SIGCHLD);
+    CHLD_COUNT.fetch_add(1, Ordering::SeqCst);
+    sigreturn();
+}
+
+fn user_handler() {
+    let action = SignalAction {
+        handler: usr1_handler as usize,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), Ok(()));
+    assert_eq!(kill(getpid() as usize, SIGUSR1), Ok(()));
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 1);
+    let mut old_action = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, None, Some(&mut old_action)), Ok(()));
+    assert_eq!(old_action.handler, usr1_handler as usize);
+    // SIGKILL cannot be caught
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), Err(Errno::EINVAL));
+    println!("user_handler passed!");
+}
+
+fn mask() {
+    assert_eq!(sigprocmask(SignalFlags::SIGUSR1.bits()), 0);
+    kill(getpid() as usize, SIGUSR1).unwrap();
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 1);
+    // the pending signal is delivered once unblocked
+    sigprocmask(0);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 2);
+    println!("mask passed!");
+}
+
+fn ignore() {
+    let action = SignalAction {
+        handler: SIG_IGN,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR2, Some(&action), None), Ok(()));
+    kill(getpid() as usize, SIGUSR2).unwrap();
+    println!("ignore passed!");
+}
+
+fn stop_continue() {
+    let action = SignalAction {
+        handler: chld_handler as usize,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGCHLD, Some(&action), None), Ok(()));
+    let pid = fork().unwrap();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    kill(pid, SIGSTOP).unwrap();
+    sleep(50);
+    // stopping the child is reported by SIGCHLD
+    assert!(CHLD_COUNT.load(Ordering::SeqCst) >= 1);
+    kill(pid, SIGCONT).unwrap();
+    sleep(50);
+    kill(pid, SIGTERM).unwrap();
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, -SIGTERM);
+    println!("stop_continue passed!");
+}
+
+fn own_group() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        // a group of its own, the signal must not reach the shell
+        setpgid(0, 0).unwrap();
+        let count = USR1_COUNT.load(Ordering::SeqCst);
+        // pid 0 means the group of the caller
+        kill(0, SIGUSR1).unwrap();
+        assert_eq!(USR1_COUNT.load(Ordering::SeqCst), count + 1);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    println!("own_group passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    user_handler();
+    mask();
+    ignore();
+    stop_continue();
+    own_group();
+    println!("sig_tests passed!");
+    0
+}
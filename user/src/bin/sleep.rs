@@ -18,12 +18,12 @@ fn sleepy() {
 #[no_mangle]
 pub fn main() -> i32 {
     let current_time = get_time();
-    let pid = fork();
+    let pid = fork().unwrap();
     let mut exit_code: i32 = 0;
     if pid == 0 {
         sleepy();
     }
-    assert!(waitpid(pid as usize, &mut exit_code) == pid && exit_code == 0);
+    assert!(waitpid(pid, &mut exit_code) == Ok(pid) && exit_code == 0);
     println!("use {} msecs.", get_time() - current_time);
     println!("sleep pass.");
     0
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::mem::MaybeUninit;
+use user_lib::{close, exit, pipe, read, thread_create, waittid, write};
+
+const DEPTH: usize = 32;
+
+/// Use about 1KiB of stack per level, 32KiB in total.
+fn f(depth: usize) -> usize {
+    let mut buf = [0u8; 1024];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        unsafe {
+            core::ptr::write_volatile(byte, (i + depth) as u8);
+        }
+    }
+    let sum = unsafe { core::ptr::read_volatile(&buf[depth]) } as usize;
+    if depth == 0 {
+        sum
+    } else {
+        sum + f(depth - 1)
+    }
+}
+
+fn expected() -> usize {
+    (0..=DEPTH).map(|depth| (depth * 2) as u8 as usize).sum()
+}
+
+/// The kernel writes into stack pages the thread has not touched yet.
+fn read_into_untouched_stack() {
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    let mut buf = MaybeUninit::<[u8; 16 * 1024]>::uninit();
+    let buf = unsafe { &mut *buf.as_mut_ptr() };
+    assert_eq!(write(pipe_fd[1], b"grown"), Ok(5));
+    assert_eq!(read(pipe_fd[0], &mut buf[..5]), Ok(5));
+    assert_eq!(&buf[..5], b"grown");
+    close(pipe_fd[0]).unwrap();
+    close(pipe_fd[1]).unwrap();
+}
+
+pub fn thread_main() -> ! {
+    read_into_untouched_stack();
+    assert_eq!(f(DEPTH), expected());
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(f(DEPTH), expected());
+    let v = vec![
+        thread_create(thread_main as usize, 0).unwrap(),
+        thread_create(thread_main as usize, 0).unwrap(),
+    ];
+    for tid in v.iter() {
+        assert_eq!(waittid(*tid), Ok(0));
+    }
+    println!("stack_grow passed!");
+    0
+}
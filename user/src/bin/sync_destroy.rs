@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    condvar_create, condvar_destroy, condvar_signal, condvar_wait, exit, exit_group, fork,
+    mutex_blocking_create, mutex_destroy, mutex_lock, mutex_unlock, rwlock_create, rwlock_destroy,
+    rwlock_read, rwlock_unlock, semaphore_create, semaphore_destroy, semaphore_down, semaphore_up,
+    sleep, thread_create, waitpid, waittid, Errno,
+};
+
+fn down(sem_id: usize) -> ! {
+    exit(semaphore_down(sem_id).map_or(-1, |()| 0))
+}
+
+fn wait(ids: usize) -> ! {
+    let (condvar_id, mutex_id) = (ids >> 16, ids & 0xffff);
+    mutex_lock(mutex_id).unwrap();
+    condvar_wait(condvar_id, mutex_id);
+    mutex_unlock(mutex_id);
+    exit(0)
+}
+
+fn semaphore() {
+    let sem_id = semaphore_create(0).unwrap();
+    let tid = thread_create(down as usize, sem_id).unwrap();
+    sleep(10);
+    assert_eq!(semaphore_destroy(sem_id), Err(Errno::EBUSY));
+    semaphore_up(sem_id);
+    assert_eq!(waittid(tid), Ok(0));
+    assert_eq!(semaphore_destroy(sem_id), Ok(()));
+    assert_eq!(semaphore_down(sem_id), Err(Errno::EINVAL));
+    assert_eq!(semaphore_destroy(sem_id), Err(Errno::EINVAL));
+    // the slot is reused
+    assert_eq!(semaphore_create(1).unwrap(), sem_id);
+    assert_eq!(semaphore_destroy(sem_id), Ok(()));
+    println!("semaphore passed!");
+}
+
+fn mutex() {
+    let mutex_id = mutex_blocking_create().unwrap();
+    mutex_lock(mutex_id).unwrap();
+    assert_eq!(mutex_destroy(mutex_id), Err(Errno::EBUSY));
+    mutex_unlock(mutex_id);
+    assert_eq!(mutex_destroy(mutex_id), Ok(()));
+    assert_eq!(mutex_lock(mutex_id), Err(Errno::EINVAL));
+    assert_eq!(mutex_blocking_create().unwrap(), mutex_id);
+    assert_eq!(mutex_destroy(mutex_id), Ok(()));
+    println!("mutex passed!");
+}
+
+fn condvar() {
+    let mutex_id = mutex_blocking_create().unwrap();
+    let condvar_id = condvar_create().unwrap();
+    let tid = thread_create(wait as usize, condvar_id << 16 | mutex_id).unwrap();
+    sleep(10);
+    assert_eq!(condvar_destroy(condvar_id), Err(Errno::EBUSY));
+    mutex_lock(mutex_id).unwrap();
+    condvar_signal(condvar_id);
+    mutex_unlock(mutex_id);
+    assert_eq!(waittid(tid), Ok(0));
+    assert_eq!(condvar_destroy(condvar_id), Ok(()));
+    assert_eq!(condvar_destroy(condvar_id), Err(Errno::EINVAL));
+    assert_eq!(mutex_destroy(mutex_id), Ok(()));
+    println!("condvar passed!");
+}
+
+fn rwlock() {
+    let rwlock_id = rwlock_create().unwrap();
+    rwlock_read(rwlock_id);
+    assert_eq!(rwlock_destroy(rwlock_id), Err(Errno::EBUSY));
+    assert_eq!(rwlock_unlock(rwlock_id), Ok(()));
+    assert_eq!(rwlock_destroy(rwlock_id), Ok(()));
+    assert_eq!(rwlock_unlock(rwlock_id), Err(Errno::EINVAL));
+    println!("rwlock passed!");
+}
+
+/// Threads still blocked on the objects of an exiting process are woken up.
+fn exit_while_blocked() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        let sem_id = semaphore_create(0).unwrap();
+        let mutex_id = mutex_blocking_create().unwrap();
+        let condvar_id = condvar_create().unwrap();
+        thread_create(down as usize, sem_id).unwrap();
+        thread_create(wait as usize, condvar_id << 16 | mutex_id).unwrap();
+        sleep(10);
+        exit_group(3);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 3);
+    println!("exit_while_blocked passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // fork only works in processes with a single thread
+    exit_while_blocked();
+    semaphore();
+    mutex();
+    condvar();
+    rwlock();
+    println!("sync_destroy passed!");
+    0
+}
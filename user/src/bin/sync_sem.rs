@@ -22,7 +22,7 @@ unsafe fn first() -> ! {
 
 unsafe fn second() -> ! {
     println!("Second want to continue,but need to wait first");
-    semaphore_down(SEM_SYNC);
+    semaphore_down(SEM_SYNC).unwrap();
     println!("Second can work now");
     exit(0)
 }
@@ -30,15 +30,15 @@ unsafe fn second() -> ! {
 #[no_mangle]
 pub fn main() -> i32 {
     // create semaphores
-    assert_eq!(semaphore_create(0) as usize, SEM_SYNC);
+    assert_eq!(semaphore_create(0).unwrap(), SEM_SYNC);
     // create threads
     let threads = vec![
-        thread_create(first as usize, 0),
-        thread_create(second as usize, 0),
+        thread_create(first as usize, 0).unwrap(),
+        thread_create(second as usize, 0).unwrap(),
     ];
     // wait for all threads to complete
     for thread in threads.iter() {
-        waittid(*thread as usize);
+        waittid(*thread).unwrap();
     }
     println!("sync_sem passed!");
     0
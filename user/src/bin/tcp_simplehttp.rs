@@ -30,10 +30,10 @@ fn handle_tcp_client(client_fd: usize) -> bool {
     // a buf to receive the data from the server
     let mut buf = vec![0u8; 1024];
 
-    let len = read(client_fd as usize, &mut buf);
+    let len = read(client_fd, &mut buf).unwrap_or(0);
 
     println!("receive {} bytes", len);
-    hexdump(&buf[..len as usize]);
+    hexdump(&buf[..len]);
 
     // verify whether it is a valid HTTP request simply, [0x47,0x45,0x54, 0x20] is GET
     if len < 4 || buf[..4] != [0x47, 0x45, 0x54, 0x20] {
@@ -61,7 +61,7 @@ fn handle_tcp_client(client_fd: usize) -> bool {
         </body>
         </html>"#;
         let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnecion: Close\r\n\r\n{}", content.len(),content);
-        write(client_fd, response.as_bytes());
+        write(client_fd, response.as_bytes()).unwrap();
         // terminate the connection immediately.
         return true;
     }
@@ -117,7 +117,7 @@ fn handle_tcp_client(client_fd: usize) -> bool {
     let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnecion: Close\r\n\r\n{}", content.len(),content);
 
     // write a response
-    write(client_fd, response.as_bytes());
+    write(client_fd, response.as_bytes()).unwrap();
 
     false
 }
@@ -126,23 +126,25 @@ fn handle_tcp_client(client_fd: usize) -> bool {
 pub fn main() -> i32 {
     println!("This is a very simple http server");
 
-    let tcp_fd = listen(80);
-
-    if tcp_fd < 0 {
-        println!("Failed to listen on port 80");
-        return -1;
-    }
+    let tcp_fd = match listen(80) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            println!("Failed to listen on port 80: {:?}", errno);
+            return -1;
+        }
+    };
 
     loop {
-        let client = accept(tcp_fd as usize);
+        let client = match accept(tcp_fd) {
+            Ok(client) => client,
+            Err(errno) => {
+                println!("Failed to accept a client on port 80: {:?}", errno);
+                return -1;
+            }
+        };
         println!("client connected: {}", client);
 
-        if client < 1 {
-            println!("Failed to accept a client on port 80");
-            return -1;
-        }
-
-        if handle_tcp_client(client as usize) {
+        if handle_tcp_client(client) {
             break;
         }
     }
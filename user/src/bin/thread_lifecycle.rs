@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, exit_group, fork, sleep, thread_create, thread_detach, tls_get, tls_set, waitpid,
+    waittid, waittid_any, yield_, Errno,
+};
+
+fn sleepy(ms: usize) -> ! {
+    sleep(ms);
+    exit(ms as i32)
+}
+
+fn spin() -> ! {
+    loop {
+        yield_();
+    }
+}
+
+fn tls_check(value: usize) -> ! {
+    assert_eq!(tls_get(0), 0);
+    tls_set(0, value);
+    for _ in 0..10 {
+        yield_();
+        assert_eq!(tls_get(0), value);
+    }
+    exit(0)
+}
+
+/// The process outlives its main thread and exits with the last thread.
+fn last_thread_exit() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        thread_create(sleepy as usize, 30).unwrap();
+        exit(1);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 30);
+    println!("last_thread_exit passed!");
+}
+
+/// `exit_group` ends threads which are still running.
+fn group_exit() {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        thread_create(spin as usize, 0).unwrap();
+        sleep(10);
+        exit_group(5);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 5);
+    println!("group_exit passed!");
+}
+
+fn blocking_join() {
+    let tid = thread_create(sleepy as usize, 20).unwrap();
+    assert_eq!(waittid(tid), Ok(20));
+    // reaped already
+    assert_eq!(waittid(tid), Err(Errno::ESRCH));
+    println!("blocking_join passed!");
+}
+
+fn join_any() {
+    let tids = [10, 30, 20].map(|ms| thread_create(sleepy as usize, ms).unwrap());
+    let mut exit_code = 0;
+    for expected in [0, 2, 1] {
+        assert_eq!(waittid_any(&mut exit_code), Ok(tids[expected]));
+        assert_eq!(exit_code as usize, [10, 30, 20][expected]);
+    }
+    assert_eq!(waittid_any(&mut exit_code), Err(Errno::ESRCH));
+    println!("join_any passed!");
+}
+
+fn detach() {
+    let tid = thread_create(sleepy as usize, 20).unwrap();
+    assert_eq!(thread_detach(tid), Ok(()));
+    assert_eq!(thread_detach(tid), Err(Errno::EINVAL));
+    assert_eq!(waittid(tid), Err(Errno::ESRCH));
+    // reaped on exit, there is nothing left to wait for
+    sleep(40);
+    let mut exit_code = 0;
+    assert_eq!(waittid_any(&mut exit_code), Err(Errno::ESRCH));
+    println!("detach passed!");
+}
+
+fn thread_local() {
+    tls_set(0, 1);
+    let tids = [2, 3, 4].map(|value| thread_create(tls_check as usize, value).unwrap());
+    for tid in tids {
+        assert_eq!(waittid(tid), Ok(0));
+    }
+    assert_eq!(tls_get(0), 1);
+    println!("thread_local passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // fork only works in processes with a single thread
+    last_thread_exit();
+    group_exit();
+    blocking_join();
+    join_any();
+    detach();
+    thread_local();
+    println!("thread_lifecycle passed!");
+    0
+}
@@ -32,12 +32,12 @@ pub fn thread_c() -> ! {
 #[no_mangle]
 pub fn main() -> i32 {
     let v = vec![
-        thread_create(thread_a as usize, 0),
-        thread_create(thread_b as usize, 0),
-        thread_create(thread_c as usize, 0),
+        thread_create(thread_a as usize, 0).unwrap(),
+        thread_create(thread_b as usize, 0).unwrap(),
+        thread_create(thread_c as usize, 0).unwrap(),
     ];
     for tid in v.iter() {
-        let exit_code = waittid(*tid as usize);
+        let exit_code = waittid(*tid).unwrap();
         println!("thread#{} exited with code {}", tid, exit_code);
     }
     println!("main thread exited.");
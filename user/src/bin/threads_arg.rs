@@ -30,13 +30,10 @@ pub fn main() -> i32 {
         Argument { ch: 'c', rc: 3 },
     ];
     for arg in args.iter() {
-        v.push(thread_create(
-            thread_print as usize,
-            arg as *const _ as usize,
-        ));
+        v.push(thread_create(thread_print as usize, arg as *const _ as usize).unwrap());
     }
     for tid in v.iter() {
-        let exit_code = waittid(*tid as usize);
+        let exit_code = waittid(*tid).unwrap();
         println!("thread#{} exited with code {}", tid, exit_code);
     }
     println!("main thread exited.");
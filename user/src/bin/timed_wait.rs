@@ -0,0 +1,145 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, clock_nanosleep, condvar_create, condvar_signal, condvar_wait_timeout, exit,
+    mutex_blocking_create, mutex_lock, mutex_lock_timeout, mutex_unlock, nanosleep,
+    semaphore_create, semaphore_down_timeout, semaphore_up, sleep, thread_create, waittid, Errno,
+    TimeSpec, CLOCK_MONOTONIC, CLOCK_THREAD_CPUTIME_ID, TIMER_ABSTIME,
+};
+
+const TIMEOUT_US: usize = 20_000;
+
+fn now_us() -> usize {
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut ts), Ok(()));
+    ts.as_us()
+}
+
+fn test_clock() {
+    let start = now_us();
+    let mut later = now_us();
+    while later == start {
+        later = now_us();
+    }
+    // microsecond resolution
+    assert!(later - start < 1000);
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_THREAD_CPUTIME_ID, &mut ts), Ok(()));
+    assert!(ts.as_us() > 0);
+    assert_eq!(clock_gettime(42, &mut ts), Err(Errno::EINVAL));
+    println!("clock ok");
+}
+
+fn test_nanosleep() {
+    let start = now_us();
+    assert_eq!(nanosleep(&TimeSpec::from_us(1500)), Ok(()));
+    let slept = now_us() - start;
+    assert!(slept >= 1500);
+    println!("nanosleep 1500us took {}us", slept);
+    // timers are not rounded up to the 10ms scheduler tick
+    let start = now_us();
+    assert_eq!(nanosleep(&TimeSpec::from_us(200)), Ok(()));
+    let slept = now_us() - start;
+    assert!((200..5000).contains(&slept));
+    let mut deadline = TimeSpec::default();
+    clock_gettime(CLOCK_MONOTONIC, &mut deadline).unwrap();
+    let deadline = deadline.add(&TimeSpec::from_us(3000));
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline),
+        Ok(())
+    );
+    assert!(now_us() >= deadline.as_us());
+    // a deadline in the past returns at once
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &TimeSpec::default()),
+        Ok(())
+    );
+    let invalid = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&invalid), Err(Errno::EINVAL));
+    println!("nanosleep ok");
+}
+
+const MUTEX: usize = 0;
+const SEM: usize = 0;
+const CONDVAR: usize = 0;
+
+fn holder() -> ! {
+    mutex_lock(MUTEX).unwrap();
+    sleep(50);
+    mutex_unlock(MUTEX);
+    exit(0)
+}
+
+fn test_mutex() {
+    assert_eq!(mutex_blocking_create().unwrap(), MUTEX);
+    let tid = thread_create(holder as usize, 0).unwrap();
+    sleep(10);
+    let start = now_us();
+    assert_eq!(mutex_lock_timeout(MUTEX, TIMEOUT_US), Err(Errno::ETIMEDOUT));
+    assert!(now_us() - start >= TIMEOUT_US);
+    // the timed out waiter is gone, unlocking hands the mutex to nobody
+    assert_eq!(mutex_lock_timeout(MUTEX, 1_000_000), Ok(()));
+    mutex_unlock(MUTEX);
+    waittid(tid).unwrap();
+    println!("mutex timeout ok");
+}
+
+fn poster() -> ! {
+    sleep(10);
+    semaphore_up(SEM);
+    exit(0)
+}
+
+fn test_semaphore() {
+    assert_eq!(semaphore_create(0).unwrap(), SEM);
+    assert_eq!(
+        semaphore_down_timeout(SEM, TIMEOUT_US),
+        Err(Errno::ETIMEDOUT)
+    );
+    // the count given back by the timed out waiter is not lost
+    let tid = thread_create(poster as usize, 0).unwrap();
+    assert_eq!(semaphore_down_timeout(SEM, 1_000_000), Ok(()));
+    waittid(tid).unwrap();
+    assert_eq!(semaphore_down_timeout(SEM, 0), Err(Errno::ETIMEDOUT));
+    println!("semaphore timeout ok");
+}
+
+fn signaler() -> ! {
+    sleep(10);
+    mutex_lock(MUTEX).unwrap();
+    condvar_signal(CONDVAR);
+    mutex_unlock(MUTEX);
+    exit(0)
+}
+
+fn test_condvar() {
+    assert_eq!(condvar_create().unwrap(), CONDVAR);
+    mutex_lock(MUTEX).unwrap();
+    assert_eq!(
+        condvar_wait_timeout(CONDVAR, MUTEX, TIMEOUT_US),
+        Err(Errno::ETIMEDOUT)
+    );
+    let tid = thread_create(signaler as usize, 0).unwrap();
+    assert_eq!(condvar_wait_timeout(CONDVAR, MUTEX, 1_000_000), Ok(()));
+    mutex_unlock(MUTEX);
+    waittid(tid).unwrap();
+    println!("condvar timeout ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_clock();
+    test_nanosleep();
+    test_mutex();
+    test_semaphore();
+    test_condvar();
+    println!("timed_wait passed!");
+    0
+}
@@ -14,31 +14,33 @@ use user_lib::{connect, read, write};
 pub fn main() -> i32 {
     println!("udp test open!");
 
-    let udp_fd = connect(10 << 24 | 0 << 16 | 2 << 8 | 2, 2001, 26099);
-
-    if udp_fd < 0 {
-        println!("failed to create udp connection.");
-        return -1;
-    }
+    let udp_fd = match connect(10 << 24 | 0 << 16 | 2 << 8 | 2, 2001, 26099) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            println!("failed to create udp connection: {:?}", errno);
+            return -1;
+        }
+    };
 
     let buf = "Hello rCoreOS user program!";
 
     println!("send <{}>", buf);
 
-    write(udp_fd as usize, buf.as_bytes());
+    write(udp_fd, buf.as_bytes()).unwrap();
 
     println!("udp send done, waiting for reply.");
 
     let mut buf = vec![0u8; 1024];
 
-    let len = read(udp_fd as usize, &mut buf);
-
-    if len < 0 {
-        println!("can't receive udp packet");
-        return -1;
-    }
+    let len = match read(udp_fd, &mut buf) {
+        Ok(len) => len,
+        Err(errno) => {
+            println!("can't receive udp packet: {:?}", errno);
+            return -1;
+        }
+    };
 
-    let recv_str = String::from_utf8_lossy(&buf[..len as usize]);
+    let recv_str = String::from_utf8_lossy(&buf[..len]);
 
     println!("receive reply <{}>", recv_str);
 
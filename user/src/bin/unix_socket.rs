@@ -0,0 +1,142 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{bind, recvfrom, recvmsg, sendmsg, sendto, socket, socketpair};
+use user_lib::{close, exit, fork, pipe, read, waitpid, write, Errno};
+use user_lib::{unix_accept, unix_connect, unix_listen, MsgHdr, AF_UNIX, SOCK_DGRAM, SOCK_STREAM};
+
+const SERVER_NAME: &str = "unix_server\0";
+
+fn pair() {
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), Ok(()));
+    let mut buf = [0u8; 16];
+    assert_eq!(write(sv[0], b"ping"), Ok(4));
+    assert_eq!(write(sv[1], b"pong"), Ok(4));
+    assert_eq!(read(sv[1], &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(read(sv[0], &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"pong");
+    // data sent before closing is still there
+    assert_eq!(write(sv[0], b"bye"), Ok(3));
+    close(sv[0]).unwrap();
+    assert_eq!(read(sv[1], &mut buf), Ok(3));
+    assert_eq!(read(sv[1], &mut buf), Ok(0));
+    assert_eq!(write(sv[1], b"lost"), Err(Errno::EPIPE));
+    close(sv[1]).unwrap();
+    println!("pair passed!");
+}
+
+fn client_server() {
+    let server = socket(AF_UNIX, SOCK_STREAM).unwrap();
+    assert_eq!(bind(server, SERVER_NAME), Ok(()));
+    assert_eq!(unix_listen(server, 4), Ok(()));
+    let pid = fork().unwrap();
+    if pid == 0 {
+        let client = socket(AF_UNIX, SOCK_STREAM).unwrap();
+        assert_eq!(unix_connect(client, SERVER_NAME), Ok(()));
+        assert_eq!(write(client, b"hello"), Ok(5));
+        let mut buf = [0u8; 16];
+        assert_eq!(read(client, &mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"HELLO");
+        exit(0);
+    }
+    let conn = unix_accept(server).unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(read(conn, &mut buf), Ok(5));
+    buf[..5].make_ascii_uppercase();
+    assert_eq!(write(conn, &buf[..5]), Ok(5));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    // the client is gone
+    assert_eq!(read(conn, &mut buf), Ok(0));
+    close(conn).unwrap();
+    close(server).unwrap();
+    // the name can be bound again once the socket is closed
+    let server = socket(AF_UNIX, SOCK_STREAM).unwrap();
+    assert_eq!(bind(server, SERVER_NAME), Ok(()));
+    let other = socket(AF_UNIX, SOCK_STREAM).unwrap();
+    assert_eq!(bind(other, SERVER_NAME), Err(Errno::EADDRINUSE));
+    // not listening
+    assert_eq!(unix_connect(other, SERVER_NAME), Err(Errno::ECONNREFUSED));
+    close(other).unwrap();
+    close(server).unwrap();
+    println!("client_server passed!");
+}
+
+fn datagram() {
+    let a = socket(AF_UNIX, SOCK_DGRAM).unwrap();
+    let b = socket(AF_UNIX, SOCK_DGRAM).unwrap();
+    assert_eq!(bind(a, "unix_dgram_a\0"), Ok(()));
+    assert_eq!(bind(b, "unix_dgram_b\0"), Ok(()));
+    assert_eq!(sendto(a, b"first", Some("unix_dgram_b\0")), Ok(5));
+    assert_eq!(sendto(a, b"second", Some("unix_dgram_b\0")), Ok(6));
+    let mut buf = [0u8; 16];
+    let mut from = [0u8; 32];
+    assert_eq!(recvfrom(b, &mut buf, &mut from), Ok(5));
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!(&from[..13], b"unix_dgram_a\0");
+    // the rest of a datagram is discarded
+    assert_eq!(read(b, &mut buf[..3]), Ok(3));
+    assert_eq!(&buf[..3], b"sec");
+    assert_eq!(unix_connect(b, "unix_dgram_a\0"), Ok(()));
+    assert_eq!(write(b, b"reply"), Ok(5));
+    assert_eq!(read(a, &mut buf), Ok(5));
+    assert_eq!(&buf[..5], b"reply");
+    close(a).unwrap();
+    close(b).unwrap();
+    println!("datagram passed!");
+}
+
+/// The child sends the read end of a pipe of its own.
+fn pass_fd() {
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), Ok(()));
+    let pid = fork().unwrap();
+    if pid == 0 {
+        close(sv[0]).unwrap();
+        let mut pipe_fd = [0usize; 2];
+        pipe(&mut pipe_fd).unwrap();
+        assert_eq!(sendmsg(sv[1], b"x", &[pipe_fd[0]]), Ok(1));
+        close(pipe_fd[0]).unwrap();
+        assert_eq!(write(pipe_fd[1], b"through"), Ok(7));
+        exit(0);
+    }
+    // no socket can be passed, a cycle of queued sockets would never be freed
+    assert_eq!(sendmsg(sv[0], b"x", &[sv[0]]), Err(Errno::EINVAL));
+    assert_eq!(sendmsg(sv[0], b"x", &[sv[1]]), Err(Errno::EINVAL));
+    let mut other = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut other), Ok(()));
+    assert_eq!(sendmsg(sv[0], b"x", &[other[0]]), Err(Errno::EINVAL));
+    close(other[0]).unwrap();
+    close(other[1]).unwrap();
+    close(sv[1]).unwrap();
+    let mut buf = [0u8; 16];
+    let mut fds = [0usize; 2];
+    let mut msg = MsgHdr::new(&mut buf, &mut fds);
+    assert_eq!(recvmsg(sv[0], &mut msg), Ok(1));
+    assert_eq!(msg.fds_len, 1);
+    assert_eq!(buf[0], b'x');
+    assert_eq!(read(fds[0], &mut buf), Ok(7));
+    assert_eq!(&buf[..7], b"through");
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    close(fds[0]).unwrap();
+    close(sv[0]).unwrap();
+    println!("pass_fd passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    pair();
+    client_server();
+    datagram();
+    pass_fd();
+    println!("unix_socket passed!");
+    0
+}
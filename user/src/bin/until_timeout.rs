@@ -4,7 +4,7 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, fork, get_time, kill, waitpid, waitpid_nb, SignalFlags};
+use user_lib::{exec, fork, get_time, kill, waitpid, waitpid_nb, SIGINT};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
@@ -12,12 +12,11 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
     let timeout_ms = argv[2]
         .parse::<isize>()
         .expect("Error when parsing timeout!");
-    let pid = fork() as usize;
+    let pid = fork().unwrap();
     if pid == 0 {
-        if exec(argv[1], &[core::ptr::null::<u8>()]) != 0 {
-            println!("Error when executing '{}'", argv[1]);
-            return -4;
-        }
+        let errno = exec(argv[1], &[core::ptr::null::<u8>()]);
+        println!("Error when executing '{}': {:?}", argv[1], errno);
+        return -4;
     } else {
         let start_time = get_time();
         let mut child_exited = false;
@@ -26,7 +25,7 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
             if get_time() - start_time > timeout_ms {
                 break;
             }
-            if waitpid_nb(pid, &mut exit_code) as usize == pid {
+            if waitpid_nb(pid, &mut exit_code) == Ok(pid) {
                 child_exited = true;
                 println!(
                     "child exited in {}ms, exit_code = {}",
@@ -37,8 +36,8 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
         }
         if !child_exited {
             println!("child has run for {}ms, kill it!", timeout_ms);
-            kill(pid, SignalFlags::SIGINT.bits());
-            assert_eq!(waitpid(pid, &mut exit_code) as usize, pid);
+            kill(pid, SIGINT).unwrap();
+            assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
             println!("exit code of the child is {}", exit_code);
         }
     }
@@ -7,16 +7,15 @@ extern crate alloc;
 #[macro_use]
 extern crate user_lib;
 
-const LF: u8 = 0x0au8;
-const CR: u8 = 0x0du8;
-const DL: u8 = 0x7fu8;
-const BS: u8 = 0x08u8;
 const LINE_START: &str = ">> ";
 
 use alloc::string::String;
 use alloc::vec::Vec;
-use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    close, dup, exec, exit, fork, getpid, killpg, open, pipe, read, setpgid, sigaction, tcgetattr,
+    tcsetattr, tcsetpgrp, waitpid_untraced, wifstopped, yield_, OpenFlags, SignalAction, Termios,
+    SIGCONT, SIGINT, SIGQUIT, SIGTSTP, SIG_IGN,
+};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -74,141 +73,318 @@ impl ProcessArguments {
     }
 }
 
-#[no_mangle]
-pub fn main() -> i32 {
-    println!("Rust user shell");
-    let mut line: String = String::new();
-    print!("{}", LINE_START);
-    loop {
-        let c = getchar();
-        match c {
-            LF | CR => {
-                println!("");
-                if !line.is_empty() {
-                    let splited: Vec<_> = line.as_str().split('|').collect();
-                    let process_arguments_list: Vec<_> = splited
-                        .iter()
-                        .map(|&cmd| ProcessArguments::new(cmd))
-                        .collect();
-                    let mut valid = true;
-                    for (i, process_args) in process_arguments_list.iter().enumerate() {
-                        if i == 0 {
-                            if !process_args.output.is_empty() {
-                                valid = false;
-                            }
-                        } else if i == process_arguments_list.len() - 1 {
-                            if !process_args.input.is_empty() {
-                                valid = false;
-                            }
-                        } else if !process_args.output.is_empty() || !process_args.input.is_empty()
-                        {
-                            valid = false;
-                        }
-                    }
-                    if process_arguments_list.len() == 1 {
-                        valid = true;
-                    }
-                    if !valid {
-                        println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
-                    } else {
-                        // create pipes
-                        let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
-                        if !process_arguments_list.is_empty() {
-                            for _ in 0..process_arguments_list.len() - 1 {
-                                let mut pipe_fd = [0usize; 2];
-                                pipe(&mut pipe_fd);
-                                pipes_fd.push(pipe_fd);
-                            }
-                        }
-                        let mut children: Vec<_> = Vec::new();
-                        for (i, process_argument) in process_arguments_list.iter().enumerate() {
-                            let pid = fork();
-                            if pid == 0 {
-                                let input = &process_argument.input;
-                                let output = &process_argument.output;
-                                let args_copy = &process_argument.args_copy;
-                                let args_addr = &process_argument.args_addr;
-                                // redirect input
-                                if !input.is_empty() {
-                                    let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                                    if input_fd == -1 {
-                                        println!("Error when opening file {}", input);
-                                        return -4;
-                                    }
-                                    let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
-                                    close(input_fd);
-                                }
-                                // redirect output
-                                if !output.is_empty() {
-                                    let output_fd = open(
-                                        output.as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd == -1 {
-                                        println!("Error when opening file {}", output);
-                                        return -4;
-                                    }
-                                    let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
-                                    close(output_fd);
-                                }
-                                // receive input from the previous process
-                                if i > 0 {
-                                    close(0);
-                                    let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
-                                }
-                                // send output to the next process
-                                if i < process_arguments_list.len() - 1 {
-                                    close(1);
-                                    let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
-                                }
-                                // close all pipe ends inherited from the parent process
-                                for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
-                                }
-                                // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                                    println!("Error when executing!");
-                                    return -4;
-                                }
-                                unreachable!();
-                            } else {
-                                children.push(pid);
-                            }
-                        }
-                        for pipe_fd in pipes_fd.iter() {
-                            close(pipe_fd[0]);
-                            close(pipe_fd[1]);
-                        }
-                        let mut exit_code: i32 = 0;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            //println!("Shell: Process {} exited with code {}", pid, exit_code);
-                        }
-                    }
-                    line.clear();
+/// Signals of the terminal which stop or kill the foreground job but not the shell.
+const JOB_CONTROL_SIGNALS: [i32; 3] = [SIGINT, SIGQUIT, SIGTSTP];
+
+/// A pipeline started by the shell, all its processes share a process group.
+struct Job {
+    id: usize,
+    pgid: usize,
+    /// processes not reaped yet
+    pids: Vec<usize>,
+    command: String,
+    stopped: bool,
+}
+
+impl Job {
+    /// Reap the processes which have exited and record whether the job was stopped.
+    /// Return whether any process of the job changed its state.
+    fn poll(&mut self) -> bool {
+        let mut changed = false;
+        let mut status: i32 = 0;
+        let mut i = 0;
+        while i < self.pids.len() {
+            if waitpid_untraced(self.pids[i] as isize, &mut status) == Ok(self.pids[i]) {
+                changed = true;
+                if wifstopped(status) {
+                    self.stopped = true;
+                } else {
+                    self.pids.remove(i);
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        changed
+    }
+    fn is_done(&self) -> bool {
+        self.pids.is_empty()
+    }
+}
+
+struct Shell {
+    pgid: usize,
+    /// terminal attributes restored after each foreground job
+    termios: Termios,
+    jobs: Vec<Job>,
+}
+
+impl Shell {
+    fn new() -> Self {
+        // the shell leads its own group and owns the terminal
+        let _ = setpgid(0, 0);
+        let pgid = getpid() as usize;
+        let _ = tcsetpgrp(0, pgid);
+        let ignore = SignalAction {
+            handler: SIG_IGN,
+            ..Default::default()
+        };
+        for signum in JOB_CONTROL_SIGNALS {
+            sigaction(signum, Some(&ignore), None).unwrap();
+        }
+        let mut termios = Termios::default();
+        let _ = tcgetattr(0, &mut termios);
+        Self {
+            pgid,
+            termios,
+            jobs: Vec::new(),
+        }
+    }
+    fn next_job_id(&self) -> usize {
+        self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1
+    }
+    /// Give the terminal to `job` and wait until it exits or stops.
+    fn wait_foreground(&mut self, mut job: Job) {
+        let _ = tcsetpgrp(0, job.pgid);
+        job.stopped = false;
+        while !job.is_done() && !job.stopped {
+            if !job.poll() {
+                yield_();
+            }
+        }
+        let _ = tcsetpgrp(0, self.pgid);
+        // a job may leave the terminal in raw mode
+        let _ = tcsetattr(0, &self.termios);
+        if job.stopped {
+            println!("[{}]+  Stopped    {}", job.id, job.command);
+            self.jobs.push(job);
+        }
+    }
+    /// Report background jobs which have finished or stopped since last prompt.
+    fn report_jobs(&mut self) {
+        for job in self.jobs.iter_mut() {
+            let was_stopped = job.stopped;
+            if job.poll() && job.stopped && !was_stopped {
+                println!("[{}]+  Stopped    {}", job.id, job.command);
+            }
+        }
+        self.jobs.retain(|job| {
+            if job.is_done() {
+                println!("[{}]+  Done       {}", job.id, job.command);
+            }
+            !job.is_done()
+        });
+    }
+    /// Find the job given by the argument of `fg`/`bg`, the latest one by default.
+    fn take_job(&mut self, arg: Option<&str>) -> Option<Job> {
+        let idx = match arg {
+            Some(arg) => {
+                let id: usize = arg.trim_start_matches('%').parse().ok()?;
+                self.jobs.iter().position(|job| job.id == id)?
+            }
+            None => self.jobs.len().checked_sub(1)?,
+        };
+        Some(self.jobs.remove(idx))
+    }
+    /// Run builtin commands, return false if `line` is not one.
+    fn builtin(&mut self, line: &str) -> bool {
+        let mut words = line.split(' ').filter(|word| !word.is_empty());
+        match words.next() {
+            Some("jobs") => {
+                for job in self.jobs.iter() {
+                    let state = if job.stopped { "Stopped" } else { "Running" };
+                    println!("[{}]   {:<10} {}", job.id, state, job.command);
                 }
-                print!("{}", LINE_START);
             }
-            BS | DL => {
-                if !line.is_empty() {
-                    print!("{}", BS as char);
-                    print!(" ");
-                    print!("{}", BS as char);
-                    line.pop();
+            Some("fg") => match self.take_job(words.next()) {
+                Some(job) => {
+                    println!("{}", job.command);
+                    let _ = killpg(job.pgid, SIGCONT);
+                    self.wait_foreground(job);
+                }
+                None => println!("fg: no such job"),
+            },
+            Some("bg") => match self.take_job(words.next()) {
+                Some(mut job) => {
+                    let _ = killpg(job.pgid, SIGCONT);
+                    job.stopped = false;
+                    println!("[{}]+ {} &", job.id, job.command);
+                    self.jobs.push(job);
+                }
+                None => println!("bg: no such job"),
+            },
+            _ => return false,
+        }
+        true
+    }
+    fn execute(&mut self, line: &str) {
+        let line = line.trim();
+        let (command, background) = match line.strip_suffix('&') {
+            Some(command) => (command.trim_end(), true),
+            None => (line, false),
+        };
+        if command.is_empty() || (!background && self.builtin(command)) {
+            return;
+        }
+        let splited: Vec<_> = command.split('|').collect();
+        let process_arguments_list: Vec<_> = splited
+            .iter()
+            .map(|&cmd| ProcessArguments::new(cmd))
+            .collect();
+        let mut valid = true;
+        for (i, process_args) in process_arguments_list.iter().enumerate() {
+            if i == 0 {
+                if !process_args.output.is_empty() {
+                    valid = false;
                 }
+            } else if i == process_arguments_list.len() - 1 {
+                if !process_args.input.is_empty() {
+                    valid = false;
+                }
+            } else if !process_args.output.is_empty() || !process_args.input.is_empty() {
+                valid = false;
             }
-            _ => {
-                print!("{}", c as char);
-                line.push(c as char);
+        }
+        if process_arguments_list.len() == 1 {
+            valid = true;
+        }
+        if !valid {
+            println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
+            return;
+        }
+        // create pipes
+        let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
+        if !process_arguments_list.is_empty() {
+            for _ in 0..process_arguments_list.len() - 1 {
+                let mut pipe_fd = [0usize; 2];
+                pipe(&mut pipe_fd).unwrap();
+                pipes_fd.push(pipe_fd);
             }
         }
+        let mut children: Vec<_> = Vec::new();
+        // the first process of the pipeline leads the group of the job
+        let mut pgid = 0;
+        for (i, process_argument) in process_arguments_list.iter().enumerate() {
+            let pid = fork().unwrap();
+            if pid == 0 {
+                let _ = setpgid(0, pgid);
+                // the terminal signals must not be ignored by the job
+                for signum in JOB_CONTROL_SIGNALS {
+                    sigaction(signum, Some(&SignalAction::default()), None).unwrap();
+                }
+                let input = &process_argument.input;
+                let output = &process_argument.output;
+                let args_copy = &process_argument.args_copy;
+                let args_addr = &process_argument.args_addr;
+                // redirect input
+                if !input.is_empty() {
+                    let input_fd = match open(input.as_str(), OpenFlags::RDONLY) {
+                        Ok(fd) => fd,
+                        Err(errno) => {
+                            println!("Error when opening file {}: {:?}", input, errno);
+                            exit(-4);
+                        }
+                    };
+                    close(0).unwrap();
+                    assert_eq!(dup(input_fd), Ok(0));
+                    close(input_fd).unwrap();
+                }
+                // redirect output
+                if !output.is_empty() {
+                    let output_fd =
+                        match open(output.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY) {
+                            Ok(fd) => fd,
+                            Err(errno) => {
+                                println!("Error when opening file {}: {:?}", output, errno);
+                                exit(-4);
+                            }
+                        };
+                    close(1).unwrap();
+                    assert_eq!(dup(output_fd), Ok(1));
+                    close(output_fd).unwrap();
+                }
+                // receive input from the previous process
+                if i > 0 {
+                    close(0).unwrap();
+                    let read_end = pipes_fd.get(i - 1).unwrap()[0];
+                    assert_eq!(dup(read_end), Ok(0));
+                }
+                // send output to the next process
+                if i < process_arguments_list.len() - 1 {
+                    close(1).unwrap();
+                    let write_end = pipes_fd.get(i).unwrap()[1];
+                    assert_eq!(dup(write_end), Ok(1));
+                }
+                // close all pipe ends inherited from the parent process
+                for pipe_fd in pipes_fd.iter() {
+                    close(pipe_fd[0]).unwrap();
+                    close(pipe_fd[1]).unwrap();
+                }
+                // execute new application
+                let errno = exec(args_copy[0].as_str(), args_addr.as_slice());
+                println!("Error when executing: {:?}", errno);
+                exit(-4);
+            } else {
+                if pgid == 0 {
+                    pgid = pid;
+                }
+                // also done by the parent, so the group exists before the terminal is handed over
+                let _ = setpgid(pid, pgid);
+                children.push(pid);
+            }
+        }
+        for pipe_fd in pipes_fd.iter() {
+            close(pipe_fd[0]).unwrap();
+            close(pipe_fd[1]).unwrap();
+        }
+        let job = Job {
+            id: self.next_job_id(),
+            pgid,
+            pids: children,
+            command: String::from(command),
+            stopped: false,
+        };
+        if background {
+            println!("[{}] {}", job.id, job.pgid);
+            self.jobs.push(job);
+        } else {
+            self.wait_foreground(job);
+        }
+    }
+}
+
+/// Read a line edited by the terminal, return `None` at end of file.
+fn read_line() -> Option<String> {
+    let mut line = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(0, &mut buf).unwrap_or(0);
+        if len == 0 {
+            return if line.is_empty() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&line).into_owned())
+            };
+        }
+        line.extend_from_slice(&buf[..len]);
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            return Some(String::from_utf8_lossy(&line).into_owned());
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("Rust user shell");
+    let mut shell = Shell::new();
+    loop {
+        shell.report_jobs();
+        print!("{}", LINE_START);
+        match read_line() {
+            Some(line) => shell.execute(line.as_str()),
+            // the shell stays alive on ^D
+            None => println!(""),
+        }
     }
 }
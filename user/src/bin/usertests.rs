@@ -12,6 +12,7 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
     ("cat\0", "filea\0", "\0", "\0", 0),
     ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
+    ("deadlock_test\0", "\0", "\0", "\0", 0),
     ("eisenberg\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
@@ -19,30 +20,47 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktest\0", "\0", "\0", "\0", 0),
     ("forktest2\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
+    ("futex_test\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
-    ("mpsc_sem\0", "\0", "\0", "\0", 0),
+    ("mpsc_mq\0", "\0", "\0", "\0", 0),
     ("peterson\0", "\0", "\0", "\0", 0),
     ("phil_din_mutex\0", "\0", "\0", "\0", 0),
     ("pipe_large_test\0", "\0", "\0", "\0", 0),
     ("pipetest\0", "\0", "\0", "\0", 0),
+    ("unix_socket\0", "\0", "\0", "\0", 0),
+    ("poll_test\0", "\0", "\0", "\0", 0),
+    ("fcntl_test\0", "\0", "\0", "\0", 0),
+    ("rlimit_test\0", "\0", "\0", "\0", 0),
+    ("errno_test\0", "\0", "\0", "\0", 0),
     ("adder_peterson_spin\0", "\0", "\0", "\0", 0),
     ("adder_peterson_yield\0", "\0", "\0", "\0", 0),
     ("adder_mutex_blocking\0", "\0", "\0", "\0", 0),
     ("adder_mutex_spin\0", "\0", "\0", "\0", 0),
+    ("adder_futex\0", "\0", "\0", "\0", 0),
     ("run_pipe_test\0", "\0", "\0", "\0", 0),
+    ("shm_test\0", "\0", "\0", "\0", 0),
+    ("stack_grow\0", "\0", "\0", "\0", 0),
+    ("sched_prio\0", "\0", "\0", "\0", 0),
+    ("sig_tests\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sync_sem\0", "\0", "\0", "\0", 0),
     ("condsync_sem\0", "\0", "\0", "\0", 0),
     ("condsync_condvar\0", "\0", "\0", "\0", 0),
+    ("timed_wait\0", "\0", "\0", "\0", 0),
     ("threads_arg\0", "\0", "\0", "\0", 0),
     ("threads\0", "\0", "\0", "\0", 0),
+    ("thread_lifecycle\0", "\0", "\0", "\0", 0),
+    ("rwlock_test\0", "\0", "\0", "\0", 0),
+    ("priority_inversion\0", "\0", "\0", "\0", 0),
+    ("sync_destroy\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
     ("barrier_fail\0", "\0", "\0", "\0", 0),
     ("barrier_condvar\0", "\0", "\0", "\0", 0),
+    ("barrier_futex\0", "\0", "\0", "\0", 0),
 ];
 
 static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[
@@ -93,14 +111,14 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
             arr[3] = core::ptr::null::<u8>();
         }
 
-        let pid = fork();
+        let pid = fork().unwrap();
         if pid == 0 {
             exec(test.0, &arr[..]);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
-            let wait_pid = waitpid(pid as usize, &mut exit_code);
-            assert_eq!(pid, wait_pid);
+            let wait_pid = waitpid(pid, &mut exit_code);
+            assert_eq!(Ok(pid), wait_pid);
             if exit_code == test.4 {
                 // summary apps with  exit_code
                 pass_num = pass_num + 1;
@@ -9,7 +9,8 @@ struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        write(STDOUT, s.as_bytes());
+        // a failure here cannot be printed anyway
+        let _ = write(STDOUT, s.as_bytes());
         Ok(())
     }
 }
@@ -34,6 +35,7 @@ macro_rules! println {
 
 pub fn getchar() -> u8 {
     let mut c = [0u8; 1];
-    read(STDIN, &mut c);
+    // left 0 if nothing is read
+    let _ = read(STDIN, &mut c);
     c[0]
 }
@@ -0,0 +1,103 @@
+//! Error numbers returned by syscalls.
+//!
+//! A failed syscall returns an error number negated, the wrappers of this
+//! crate turn it into `Err(Errno)`.
+
+use core::fmt;
+
+/// The same as the error numbers of Linux.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Errno(pub isize);
+
+impl Errno {
+    pub const EPERM: Self = Self(1);
+    pub const ENOENT: Self = Self(2);
+    pub const ESRCH: Self = Self(3);
+    pub const EINTR: Self = Self(4);
+    pub const E2BIG: Self = Self(7);
+    pub const ENOEXEC: Self = Self(8);
+    pub const EBADF: Self = Self(9);
+    pub const ECHILD: Self = Self(10);
+    pub const EAGAIN: Self = Self(11);
+    pub const ENOMEM: Self = Self(12);
+    pub const EFAULT: Self = Self(14);
+    pub const EBUSY: Self = Self(16);
+    pub const EEXIST: Self = Self(17);
+    pub const EINVAL: Self = Self(22);
+    pub const EMFILE: Self = Self(24);
+    pub const ENOTTY: Self = Self(25);
+    pub const ENOSPC: Self = Self(28);
+    pub const EPIPE: Self = Self(32);
+    pub const EDEADLK: Self = Self(35);
+    pub const ENAMETOOLONG: Self = Self(36);
+    pub const ENOSYS: Self = Self(38);
+    pub const ENOTSOCK: Self = Self(88);
+    pub const EMSGSIZE: Self = Self(90);
+    pub const EPROTOTYPE: Self = Self(91);
+    pub const EOPNOTSUPP: Self = Self(95);
+    pub const EAFNOSUPPORT: Self = Self(97);
+    pub const EADDRINUSE: Self = Self(98);
+    pub const EISCONN: Self = Self(106);
+    pub const ENOTCONN: Self = Self(107);
+    pub const ETIMEDOUT: Self = Self(110);
+    pub const ECONNREFUSED: Self = Self(111);
+
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            Self::EPERM => "EPERM",
+            Self::ENOENT => "ENOENT",
+            Self::ESRCH => "ESRCH",
+            Self::EINTR => "EINTR",
+            Self::E2BIG => "E2BIG",
+            Self::ENOEXEC => "ENOEXEC",
+            Self::EBADF => "EBADF",
+            Self::ECHILD => "ECHILD",
+            Self::EAGAIN => "EAGAIN",
+            Self::ENOMEM => "ENOMEM",
+            Self::EFAULT => "EFAULT",
+            Self::EBUSY => "EBUSY",
+            Self::EEXIST => "EEXIST",
+            Self::EINVAL => "EINVAL",
+            Self::EMFILE => "EMFILE",
+            Self::ENOTTY => "ENOTTY",
+            Self::ENOSPC => "ENOSPC",
+            Self::EPIPE => "EPIPE",
+            Self::EDEADLK => "EDEADLK",
+            Self::ENAMETOOLONG => "ENAMETOOLONG",
+            Self::ENOSYS => "ENOSYS",
+            Self::ENOTSOCK => "ENOTSOCK",
+            Self::EMSGSIZE => "EMSGSIZE",
+            Self::EPROTOTYPE => "EPROTOTYPE",
+            Self::EOPNOTSUPP => "EOPNOTSUPP",
+            Self::EAFNOSUPPORT => "EAFNOSUPPORT",
+            Self::EADDRINUSE => "EADDRINUSE",
+            Self::EISCONN => "EISCONN",
+            Self::ENOTCONN => "ENOTCONN",
+            Self::ETIMEDOUT => "ETIMEDOUT",
+            Self::ECONNREFUSED => "ECONNREFUSED",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Debug for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "Errno({})", self.0),
+        }
+    }
+}
+
+/// The value of a successful syscall, or why it failed.
+pub type SysResult<T> = Result<T, Errno>;
+
+/// Split the return value of a syscall into its value and its error.
+pub(crate) fn check(ret: isize) -> SysResult<usize> {
+    if ret < 0 {
+        Err(Errno(-ret))
+    } else {
+        Ok(ret as usize)
+    }
+}
@@ -7,24 +7,47 @@ bitflags! {
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        /// the fd is closed by `exec`
+        const CLOEXEC = 1 << 19;
     }
 }
 
-pub fn dup(fd: usize) -> isize {
-    sys_dup(fd)
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+pub const FD_CLOEXEC: usize = 1;
+
+pub fn dup(fd: usize) -> SysResult<usize> {
+    check(sys_dup(fd))
+}
+pub fn open(path: &str, flags: OpenFlags) -> SysResult<usize> {
+    check(sys_open(path, flags.bits))
+}
+pub fn close(fd: usize) -> SysResult<()> {
+    check(sys_close(fd)).map(drop)
 }
-pub fn open(path: &str, flags: OpenFlags) -> isize {
-    sys_open(path, flags.bits)
+pub fn pipe(pipe_fd: &mut [usize]) -> SysResult<()> {
+    check(sys_pipe(pipe_fd, 0)).map(drop)
 }
-pub fn close(fd: usize) -> isize {
-    sys_close(fd)
+/// Only `OpenFlags::NONBLOCK` and `OpenFlags::CLOEXEC` are accepted.
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> SysResult<()> {
+    check(sys_pipe(pipe_fd, flags.bits)).map(drop)
 }
-pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+/// Fail with `EAGAIN` instead of blocking if `fd` is non-blocking.
+pub fn read(fd: usize, buf: &mut [u8]) -> SysResult<usize> {
+    check(sys_read(fd, buf))
 }
-pub fn read(fd: usize, buf: &mut [u8]) -> isize {
-    sys_read(fd, buf)
+/// Fail with `EAGAIN` instead of blocking if `fd` is non-blocking, or
+/// return the bytes which fit.
+pub fn write(fd: usize, buf: &[u8]) -> SysResult<usize> {
+    check(sys_write(fd, buf))
 }
-pub fn write(fd: usize, buf: &[u8]) -> isize {
-    sys_write(fd, buf)
+/// `F_DUPFD` returns the lowest free fd not below `arg`, `F_SETFL` changes
+/// `OpenFlags::NONBLOCK` only.
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> SysResult<usize> {
+    check(sys_fcntl(fd, cmd, arg))
 }
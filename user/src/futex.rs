@@ -0,0 +1,338 @@
+//! Synchronization primitives on top of the `futex` syscall.
+//!
+//! Unlike the kernel objects of `mutex_create` and friends, these only
+//! enter the kernel when a thread has to wait or there are threads to
+//! wake up. The state lives in user memory, so they can be placed in
+//! statics or in shared memory.
+
+use super::*;
+use core::cell::UnsafeCell;
+use core::ptr::null;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+fn addr(word: &AtomicU32) -> *const u32 {
+    word as *const AtomicU32 as *const u32
+}
+
+/// Wait until woken up if `word` still holds `val`. Fail with `EAGAIN` if
+/// `word` has changed and with `ETIMEDOUT` on timeout.
+pub fn futex_wait(word: &AtomicU32, val: u32, timeout: Option<&TimeSpec>) -> SysResult<()> {
+    let timeout = timeout.map_or(null(), |timeout| timeout as *const _);
+    check(sys_futex(
+        addr(word),
+        FUTEX_WAIT,
+        val as usize,
+        timeout as usize,
+        null(),
+    ))
+    .map(drop)
+}
+
+/// Wake up at most `count` waiters, return how many are woken up.
+pub fn futex_wake(word: &AtomicU32, count: usize) -> SysResult<usize> {
+    check(sys_futex(addr(word), FUTEX_WAKE, count, 0, null()))
+}
+
+/// Wake up at most `count` waiters of `word` and move at most `requeue`
+/// of the others to `target`.
+pub fn futex_requeue(
+    word: &AtomicU32,
+    count: usize,
+    target: &AtomicU32,
+    requeue: usize,
+) -> SysResult<usize> {
+    check(sys_futex(
+        addr(word),
+        FUTEX_REQUEUE,
+        count,
+        requeue,
+        addr(target),
+    ))
+}
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// locked and there may be waiters
+const CONTENDED: u32 = 2;
+
+/// Ref: Ulrich Drepper, Futexes Are Tricky, mutex 2.
+pub struct Mutex {
+    state: AtomicU32,
+}
+
+impl Mutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+
+    pub fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    pub fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_contended();
+        }
+    }
+
+    /// Taking the lock this way keeps it marked contended, which is
+    /// needed by threads moved here from a `Condvar`.
+    fn lock_contended(&self) {
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            let _ = futex_wait(&self.state, CONTENDED, None);
+        }
+    }
+
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            let _ = futex_wake(&self.state, 1);
+        }
+    }
+}
+
+pub struct Condvar {
+    /// bumped by every notification
+    seq: AtomicU32,
+    /// the mutex used by the waiters, waiters are moved to it on `notify_all`
+    mutex: AtomicUsize,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            mutex: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn wait(&self, mutex: &Mutex) {
+        self.wait_timeout(mutex, None);
+    }
+
+    /// Return false on timeout, `mutex` is locked again in either case.
+    /// Spurious wakeups are possible.
+    pub fn wait_timeout(&self, mutex: &Mutex, timeout: Option<&TimeSpec>) -> bool {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.mutex
+            .store(mutex as *const _ as usize, Ordering::Relaxed);
+        mutex.unlock();
+        let woken = futex_wait(&self.seq, seq, timeout) != Err(Errno::ETIMEDOUT);
+        mutex.lock_contended();
+        woken
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        let _ = futex_wake(&self.seq, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        let mutex = self.mutex.load(Ordering::Relaxed) as *const Mutex;
+        if mutex.is_null() {
+            return;
+        }
+        // SAFETY: the mutex outlives the waiters using it
+        let mutex = unsafe { &*mutex };
+        // the moved waiters are only woken up by an unlock of a contended mutex
+        let contended = mutex
+            .state
+            .compare_exchange(LOCKED, CONTENDED, Ordering::Relaxed, Ordering::Relaxed)
+            .map_or_else(|state| state == CONTENDED, |_| true);
+        if contended {
+            // wake up one and let the others wait for the mutex instead of racing for it
+            let _ = futex_requeue(&self.seq, 1, &mutex.state, usize::MAX);
+        } else {
+            let _ = futex_wake(&self.seq, usize::MAX);
+        }
+    }
+}
+
+const WRITE_LOCKED: u32 = u32::MAX;
+
+/// Readers-writer lock, new readers wait while writers are waiting.
+pub struct RwLock {
+    /// number of readers or `WRITE_LOCKED`
+    state: AtomicU32,
+    writers_waiting: AtomicU32,
+}
+
+impl RwLock {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            writers_waiting: AtomicU32::new(0),
+        }
+    }
+
+    pub fn read(&self) {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state != WRITE_LOCKED
+                && self.writers_waiting.load(Ordering::Relaxed) == 0
+                && self
+                    .state
+                    .compare_exchange(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return;
+            }
+            if state == WRITE_LOCKED || self.writers_waiting.load(Ordering::Relaxed) != 0 {
+                let _ = futex_wait(&self.state, state, None);
+            }
+        }
+    }
+
+    pub fn read_unlock(&self) {
+        if self.state.fetch_sub(1, Ordering::Release) == 1 {
+            let _ = futex_wake(&self.state, usize::MAX);
+        }
+    }
+
+    pub fn write(&self) {
+        self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+        loop {
+            match self
+                .state
+                .compare_exchange(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(state) => {
+                    let _ = futex_wait(&self.state, state, None);
+                }
+            }
+        }
+        self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn write_unlock(&self) {
+        self.state.store(0, Ordering::Release);
+        let _ = futex_wake(&self.state, usize::MAX);
+    }
+}
+
+pub struct Barrier {
+    count: AtomicU32,
+    /// bumped when all threads have arrived
+    generation: AtomicU32,
+    threads: u32,
+}
+
+impl Barrier {
+    pub const fn new(threads: u32) -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            generation: AtomicU32::new(0),
+            threads,
+        }
+    }
+
+    /// Return true in exactly one of the threads.
+    pub fn wait(&self) -> bool {
+        let generation = self.generation.load(Ordering::Acquire);
+        if self.count.fetch_add(1, Ordering::AcqRel) + 1 == self.threads {
+            self.count.store(0, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+            let _ = futex_wake(&self.generation, usize::MAX);
+            return true;
+        }
+        while self.generation.load(Ordering::Acquire) == generation {
+            let _ = futex_wait(&self.generation, generation, None);
+        }
+        false
+    }
+}
+
+const INCOMPLETE: u32 = 0;
+const RUNNING: u32 = 1;
+/// running and there are waiters
+const RUNNING_WAITED: u32 = 2;
+const COMPLETE: u32 = 3;
+
+/// Run an initialization once, the other callers wait until it is done.
+pub struct Once {
+    state: AtomicU32,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(INCOMPLETE),
+        }
+    }
+
+    pub fn call_once<F: FnOnce()>(&self, f: F) {
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => {
+                f();
+                if self.state.swap(COMPLETE, Ordering::Release) == RUNNING_WAITED {
+                    let _ = futex_wake(&self.state, usize::MAX);
+                }
+            }
+            Err(_) => loop {
+                match self.state.load(Ordering::Acquire) {
+                    COMPLETE => return,
+                    RUNNING => {
+                        let _ = self.state.compare_exchange(
+                            RUNNING,
+                            RUNNING_WAITED,
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                        );
+                    }
+                    _ => {
+                        let _ = futex_wait(&self.state, RUNNING_WAITED, None);
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+}
+
+/// A value initialized by the first caller of `get_or_init`.
+pub struct OnceCell<T> {
+    once: Once,
+    value: UnsafeCell<Option<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        // SAFETY: the value is only written by the one thread running `call_once`
+        self.once
+            .call_once(|| unsafe { *self.value.get() = Some(f()) });
+        unsafe { (*self.value.get()).as_ref().unwrap() }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            unsafe { (*self.value.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+}
@@ -9,11 +9,12 @@ pub const VIRTGPU_XRES: u32 = 1280;
 pub const VIRTGPU_YRES: u32 = 800;
 pub const VIRTGPU_LEN: usize = (VIRTGPU_XRES * VIRTGPU_YRES * 4) as usize;
 
-pub fn framebuffer() -> isize {
-    sys_framebuffer()
+/// Map the framebuffer into current process, return its address.
+pub fn framebuffer() -> SysResult<usize> {
+    check(sys_framebuffer())
 }
-pub fn framebuffer_flush() -> isize {
-    sys_framebuffer_flush()
+pub fn framebuffer_flush() {
+    sys_framebuffer_flush();
 }
 
 pub struct Display {
@@ -23,7 +24,7 @@ pub struct Display {
 
 impl Display {
     pub fn new(size: Size) -> Self {
-        let fb_ptr = framebuffer() as *mut u8;
+        let fb_ptr = framebuffer().unwrap() as *mut u8;
         let fb = unsafe { core::slice::from_raw_parts_mut(fb_ptr, VIRTGPU_LEN as usize) };
         Self { size, fb }
     }
@@ -74,6 +75,12 @@ pub fn event_get() -> Option<InputEvent> {
     }
 }
 
+/// Open the input events as a file, each read returns whole events of 8
+/// bytes, to be made into `InputEvent`s by `u64::from_le_bytes`.
+pub fn event_open() -> SysResult<usize> {
+    check(sys_event_open())
+}
+
 pub fn key_pressed() -> bool {
     if sys_key_pressed() == 1 {
         true
@@ -0,0 +1,69 @@
+use super::*;
+
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_RMID: usize = 0;
+
+pub fn shmget(key: usize, size: usize) -> SysResult<usize> {
+    check(sys_shmget(key, size))
+}
+/// Return the address the segment is attached at.
+pub fn shmat(shm_id: usize, addr: usize) -> SysResult<usize> {
+    check(sys_shmat(shm_id, addr))
+}
+pub fn shmdt(addr: usize) -> SysResult<()> {
+    check(sys_shmdt(addr)).map(drop)
+}
+pub fn shmctl(shm_id: usize, cmd: usize) -> SysResult<()> {
+    check(sys_shmctl(shm_id, cmd)).map(drop)
+}
+
+/// Priorities of messages are below it.
+pub const MQ_PRIO_MAX: usize = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MqAttr {
+    /// 1 if the descriptor is non-blocking
+    pub flags: usize,
+    pub max_msg: usize,
+    pub msg_size: usize,
+    pub cur_msgs: usize,
+}
+
+impl MqAttr {
+    pub fn new(max_msg: usize, msg_size: usize) -> Self {
+        Self {
+            max_msg,
+            msg_size,
+            ..Default::default()
+        }
+    }
+}
+
+/// Open the message queue `name` ("/..." ending with '\0'), `attr` sets the
+/// limits of a queue created with `OpenFlags::CREATE`. Return its fd.
+pub fn mq_open(name: &str, flags: OpenFlags, attr: Option<&MqAttr>) -> SysResult<usize> {
+    check(sys_mq_open(name, flags.bits, attr))
+}
+/// The queue goes away once the last fd of it is closed.
+pub fn mq_unlink(name: &str) -> SysResult<()> {
+    check(sys_mq_unlink(name)).map(drop)
+}
+/// Fail with `EAGAIN` instead of blocking if the queue is full and opened
+/// with `OpenFlags::NONBLOCK`.
+pub fn mq_send(fd: usize, msg: &[u8], prio: usize) -> SysResult<()> {
+    check(sys_mq_send(fd, msg, prio)).map(drop)
+}
+/// Receive the oldest message of the highest priority, return its length.
+/// `buf` must hold `msg_size` bytes. Fail with `EAGAIN` instead of blocking
+/// if the queue is empty and opened with `OpenFlags::NONBLOCK`.
+pub fn mq_receive(fd: usize, buf: &mut [u8], prio: Option<&mut usize>) -> SysResult<usize> {
+    check(sys_mq_receive(
+        fd,
+        buf,
+        prio.map_or(core::ptr::null_mut(), |prio| prio as *mut _),
+    ))
+}
+pub fn mq_getattr(fd: usize, attr: &mut MqAttr) -> SysResult<()> {
+    check(sys_mq_getattr(fd, attr)).map(drop)
+}
@@ -6,13 +6,21 @@
 
 #[macro_use]
 pub mod console;
+mod errno;
 mod file;
+pub mod futex;
 mod io;
+mod ipc;
 mod lang_items;
 mod net;
+mod poll;
+mod resource;
+mod signal;
 mod sync;
 mod syscall;
 mod task;
+mod time;
+mod tty;
 
 extern crate alloc;
 #[macro_use]
@@ -20,12 +28,19 @@ extern crate bitflags;
 
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+pub use errno::*;
 pub use file::*;
 pub use io::*;
+pub use ipc::*;
 pub use net::*;
+pub use poll::*;
+pub use resource::*;
+pub use signal::*;
 pub use sync::*;
 use syscall::*;
 pub use task::*;
+pub use time::*;
+pub use tty::*;
 
 const USER_HEAP_SIZE: usize = 32768;
 
@@ -60,7 +75,7 @@ pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
             .unwrap(),
         );
     }
-    exit(main(argc, v.as_slice()));
+    exit_group(main(argc, v.as_slice()));
 }
 
 #[linkage = "weak"]
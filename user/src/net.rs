@@ -1,13 +1,92 @@
 use super::*;
 
-pub fn connect(ip: u32, sport: u16, dport: u16) -> isize {
-    sys_connect(ip, sport, dport)
+pub fn connect(ip: u32, sport: u16, dport: u16) -> SysResult<usize> {
+    check(sys_connect(ip, sport, dport))
 }
 
-pub fn listen(sport: u16) -> isize {
-    sys_listen(sport)
+pub fn listen(sport: u16) -> SysResult<usize> {
+    check(sys_listen(sport))
 }
 
-pub fn accept(socket_fd: usize) -> isize {
-    sys_accept(socket_fd)
+pub fn accept(socket_fd: usize) -> SysResult<usize> {
+    check(sys_accept(socket_fd, 0))
+}
+/// Fail with `EAGAIN` unless a connection request has come in already.
+pub fn accept_nonblock(socket_fd: usize) -> SysResult<usize> {
+    check(sys_accept(socket_fd, OpenFlags::NONBLOCK.bits()))
+}
+
+pub const AF_UNIX: usize = 1;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+/// Fds passed by one message at most.
+pub const SCM_MAX_FD: usize = 16;
+
+/// Data and fds of `sendmsg` and `recvmsg`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MsgHdr {
+    pub buf: *mut u8,
+    pub len: usize,
+    pub fds: *mut usize,
+    /// set to the number of fds received by `recvmsg`
+    pub fds_len: usize,
+}
+
+impl MsgHdr {
+    pub fn new(buf: &mut [u8], fds: &mut [usize]) -> Self {
+        Self {
+            buf: buf.as_mut_ptr(),
+            len: buf.len(),
+            fds: fds.as_mut_ptr(),
+            fds_len: fds.len(),
+        }
+    }
+}
+
+/// Only `AF_UNIX` is supported.
+pub fn socket(domain: usize, type_: usize) -> SysResult<usize> {
+    check(sys_socket(domain, type_))
+}
+/// Two unix domain sockets connected to each other.
+pub fn socketpair(domain: usize, type_: usize, sv: &mut [usize]) -> SysResult<()> {
+    check(sys_socketpair(domain, type_, sv)).map(drop)
+}
+/// Name a unix domain socket by the file `path` ending with '\0'.
+pub fn bind(fd: usize, path: &str) -> SysResult<()> {
+    check(sys_bind(fd, path)).map(drop)
+}
+pub fn unix_listen(fd: usize, backlog: usize) -> SysResult<()> {
+    check(sys_unix_listen(fd, backlog)).map(drop)
+}
+/// Block until a connection comes, return the fd of this end.
+pub fn unix_accept(fd: usize) -> SysResult<usize> {
+    check(sys_unix_accept(fd))
+}
+/// Connect a stream to a listening socket, or set the destination of a datagram socket.
+pub fn unix_connect(fd: usize, path: &str) -> SysResult<()> {
+    check(sys_unix_connect(fd, path)).map(drop)
+}
+/// Send to the socket bound to `path`, or to the peer if `path` is `None`.
+pub fn sendto(fd: usize, buf: &[u8], path: Option<&str>) -> SysResult<usize> {
+    check(sys_sendto(fd, buf, path))
+}
+/// The name of the sender, if any, is written to `from` with a '\0' at the end.
+pub fn recvfrom(fd: usize, buf: &mut [u8], from: &mut [u8]) -> SysResult<usize> {
+    check(sys_recvfrom(fd, buf, from))
+}
+/// Send `buf` with `fds` attached.
+pub fn sendmsg(fd: usize, buf: &[u8], fds: &[usize]) -> SysResult<usize> {
+    let msg = MsgHdr {
+        buf: buf.as_ptr() as *mut u8,
+        len: buf.len(),
+        fds: fds.as_ptr() as *mut usize,
+        fds_len: fds.len(),
+    };
+    check(sys_sendmsg(fd, &msg))
+}
+/// Return the length of the data received, `msg.fds_len` is set to the
+/// number of fds received.
+pub fn recvmsg(fd: usize, msg: &mut MsgHdr) -> SysResult<usize> {
+    check(sys_recvmsg(fd, msg))
 }
@@ -0,0 +1,129 @@
+use super::*;
+
+pub const POLLIN: i16 = 0x001;
+pub const POLLPRI: i16 = 0x002;
+pub const POLLOUT: i16 = 0x004;
+pub const POLLERR: i16 = 0x008;
+pub const POLLHUP: i16 = 0x010;
+pub const POLLNVAL: i16 = 0x020;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PollFd {
+    /// skipped if negative
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: i16) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        }
+    }
+}
+
+/// No timeout if negative.
+fn timeout_spec(timeout_ms: isize) -> Option<TimeSpec> {
+    if timeout_ms < 0 {
+        None
+    } else {
+        Some(TimeSpec::from_us(timeout_ms as usize * 1000))
+    }
+}
+
+/// Wait up to `timeout_ms` ms for the events of `fds`, forever if it is
+/// negative. Return the number of fds with `revents` set, 0 on timeout.
+/// `POLLERR`, `POLLHUP` and `POLLNVAL` are reported without asking.
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> SysResult<usize> {
+    check(sys_ppoll(fds, timeout_spec(timeout_ms).as_ref()))
+}
+
+pub const FD_SETSIZE: usize = 1024;
+const FD_SET_BITS: usize = usize::BITS as usize;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FdSet([usize; FD_SETSIZE / FD_SET_BITS]);
+
+impl FdSet {
+    pub fn new() -> Self {
+        Self([0; FD_SETSIZE / FD_SET_BITS])
+    }
+    pub fn set(&mut self, fd: usize) {
+        self.0[fd / FD_SET_BITS] |= 1 << (fd % FD_SET_BITS);
+    }
+    pub fn clear(&mut self, fd: usize) {
+        self.0[fd / FD_SET_BITS] &= !(1 << (fd % FD_SET_BITS));
+    }
+    pub fn is_set(&self, fd: usize) -> bool {
+        self.0[fd / FD_SET_BITS] & (1 << (fd % FD_SET_BITS)) != 0
+    }
+}
+
+impl Default for FdSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wait up to `timeout_ms` ms for fds below `nfds`, forever if it is
+/// negative. The sets are left with the fds ready, return how many there
+/// are in all of them, 0 on timeout.
+pub fn select(
+    nfds: usize,
+    readfds: Option<&mut FdSet>,
+    writefds: Option<&mut FdSet>,
+    exceptfds: Option<&mut FdSet>,
+    timeout_ms: isize,
+) -> SysResult<usize> {
+    let set_ptr = |set: Option<&mut FdSet>| set.map_or(core::ptr::null_mut(), |set| set as *mut _);
+    check(sys_pselect6(
+        nfds,
+        set_ptr(readfds),
+        set_ptr(writefds),
+        set_ptr(exceptfds),
+        timeout_spec(timeout_ms).as_ref(),
+    ))
+}
+
+pub const EPOLLIN: u32 = POLLIN as u32;
+pub const EPOLLPRI: u32 = POLLPRI as u32;
+pub const EPOLLOUT: u32 = POLLOUT as u32;
+pub const EPOLLERR: u32 = POLLERR as u32;
+pub const EPOLLHUP: u32 = POLLHUP as u32;
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    /// handed back as it is with the events
+    pub data: u64,
+}
+
+impl EpollEvent {
+    pub fn new(events: u32, data: u64) -> Self {
+        Self { events, data }
+    }
+}
+
+pub fn epoll_create() -> SysResult<usize> {
+    check(sys_epoll_create1(0))
+}
+/// `event` is not needed by `EPOLL_CTL_DEL`. A file leaves the interest
+/// list once all its fds are closed.
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: Option<&EpollEvent>) -> SysResult<()> {
+    check(sys_epoll_ctl(epfd, op, fd, event)).map(drop)
+}
+/// Wait up to `timeout_ms` ms, forever if it is negative, return the
+/// number of `events` filled, 0 on timeout.
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout_ms: isize) -> SysResult<usize> {
+    check(sys_epoll_pwait(epfd, events, timeout_ms))
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+/// CPU time in seconds, SIGXCPU at the soft limit and SIGKILL at the hard one.
+pub const RLIMIT_CPU: usize = 0;
+/// Number of children not reaped yet.
+pub const RLIMIT_NPROC: usize = 6;
+/// Number of open files.
+pub const RLIMIT_NOFILE: usize = 7;
+/// Size of the address space in bytes.
+pub const RLIMIT_AS: usize = 9;
+/// CPU time in microseconds a realtime thread may use without blocking.
+pub const RLIMIT_RTTIME: usize = 15;
+/// Number of threads.
+pub const RLIMIT_NTHREAD: usize = 16;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
+/// Clock ticks per second of `times`.
+pub const CLK_TCK: usize = 100;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn as_ms(&self) -> usize {
+        self.sec * 1000 + self.usec / 1000
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RUsage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tms {
+    pub tms_utime: usize,
+    pub tms_stime: usize,
+    pub tms_cutime: usize,
+    pub tms_cstime: usize,
+}
+
+pub fn getrlimit(resource: usize, rlimit: &mut RLimit) -> SysResult<()> {
+    check(sys_getrlimit(resource, rlimit as *mut _)).map(drop)
+}
+/// The hard limit can only be lowered.
+pub fn setrlimit(resource: usize, rlimit: &RLimit) -> SysResult<()> {
+    check(sys_setrlimit(resource, rlimit as *const _)).map(drop)
+}
+pub fn getrusage(who: isize, usage: &mut RUsage) -> SysResult<()> {
+    check(sys_getrusage(who, usage as *mut _)).map(drop)
+}
+/// Return clock ticks elapsed since boot.
+pub fn times(tms: &mut Tms) -> SysResult<usize> {
+    check(sys_times(tms as *mut _))
+}
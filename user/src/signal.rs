@@ -0,0 +1,120 @@
+use super::*;
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGSTKFLT: i32 = 16;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+pub const SIGTTIN: i32 = 21;
+pub const SIGTTOU: i32 = 22;
+pub const SIGURG: i32 = 23;
+pub const SIGXCPU: i32 = 24;
+pub const SIGXFSZ: i32 = 25;
+pub const SIGVTALRM: i32 = 26;
+pub const SIGPROF: i32 = 27;
+pub const SIGWINCH: i32 = 28;
+pub const SIGIO: i32 = 29;
+pub const SIGPWR: i32 = 30;
+pub const SIGSYS: i32 = 31;
+
+/// Apply the default action of the signal.
+pub const SIG_DFL: usize = 0;
+/// Ignore the signal.
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    /// Signal `n` is represented by bit `1 << n`.
+    pub struct SignalFlags: u32 {
+        const SIGHUP    = 1 << SIGHUP;
+        const SIGINT    = 1 << SIGINT;
+        const SIGQUIT   = 1 << SIGQUIT;
+        const SIGILL    = 1 << SIGILL;
+        const SIGTRAP   = 1 << SIGTRAP;
+        const SIGABRT   = 1 << SIGABRT;
+        const SIGBUS    = 1 << SIGBUS;
+        const SIGFPE    = 1 << SIGFPE;
+        const SIGKILL   = 1 << SIGKILL;
+        const SIGUSR1   = 1 << SIGUSR1;
+        const SIGSEGV   = 1 << SIGSEGV;
+        const SIGUSR2   = 1 << SIGUSR2;
+        const SIGPIPE   = 1 << SIGPIPE;
+        const SIGALRM   = 1 << SIGALRM;
+        const SIGTERM   = 1 << SIGTERM;
+        const SIGSTKFLT = 1 << SIGSTKFLT;
+        const SIGCHLD   = 1 << SIGCHLD;
+        const SIGCONT   = 1 << SIGCONT;
+        const SIGSTOP   = 1 << SIGSTOP;
+        const SIGTSTP   = 1 << SIGTSTP;
+        const SIGTTIN   = 1 << SIGTTIN;
+        const SIGTTOU   = 1 << SIGTTOU;
+        const SIGURG    = 1 << SIGURG;
+        const SIGXCPU   = 1 << SIGXCPU;
+        const SIGXFSZ   = 1 << SIGXFSZ;
+        const SIGVTALRM = 1 << SIGVTALRM;
+        const SIGPROF   = 1 << SIGPROF;
+        const SIGWINCH  = 1 << SIGWINCH;
+        const SIGIO     = 1 << SIGIO;
+        const SIGPWR    = 1 << SIGPWR;
+        const SIGSYS    = 1 << SIGSYS;
+    }
+}
+
+/// A handler is called with the signal number as its only argument
+/// and must finish with `sigreturn`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> SysResult<()> {
+    check(sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |a| a),
+        old_action.map_or(core::ptr::null_mut(), |a| a),
+    ))
+    .map(drop)
+}
+
+/// Replace the signal mask, return the old one.
+pub fn sigprocmask(mask: u32) -> u32 {
+    sys_sigprocmask(mask) as u32
+}
+
+/// Only return if not called by a signal handler.
+pub fn sigreturn() -> Errno {
+    match check(sys_sigreturn()) {
+        Err(errno) => errno,
+        Ok(_) => unreachable!(),
+    }
+}
@@ -1,28 +1,64 @@
 use super::*;
 
-pub fn mutex_create() -> isize {
-    sys_mutex_create(false)
+/// Refuse locks and downs which may deadlock in current process.
+pub fn enable_deadlock_detect(enabled: bool) -> SysResult<()> {
+    check(sys_enable_deadlock_detect(enabled as usize)).map(drop)
 }
-pub fn mutex_blocking_create() -> isize {
-    sys_mutex_create(true)
+
+/// Print who waits for whom in current process on the kernel console.
+pub fn dump_wait_for_graph() {
+    sys_dump_wait_for_graph();
+}
+
+const MUTEX_SPIN: usize = 0;
+const MUTEX_BLOCKING: usize = 1;
+const MUTEX_PI: usize = 2;
+
+pub fn mutex_create() -> SysResult<usize> {
+    check(sys_mutex_create(MUTEX_SPIN))
+}
+pub fn mutex_blocking_create() -> SysResult<usize> {
+    check(sys_mutex_create(MUTEX_BLOCKING))
 }
-pub fn mutex_lock(mutex_id: usize) {
-    sys_mutex_lock(mutex_id);
+/// A blocking mutex whose owner inherits the realtime priority of its waiters.
+pub fn mutex_pi_create() -> SysResult<usize> {
+    check(sys_mutex_create(MUTEX_PI))
+}
+/// Fail with `EDEADLK` if refused by the deadlock detector.
+pub fn mutex_lock(mutex_id: usize) -> SysResult<()> {
+    check(sys_mutex_lock(mutex_id)).map(drop)
+}
+/// Fail with `ETIMEDOUT` if the mutex is not taken within `timeout_us` microseconds.
+pub fn mutex_lock_timeout(mutex_id: usize, timeout_us: usize) -> SysResult<()> {
+    check(sys_mutex_lock_timeout(mutex_id, timeout_us)).map(drop)
 }
 pub fn mutex_unlock(mutex_id: usize) {
     sys_mutex_unlock(mutex_id);
 }
-pub fn semaphore_create(res_count: usize) -> isize {
-    sys_semaphore_create(res_count)
+/// Fail with `EBUSY` if the mutex is locked or waited for, its id may be reused afterwards.
+pub fn mutex_destroy(mutex_id: usize) -> SysResult<()> {
+    check(sys_mutex_destroy(mutex_id)).map(drop)
+}
+pub fn semaphore_create(res_count: usize) -> SysResult<usize> {
+    check(sys_semaphore_create(res_count))
 }
 pub fn semaphore_up(sem_id: usize) {
     sys_semaphore_up(sem_id);
 }
-pub fn semaphore_down(sem_id: usize) {
-    sys_semaphore_down(sem_id);
+/// Fail with `EDEADLK` if refused by the deadlock detector.
+pub fn semaphore_down(sem_id: usize) -> SysResult<()> {
+    check(sys_semaphore_down(sem_id)).map(drop)
+}
+/// Fail with `ETIMEDOUT` if the semaphore is not acquired within `timeout_us` microseconds.
+pub fn semaphore_down_timeout(sem_id: usize, timeout_us: usize) -> SysResult<()> {
+    check(sys_semaphore_down_timeout(sem_id, timeout_us)).map(drop)
 }
-pub fn condvar_create() -> isize {
-    sys_condvar_create()
+/// Fail with `EBUSY` if the semaphore is waited for.
+pub fn semaphore_destroy(sem_id: usize) -> SysResult<()> {
+    check(sys_semaphore_destroy(sem_id)).map(drop)
+}
+pub fn condvar_create() -> SysResult<usize> {
+    check(sys_condvar_create())
 }
 pub fn condvar_signal(condvar_id: usize) {
     sys_condvar_signal(condvar_id);
@@ -30,3 +66,33 @@ pub fn condvar_signal(condvar_id: usize) {
 pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
     sys_condvar_wait(condvar_id, mutex_id);
 }
+/// Fail with `ETIMEDOUT` on timeout, the mutex is held again in either case.
+pub fn condvar_wait_timeout(
+    condvar_id: usize,
+    mutex_id: usize,
+    timeout_us: usize,
+) -> SysResult<()> {
+    check(sys_condvar_wait_timeout(condvar_id, mutex_id, timeout_us)).map(drop)
+}
+/// Fail with `EBUSY` if the condvar is waited for.
+pub fn condvar_destroy(condvar_id: usize) -> SysResult<()> {
+    check(sys_condvar_destroy(condvar_id)).map(drop)
+}
+/// A reader-writer lock preferring writers.
+pub fn rwlock_create() -> SysResult<usize> {
+    check(sys_rwlock_create())
+}
+pub fn rwlock_read(rwlock_id: usize) {
+    sys_rwlock_read(rwlock_id);
+}
+pub fn rwlock_write(rwlock_id: usize) {
+    sys_rwlock_write(rwlock_id);
+}
+/// Release a read or write lock, fail with `EPERM` if it is not locked.
+pub fn rwlock_unlock(rwlock_id: usize) -> SysResult<()> {
+    check(sys_rwlock_unlock(rwlock_id)).map(drop)
+}
+/// Fail with `EBUSY` if the lock is held or waited for.
+pub fn rwlock_destroy(rwlock_id: usize) -> SysResult<()> {
+    check(sys_rwlock_destroy(rwlock_id)).map(drop)
+}
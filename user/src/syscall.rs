@@ -1,4 +1,13 @@
+use super::{
+    EpollEvent, FdSet, MqAttr, MsgHdr, PollFd, RLimit, RUsage, SignalAction, TimeSpec, Tms,
+};
+
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
 const SYSCALL_DUP: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_IOCTL: usize = 28;
 const SYSCALL_CONNECT: usize = 29;
 const SYSCALL_LISTEN: usize = 30;
 const SYSCALL_ACCEPT: usize = 31;
@@ -7,31 +16,85 @@ const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_PSELECT6: usize = 72;
+const SYSCALL_PPOLL: usize = 73;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_FUTEX: usize = 98;
 const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_CLOCK_SETTIME: usize = 112;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_SEND: usize = 182;
+const SYSCALL_MQ_RECEIVE: usize = 183;
+const SYSCALL_MQ_GETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_UNIX_LISTEN: usize = 201;
+const SYSCALL_UNIX_ACCEPT: usize = 202;
+const SYSCALL_UNIX_CONNECT: usize = 203;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SENDMSG: usize = 211;
+const SYSCALL_RECVMSG: usize = 212;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_DUMP_WAIT_FOR_GRAPH: usize = 470;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
+const SYSCALL_THREAD_DETACH: usize = 1003;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_LOCK_TIMEOUT: usize = 1013;
+const SYSCALL_MUTEX_DESTROY: usize = 1014;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_DOWN_TIMEOUT: usize = 1023;
+const SYSCALL_SEMAPHORE_DESTROY: usize = 1024;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_WAIT_TIMEOUT: usize = 1033;
+const SYSCALL_CONDVAR_DESTROY: usize = 1034;
+const SYSCALL_RWLOCK_CREATE: usize = 1040;
+const SYSCALL_RWLOCK_READ: usize = 1041;
+const SYSCALL_RWLOCK_WRITE: usize = 1042;
+const SYSCALL_RWLOCK_UNLOCK: usize = 1043;
+const SYSCALL_RWLOCK_DESTROY: usize = 1044;
 const SYSCALL_FRAMEBUFFER: usize = 2000;
 const SYSCALL_FRAMEBUFFER_FLUSH: usize = 2001;
 const SYSCALL_EVENT_GET: usize = 3000;
 const SYSCALL_KEY_PRESSED: usize = 3001;
+const SYSCALL_EVENT_OPEN: usize = 3002;
 
 fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
@@ -47,10 +110,59 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+/// For the few syscalls taking more than 3 arguments.
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
+pub fn sys_epoll_create1(flags: usize) -> isize {
+    syscall(SYSCALL_EPOLL_CREATE1, [flags, 0, 0])
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: Option<&EpollEvent>) -> isize {
+    let event = event.map_or(core::ptr::null(), |event| event as *const _);
+    syscall6(SYSCALL_EPOLL_CTL, [epfd, op, fd, event as usize, 0, 0])
+}
+
+pub fn sys_epoll_pwait(epfd: usize, events: &mut [EpollEvent], timeout: isize) -> isize {
+    syscall6(
+        SYSCALL_EPOLL_PWAIT,
+        [
+            epfd,
+            events.as_mut_ptr() as usize,
+            events.len(),
+            timeout as usize,
+            0,
+            0,
+        ],
+    )
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, cmd, arg])
+}
+
 pub fn sys_connect(dest: u32, sport: u16, dport: u16) -> isize {
     syscall(
         SYSCALL_CONNECT,
@@ -63,8 +175,8 @@ pub fn sys_listen(sport: u16) -> isize {
     syscall(SYSCALL_LISTEN, [sport as usize, 0, 0])
 }
 
-pub fn sys_accept(socket_fd: usize) -> isize {
-    syscall(SYSCALL_ACCEPT, [socket_fd, 0, 0])
+pub fn sys_accept(socket_fd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_ACCEPT, [socket_fd, flags as usize, 0])
 }
 
 pub fn sys_open(path: &str, flags: u32) -> isize {
@@ -75,8 +187,11 @@ pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+pub fn sys_pipe(pipe: &mut [usize], flags: u32) -> isize {
+    syscall(
+        SYSCALL_PIPE,
+        [pipe.as_mut_ptr() as usize, flags as usize, 0],
+    )
 }
 
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
@@ -90,21 +205,139 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut FdSet,
+    writefds: *mut FdSet,
+    exceptfds: *mut FdSet,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    let timeout = timeout.map_or(core::ptr::null(), |timeout| timeout as *const _);
+    syscall6(
+        SYSCALL_PSELECT6,
+        [
+            nfds,
+            readfds as usize,
+            writefds as usize,
+            exceptfds as usize,
+            timeout as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(core::ptr::null(), |timeout| timeout as *const _);
+    syscall(
+        SYSCALL_PPOLL,
+        [fds.as_mut_ptr() as usize, fds.len(), timeout as usize],
+    )
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
     panic!("sys_exit never returns!");
 }
 
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT_GROUP, [exit_code as usize, 0, 0]);
+    panic!("sys_exit_group never returns!");
+}
+
+pub fn sys_futex(
+    uaddr: *const u32,
+    op: usize,
+    val: usize,
+    val2: usize,
+    uaddr2: *const u32,
+) -> isize {
+    syscall6(
+        SYSCALL_FUTEX,
+        [uaddr as usize, op, val, val2, uaddr2 as usize, 0],
+    )
+}
+
 pub fn sys_sleep(sleep_ms: usize) -> isize {
     syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
 }
 
+pub fn sys_clock_settime(clock_id: usize, tp: *const TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_SETTIME, [clock_id, tp as usize, 0])
+}
+
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock_id, tp as usize, 0])
+}
+
+pub fn sys_clock_nanosleep(clock_id: usize, flags: usize, req: *const TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_NANOSLEEP, [clock_id, flags, req as usize])
+}
+
+pub fn sys_sched_setscheduler(tid: usize, policy: usize, priority: usize) -> isize {
+    syscall(SYSCALL_SCHED_SETSCHEDULER, [tid, policy, priority])
+}
+
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
-pub fn sys_kill(pid: usize, signal: i32) -> isize {
-    syscall(SYSCALL_KILL, [pid, signal as usize, 0])
+pub fn sys_kill(pid: isize, signum: i32) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signum as usize, 0])
+}
+
+pub fn sys_sigaction(
+    signum: i32,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum as usize, action as usize, old_action as usize],
+    )
+}
+
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+}
+
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
+pub fn sys_setpriority(tid: usize, nice: isize) -> isize {
+    syscall(SYSCALL_SETPRIORITY, [tid, nice as usize, 0])
+}
+
+pub fn sys_times(tms: *mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, [tms as usize, 0, 0])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
+pub fn sys_getsid(pid: usize) -> isize {
+    syscall(SYSCALL_GETSID, [pid, 0, 0])
+}
+
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0, 0, 0])
+}
+
+pub fn sys_getrlimit(resource: usize, rlimit: *mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlimit as usize, 0])
+}
+
+pub fn sys_setrlimit(resource: usize, rlimit: *const RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlimit as usize, 0])
+}
+
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    syscall(SYSCALL_GETRUSAGE, [who as usize, usage as usize, 0])
 }
 
 pub fn sys_get_time() -> isize {
@@ -115,6 +348,116 @@ pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_mq_open(name: &str, flags: u32, attr: Option<&MqAttr>) -> isize {
+    let attr = attr.map_or(core::ptr::null(), |attr| attr as *const _);
+    syscall(
+        SYSCALL_MQ_OPEN,
+        [name.as_ptr() as usize, flags as usize, attr as usize],
+    )
+}
+
+pub fn sys_mq_unlink(name: &str) -> isize {
+    syscall(SYSCALL_MQ_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_mq_send(fd: usize, msg: &[u8], prio: usize) -> isize {
+    syscall6(
+        SYSCALL_MQ_SEND,
+        [fd, msg.as_ptr() as usize, msg.len(), prio, 0, 0],
+    )
+}
+
+pub fn sys_mq_receive(fd: usize, buf: &mut [u8], prio: *mut usize) -> isize {
+    syscall6(
+        SYSCALL_MQ_RECEIVE,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            prio as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_mq_getattr(fd: usize, attr: &mut MqAttr) -> isize {
+    syscall(SYSCALL_MQ_GETATTR, [fd, attr as *mut _ as usize, 0])
+}
+
+pub fn sys_shmget(key: usize, size: usize) -> isize {
+    syscall(SYSCALL_SHMGET, [key, size, 0])
+}
+
+pub fn sys_shmctl(id: usize, cmd: usize) -> isize {
+    syscall(SYSCALL_SHMCTL, [id, cmd, 0])
+}
+
+pub fn sys_shmat(id: usize, addr: usize) -> isize {
+    syscall(SYSCALL_SHMAT, [id, addr, 0])
+}
+
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [addr, 0, 0])
+}
+
+pub fn sys_socket(domain: usize, type_: usize) -> isize {
+    syscall(SYSCALL_SOCKET, [domain, type_, 0])
+}
+
+pub fn sys_socketpair(domain: usize, type_: usize, sv: &mut [usize]) -> isize {
+    syscall(
+        SYSCALL_SOCKETPAIR,
+        [domain, type_, sv.as_mut_ptr() as usize],
+    )
+}
+
+pub fn sys_bind(fd: usize, path: &str) -> isize {
+    syscall(SYSCALL_BIND, [fd, path.as_ptr() as usize, 0])
+}
+
+pub fn sys_unix_listen(fd: usize, backlog: usize) -> isize {
+    syscall(SYSCALL_UNIX_LISTEN, [fd, backlog, 0])
+}
+
+pub fn sys_unix_accept(fd: usize) -> isize {
+    syscall(SYSCALL_UNIX_ACCEPT, [fd, 0, 0])
+}
+
+pub fn sys_unix_connect(fd: usize, path: &str) -> isize {
+    syscall(SYSCALL_UNIX_CONNECT, [fd, path.as_ptr() as usize, 0])
+}
+
+pub fn sys_sendto(fd: usize, buf: &[u8], path: Option<&str>) -> isize {
+    let path = path.map_or(core::ptr::null(), str::as_ptr);
+    syscall6(
+        SYSCALL_SENDTO,
+        [fd, buf.as_ptr() as usize, buf.len(), path as usize, 0, 0],
+    )
+}
+
+pub fn sys_recvfrom(fd: usize, buf: &mut [u8], from: &mut [u8]) -> isize {
+    syscall6(
+        SYSCALL_RECVFROM,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            from.as_mut_ptr() as usize,
+            from.len(),
+            0,
+        ],
+    )
+}
+
+pub fn sys_sendmsg(fd: usize, msg: &MsgHdr) -> isize {
+    syscall(SYSCALL_SENDMSG, [fd, msg as *const _ as usize, 0])
+}
+
+pub fn sys_recvmsg(fd: usize, msg: &mut MsgHdr) -> isize {
+    syscall(SYSCALL_RECVMSG, [fd, msg as *mut _ as usize, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
@@ -126,8 +469,16 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
     )
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, options])
+}
+
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    syscall(SYSCALL_ENABLE_DEADLOCK_DETECT, [enabled, 0, 0])
+}
+
+pub fn sys_dump_wait_for_graph() -> isize {
+    syscall(SYSCALL_DUMP_WAIT_FOR_GRAPH, [0, 0, 0])
 }
 
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
@@ -138,18 +489,30 @@ pub fn sys_gettid() -> isize {
     syscall(SYSCALL_GETTID, [0; 3])
 }
 
-pub fn sys_waittid(tid: usize) -> isize {
-    syscall(SYSCALL_WAITTID, [tid, 0, 0])
+pub fn sys_waittid(tid: isize, exit_code: *mut i32) -> isize {
+    syscall(SYSCALL_WAITTID, [tid as usize, exit_code as usize, 0])
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
-    syscall(SYSCALL_MUTEX_CREATE, [blocking as usize, 0, 0])
+pub fn sys_thread_detach(tid: usize) -> isize {
+    syscall(SYSCALL_THREAD_DETACH, [tid, 0, 0])
+}
+
+pub fn sys_mutex_create(kind: usize) -> isize {
+    syscall(SYSCALL_MUTEX_CREATE, [kind, 0, 0])
 }
 
 pub fn sys_mutex_lock(id: usize) -> isize {
     syscall(SYSCALL_MUTEX_LOCK, [id, 0, 0])
 }
 
+pub fn sys_mutex_lock_timeout(id: usize, timeout_us: usize) -> isize {
+    syscall(SYSCALL_MUTEX_LOCK_TIMEOUT, [id, timeout_us, 0])
+}
+
+pub fn sys_mutex_destroy(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_DESTROY, [id, 0, 0])
+}
+
 pub fn sys_mutex_unlock(id: usize) -> isize {
     syscall(SYSCALL_MUTEX_UNLOCK, [id, 0, 0])
 }
@@ -166,6 +529,14 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
     syscall(SYSCALL_SEMAPHORE_DOWN, [sem_id, 0, 0])
 }
 
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout_us: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DOWN_TIMEOUT, [sem_id, timeout_us, 0])
+}
+
+pub fn sys_semaphore_destroy(sem_id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DESTROY, [sem_id, 0, 0])
+}
+
 pub fn sys_condvar_create() -> isize {
     syscall(SYSCALL_CONDVAR_CREATE, [0, 0, 0])
 }
@@ -178,6 +549,37 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
 }
 
+pub fn sys_condvar_wait_timeout(condvar_id: usize, mutex_id: usize, timeout_us: usize) -> isize {
+    syscall(
+        SYSCALL_CONDVAR_WAIT_TIMEOUT,
+        [condvar_id, mutex_id, timeout_us],
+    )
+}
+
+pub fn sys_condvar_destroy(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_DESTROY, [condvar_id, 0, 0])
+}
+
+pub fn sys_rwlock_create() -> isize {
+    syscall(SYSCALL_RWLOCK_CREATE, [0; 3])
+}
+
+pub fn sys_rwlock_read(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_READ, [rwlock_id, 0, 0])
+}
+
+pub fn sys_rwlock_write(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_WRITE, [rwlock_id, 0, 0])
+}
+
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_UNLOCK, [rwlock_id, 0, 0])
+}
+
+pub fn sys_rwlock_destroy(rwlock_id: usize) -> isize {
+    syscall(SYSCALL_RWLOCK_DESTROY, [rwlock_id, 0, 0])
+}
+
 pub fn sys_framebuffer() -> isize {
     syscall(SYSCALL_FRAMEBUFFER, [0, 0, 0])
 }
@@ -193,3 +595,7 @@ pub fn sys_event_get() -> isize {
 pub fn sys_key_pressed() -> isize {
     syscall(SYSCALL_KEY_PRESSED, [0, 0, 0])
 }
+
+pub fn sys_event_open() -> isize {
+    syscall(SYSCALL_EVENT_OPEN, [0, 0, 0])
+}
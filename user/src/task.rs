@@ -1,8 +1,13 @@
 use super::*;
 
+/// Exit current thread, the process exits with its last thread.
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
+/// Exit current process with all its threads.
+pub fn exit_group(exit_code: i32) -> ! {
+    sys_exit_group(exit_code);
+}
 pub fn yield_() -> isize {
     sys_yield()
 }
@@ -12,72 +17,150 @@ pub fn get_time() -> isize {
 pub fn getpid() -> isize {
     sys_getpid()
 }
-pub fn fork() -> isize {
-    sys_fork()
+/// Return the pid of the child in the parent and 0 in the child.
+pub fn fork() -> SysResult<usize> {
+    check(sys_fork())
 }
-pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+/// Only return if `path` cannot be executed.
+pub fn exec(path: &str, args: &[*const u8]) -> Errno {
+    match check(sys_exec(path, args)) {
+        Err(errno) => errno,
+        Ok(_) => unreachable!(),
+    }
 }
 
-pub fn wait(exit_code: &mut i32) -> isize {
+pub fn wait(exit_code: &mut i32) -> SysResult<usize> {
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
+        match check(sys_waitpid(-1, exit_code as *mut _, 0)) {
+            Err(Errno::EAGAIN) => {
                 yield_();
             }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+            // ECHILD or a real pid
+            result => return result,
         }
     }
 }
 
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> SysResult<usize> {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
+        match check(sys_waitpid(pid as isize, exit_code as *mut _, 0)) {
+            Err(Errno::EAGAIN) => {
                 yield_();
             }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+            // ECHILD or a real pid
+            result => return result,
         }
     }
 }
 
-pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+/// Fail with `EAGAIN` if the child has not exited yet.
+pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> SysResult<usize> {
+    check(sys_waitpid(pid as isize, exit_code as *mut _, 0))
 }
 
-bitflags! {
-    pub struct SignalFlags: i32 {
-        const SIGINT    = 1 << 2;
-        const SIGILL    = 1 << 4;
-        const SIGABRT   = 1 << 6;
-        const SIGFPE    = 1 << 8;
-        const SIGSEGV   = 1 << 11;
-    }
+/// Also report stopped children, the status is then encoded as in POSIX.
+pub const WUNTRACED: usize = 2;
+
+/// Nonblocking wait which also reports stopped children, decode `status`
+/// with `wifstopped`, `wstopsig` and `wexitstatus`.
+pub fn waitpid_untraced(pid: isize, status: &mut i32) -> SysResult<usize> {
+    check(sys_waitpid(pid, status as *mut _, WUNTRACED))
+}
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f
+}
+pub fn wstopsig(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+pub fn wexitstatus(status: i32) -> i32 {
+    (status >> 8) as i8 as i32
 }
 
-pub fn kill(pid: usize, signal: i32) -> isize {
-    sys_kill(pid, signal)
+/// Send signal `signum` to process `pid`, 0 means the group of current process.
+pub fn kill(pid: usize, signum: i32) -> SysResult<()> {
+    check(sys_kill(pid as isize, signum)).map(drop)
+}
+/// Send signal `signum` to every process in group `pgid`.
+pub fn killpg(pgid: usize, signum: i32) -> SysResult<()> {
+    check(sys_kill(-(pgid as isize), signum)).map(drop)
+}
+
+/// 0 for `pid` means current process, 0 for `pgid` means the pid of the target.
+pub fn setpgid(pid: usize, pgid: usize) -> SysResult<()> {
+    check(sys_setpgid(pid, pgid)).map(drop)
+}
+pub fn getpgid(pid: usize) -> SysResult<usize> {
+    check(sys_getpgid(pid))
+}
+/// Return the id of the new session.
+pub fn setsid() -> SysResult<usize> {
+    check(sys_setsid())
+}
+pub fn getsid(pid: usize) -> SysResult<usize> {
+    check(sys_getsid(pid))
 }
 
 pub fn sleep(sleep_ms: usize) {
     sys_sleep(sleep_ms);
 }
 
-pub fn thread_create(entry: usize, arg: usize) -> isize {
-    sys_thread_create(entry, arg)
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_FIFO: usize = 1;
+pub const SCHED_RR: usize = 2;
+pub const SCHED_IDLE: usize = 5;
+
+/// Set the nice value (-20..=19) of a thread in current process.
+pub fn setpriority(tid: usize, nice: isize) -> SysResult<()> {
+    check(sys_setpriority(tid, nice)).map(drop)
+}
+/// `priority` is 1..=99 for `SCHED_FIFO` and `SCHED_RR`, 0 otherwise.
+pub fn sched_setscheduler(tid: usize, policy: usize, priority: usize) -> SysResult<()> {
+    check(sys_sched_setscheduler(tid, policy, priority)).map(drop)
+}
+
+/// Return the tid of the new thread.
+pub fn thread_create(entry: usize, arg: usize) -> SysResult<usize> {
+    check(sys_thread_create(entry, arg))
 }
 pub fn gettid() -> isize {
     sys_gettid()
 }
-pub fn waittid(tid: usize) -> isize {
-    loop {
-        match sys_waittid(tid) {
-            -2 => {
-                yield_();
-            }
-            exit_code => return exit_code,
-        }
+/// Block until thread `tid` exits and return its exit code, fail with
+/// `ESRCH` if it does not exist or is detached.
+pub fn waittid(tid: usize) -> SysResult<i32> {
+    // taken from `exit_code`, since a negative one looks like an error
+    let mut exit_code = 0;
+    check(sys_waittid(tid as isize, &mut exit_code as *mut _))?;
+    Ok(exit_code)
+}
+/// Block until any joinable thread exits and return its tid, fail with
+/// `ESRCH` if there is none.
+pub fn waittid_any(exit_code: &mut i32) -> SysResult<usize> {
+    check(sys_waittid(-1, exit_code as *mut _))
+}
+/// The thread is reaped as soon as it exits and cannot be waited for.
+pub fn thread_detach(tid: usize) -> SysResult<()> {
+    check(sys_thread_detach(tid)).map(drop)
+}
+
+/// Size of the thread-local block every thread starts with, zeroed.
+pub const TLS_SIZE: usize = 256;
+
+/// The thread-local block of current thread, which `tp` points to.
+pub fn tls_block() -> *mut usize {
+    let tp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) tp);
     }
+    tp as *mut usize
+}
+/// Read word `slot` of the thread-local block.
+pub fn tls_get(slot: usize) -> usize {
+    assert!(slot < TLS_SIZE / core::mem::size_of::<usize>());
+    unsafe { tls_block().add(slot).read_volatile() }
+}
+/// Write word `slot` of the thread-local block.
+pub fn tls_set(slot: usize, value: usize) {
+    assert!(slot < TLS_SIZE / core::mem::size_of::<usize>());
+    unsafe { tls_block().add(slot).write_volatile(value) }
 }
@@ -0,0 +1,54 @@
+use super::*;
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+
+/// `clock_nanosleep` flag, the request is an absolute time of the clock.
+pub const TIMER_ABSTIME: usize = 1;
+
+const NSEC_PER_SEC: usize = 1_000_000_000;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / 1_000_000,
+            nsec: us % 1_000_000 * 1000,
+        }
+    }
+    pub fn as_us(&self) -> usize {
+        self.sec * 1_000_000 + self.nsec / 1000
+    }
+    pub fn add(&self, other: &Self) -> Self {
+        let nsec = self.nsec + other.nsec;
+        Self {
+            sec: self.sec + other.sec + nsec / NSEC_PER_SEC,
+            nsec: nsec % NSEC_PER_SEC,
+        }
+    }
+}
+
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> SysResult<()> {
+    check(sys_clock_gettime(clock_id, tp as *mut _)).map(drop)
+}
+
+/// Only `CLOCK_REALTIME` can be set.
+pub fn clock_settime(clock_id: usize, tp: &TimeSpec) -> SysResult<()> {
+    check(sys_clock_settime(clock_id, tp as *const _)).map(drop)
+}
+
+pub fn clock_nanosleep(clock_id: usize, flags: usize, req: &TimeSpec) -> SysResult<()> {
+    check(sys_clock_nanosleep(clock_id, flags, req as *const _)).map(drop)
+}
+
+pub fn nanosleep(req: &TimeSpec) -> SysResult<()> {
+    clock_nanosleep(CLOCK_MONOTONIC, 0, req)
+}
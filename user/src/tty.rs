@@ -0,0 +1,81 @@
+use super::*;
+
+pub const TCGETS: usize = 0x5401;
+pub const TCSETS: usize = 0x5402;
+pub const TCSETSW: usize = 0x5403;
+pub const TCSETSF: usize = 0x5404;
+pub const TIOCGPGRP: usize = 0x540f;
+pub const TIOCSPGRP: usize = 0x5410;
+pub const TIOCGWINSZ: usize = 0x5413;
+pub const TIOCSWINSZ: usize = 0x5414;
+
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+pub const NCCS: usize = 19;
+
+pub const ICRNL: u32 = 0o400;
+pub const ISIG: u32 = 0o1;
+pub const ICANON: u32 = 0o2;
+pub const ECHO: u32 = 0o10;
+pub const ECHOE: u32 = 0o20;
+pub const ECHOK: u32 = 0o40;
+
+/// Terminal attributes, the same layout as `struct termios` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Termios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// Switch to raw mode: no line editing, echo, signals or CR translation.
+    pub fn make_raw(&mut self) {
+        self.c_iflag &= !ICRNL;
+        self.c_lflag &= !(ISIG | ICANON | ECHO | ECHOE | ECHOK);
+        self.c_cc[VMIN] = 1;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WinSize {
+    pub ws_row: u16,
+    pub ws_col: u16,
+    pub ws_xpixel: u16,
+    pub ws_ypixel: u16,
+}
+
+pub fn ioctl(fd: usize, cmd: usize, arg: usize) -> SysResult<usize> {
+    check(sys_ioctl(fd, cmd, arg))
+}
+pub fn tcgetattr(fd: usize, termios: &mut Termios) -> SysResult<()> {
+    ioctl(fd, TCGETS, termios as *mut _ as usize).map(drop)
+}
+pub fn tcsetattr(fd: usize, termios: &Termios) -> SysResult<()> {
+    ioctl(fd, TCSETS, termios as *const _ as usize).map(drop)
+}
+/// Foreground process group of the terminal behind `fd`.
+pub fn tcgetpgrp(fd: usize) -> SysResult<usize> {
+    let mut pgid = 0usize;
+    ioctl(fd, TIOCGPGRP, &mut pgid as *mut _ as usize)?;
+    Ok(pgid)
+}
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> SysResult<()> {
+    ioctl(fd, TIOCSPGRP, &pgid as *const _ as usize).map(drop)
+}
+pub fn get_winsize(fd: usize, winsize: &mut WinSize) -> SysResult<()> {
+    ioctl(fd, TIOCGWINSZ, winsize as *mut _ as usize).map(drop)
+}
+pub fn set_winsize(fd: usize, winsize: &WinSize) -> SysResult<()> {
+    ioctl(fd, TIOCSWINSZ, winsize as *const _ as usize).map(drop)
+}